@@ -6,6 +6,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         "src/network/protos/common.proto",
         "src/network/protos/datatransfer.proto",
         "src/network/protos/nodecontroller.proto",
+        "src/network/protos/jobservice.proto",
     ];
 
     let proto_dir = "src/network/protos";
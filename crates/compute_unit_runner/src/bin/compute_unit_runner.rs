@@ -1,6 +1,13 @@
 use compute_unit_runner::{
     data_tracker,
+    health::{
+        start_monitor_server,
+        RunnerHealth,
+        GRPC_SERVER_TASK,
+        IPC_SERVER_TASK,
+    },
     ipc,
+    metrics::RunnerMetrics,
     state_controller::StateController,
     stream::ChannelDataStream,
 };
@@ -8,7 +15,10 @@ use jiaoziflow::{
     core::db::NodeRepo,
     dbrepo::MongoRunDbRepo,
     network::datatransfer::data_stream_server::DataStreamServer,
-    utils::StdIntoAnyhowResult,
+    utils::{
+        health::TaskHealth,
+        StdIntoAnyhowResult,
+    },
 };
 use nodes_sdk::fs_cache::{
     FSCache,
@@ -36,6 +46,7 @@ use tonic::transport::Server;
 use nodes_sdk::monitor_tasks;
 use tokio_util::sync::CancellationToken;
 use tracing::{
+    error,
     info,
     Level,
 };
@@ -68,6 +79,9 @@ struct Args {
 
     #[arg(long, default_value = "0.0.0.0:80")]
     host_port: String,
+
+    #[arg(long, default_value = "0.0.0.0:9090")]
+    metrics_addr: String,
 }
 
 #[tokio::main(flavor = "multi_thread")]
@@ -81,14 +95,23 @@ async fn main() -> Result<()> {
     let mut join_set = JoinSet::new();
     let token = CancellationToken::new();
 
-    let fs_cache: Arc<dyn FileCache> = Arc::new(FSCache::new(
-        args.tmp_path.expect("compute node only support disk cache"),
-    ));
+    let tmp_path = args.tmp_path.expect("compute node only support disk cache");
+    let fs_cache: Arc<dyn FileCache> = Arc::new(FSCache::new(tmp_path.clone()));
+
+    let metrics = RunnerMetrics::new();
+    metrics.run_cache_usage(&mut join_set, token.clone(), &tmp_path);
 
     let db_repo = MongoRunDbRepo::new(&args.mongo_url).await?;
 
     let node = db_repo.get_node_by_name(&args.node_name).await?;
 
+    {
+        //publish changes made by other processes
+        let db_repo = db_repo.clone();
+        let token = token.clone();
+        join_set.spawn(async move { db_repo.watch(token).await });
+    }
+
     let mut program = MediaDataTracker::new(
         db_repo.clone(),
         &args.node_name,
@@ -96,20 +119,36 @@ async fn main() -> Result<()> {
         args.buf_size,
         node.up_nodes,
         node.outgoing_streams,
-    );
+        db_repo.notifier().clone(),
+    )
+    .set_metrics(metrics.clone());
     program.run_backend(&mut join_set, token.clone())?;
 
+    let tasks = TaskHealth::default();
+    let health = RunnerHealth::new(&program, tasks.clone());
     let program_safe = Arc::new(RwLock::new(program));
 
-    let server = ipc::start_ipc_server(&args.unix_socket_addr, program_safe.clone()).unwrap();
+    let server = ipc::start_ipc_server(
+        &args.unix_socket_addr,
+        program_safe.clone(),
+        metrics.clone(),
+    )
+    .unwrap();
     let handler = server.handle();
     {
         //listen unix socket
         let token = token.clone();
         let handler = handler.clone();
+        let tasks = tasks.clone();
         join_set.spawn(async move {
             info!("start ipc server {}", &args.unix_socket_addr);
-            tokio::spawn(server);
+            tasks.set_running(IPC_SERVER_TASK, true);
+            tokio::spawn(async move {
+                if let Err(err) = server.await {
+                    error!("ipc server exited {err}");
+                }
+                tasks.set_running(IPC_SERVER_TASK, false);
+            });
             select! {
                 _ = token.cancelled() => {
                     handler.stop(true).await;
@@ -119,6 +158,19 @@ async fn main() -> Result<()> {
             Ok::<(), anyhow::Error>(())
         });
     }
+    {
+        //listen metrics port, also serve probes
+        let server = start_monitor_server(&args.metrics_addr, metrics, health)?;
+        let handler = server.handle();
+        let token = token.clone();
+        join_set.spawn(async move {
+            tokio::spawn(server);
+            token.cancelled().await;
+            handler.stop(true).await;
+            info!("monitor server stopped");
+            Ok::<(), anyhow::Error>(())
+        });
+    }
     {
         let program_safe = program_safe.clone();
         let node_name = args.node_name.clone();
@@ -138,6 +190,7 @@ async fn main() -> Result<()> {
     {
         //listen port
         let addr = args.host_port.parse()?;
+        let token = token.clone();
         join_set.spawn(async move {
             let data_stream = ChannelDataStream {
                 program: program_safe,
@@ -146,11 +199,14 @@ async fn main() -> Result<()> {
             let server = DataStreamServer::new(data_stream)
                 .max_encoding_message_size(usize::MAX)
                 .max_decoding_message_size(usize::MAX);
-            Server::builder()
+            tasks.set_running(GRPC_SERVER_TASK, true);
+            let result = Server::builder()
                 .add_service(server)
-                .serve(addr)
+                .serve_with_shutdown(addr, token.cancelled())
                 .await
-                .anyhow()
+                .anyhow();
+            tasks.set_running(GRPC_SERVER_TASK, false);
+            result
         });
 
         info!("node listening on {}", addr);
@@ -1,10 +1,13 @@
-use crate::ipc::{
-    AvaiableDataResponse,
-    CompleteDataReq,
-    ErrorNumber,
-    IPCError,
-    RequetDataReq,
-    SubmitOuputDataReq,
+use crate::{
+    ipc::{
+        AvaiableDataResponse,
+        CompleteDataReq,
+        ErrorNumber,
+        IPCError,
+        RequetDataReq,
+        SubmitOuputDataReq,
+    },
+    metrics::RunnerMetrics,
 };
 use anyhow::{
     anyhow,
@@ -13,13 +16,17 @@ use anyhow::{
 
 use chrono::Utc;
 use jiaoziflow::{
-    core::db::{
-        DataFlag,
-        DataRecord,
-        DataState,
-        Direction,
-        JobDbRepo,
-        TrackerState,
+    core::{
+        db::{
+            DataFlag,
+            DataRecord,
+            DataState,
+            Direction,
+            JobDbRepo,
+            TrackerState,
+        },
+        Notification,
+        Notifier,
     },
     network::datatransfer::DataBatch,
     utils::k8s_helper::get_machine_name,
@@ -47,6 +54,7 @@ use tokio::{
     sync::{
         broadcast,
         mpsc,
+        Notify,
         RwLock,
     },
     task::JoinSet,
@@ -74,6 +82,11 @@ where
 
     pub(crate) local_state: Arc<RwLock<TrackerState>>,
 
+    // notify waiters when local state was changed
+    pub(crate) local_state_changed: Arc<Notify>,
+
+    pub(crate) notifier: Notifier,
+
     pub(crate) up_nodes: Vec<String>,
 
     pub(crate) outgoing_streams: Vec<String>,
@@ -92,6 +105,8 @@ where
     pub(crate) ipc_process_finish_state_tx: Option<MessageSender<(), ()>>,
 
     pub(crate) incoming_tx: Option<MessageSender<DataBatch, ()>>,
+
+    pub(crate) metrics: RunnerMetrics,
 }
 impl<R> MediaDataTracker<R>
 where
@@ -104,6 +119,7 @@ where
         buf_size: usize,
         up_nodes: Vec<String>,
         outgoing_streams: Vec<String>,
+        notifier: Notifier,
     ) -> Self {
         MediaDataTracker {
             data_cache,
@@ -111,6 +127,8 @@ where
             name: name.to_string(),
             repo,
             local_state: Arc::new(RwLock::new(TrackerState::Init)),
+            local_state_changed: Arc::new(Notify::new()),
+            notifier,
             up_nodes,
             outgoing_streams,
             ipc_process_submit_output_tx: None,
@@ -119,8 +137,19 @@ where
             ipc_process_finish_state_tx: None,
 
             incoming_tx: None,
+            metrics: RunnerMetrics::new(),
         }
     }
+
+    pub fn set_metrics(mut self, metrics: RunnerMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+}
+
+/// is_delivered match batches received by other nodes, data sent by this node is drained this way
+fn is_delivered(notification: &Notification, node_name: &str) -> bool {
+    matches!(notification, Notification::DataReady { node_name: name, direction: Direction::In } if name != node_name)
 }
 
 impl<R> MediaDataTracker<R>
@@ -136,46 +165,54 @@ where
         let db_repo = self.repo.clone();
         let up_nodes = self.up_nodes.clone();
         let node_name = self.name.clone();
+        let mut subscription = self.notifier.subscribe();
         join_set.spawn(async move {
-                let mut interval = time::interval(Duration::from_secs(30));
-                let mut to_check_finish = true;
-                loop {
-                    select! {
-                        _ = token.cancelled() => {
-                           return Ok(());
-                        }
-                        _ = interval.tick() => {
-                         if let Err(err) = {
-                            let now = Instant::now();
-                            info!("backend thread start");
-                            //select for sent
-                            db_repo.revert_no_success_sent(&node_name, &Direction::Out).await
-                            .map_err(|err|anyhow!("revert data {err}"))
-                            .map(|count| info!("revert {count} SelectForSent data to Received"))?;
-
-                            //check ready if both upnodes is finish and no pending data, we think it finish
-                            if to_check_finish && !up_nodes.is_empty() {
-                                let is_all_success = try_join_all(up_nodes.iter().map(|node_name|db_repo.get_node_by_name(node_name))).await
-                                .map_err(|err|anyhow!("query node data {err}"))?
-                                .iter()
-                                .all(|node| node.state == TrackerState::Finish);
-
-                                println!(" is upnodes finish {}", is_all_success);
-                                if is_all_success && db_repo.count(&node_name,  &[&DataState::Received,&DataState::Assigned], Some(&Direction::In)).await? == 0 {
-                                    db_repo.mark_incoming_finish(&node_name).await.map_err(|err|anyhow!("update node data {err}"))?;
-                                    info!("incoming data was finished, not need to run backend");
-                                    to_check_finish = false;
-                                }
-                            }
-                            info!("backend thread end {:?}", now.elapsed());
-                            anyhow::Ok(())
-                         }{
-                            error!("error in run backend {err}");
-                         }
+            let mut interval = time::interval(Duration::from_secs(30));
+            let mut to_check_finish = true;
+            loop {
+                //upstream finish may let this node finish, check it immediately
+                select! {
+                    _ = token.cancelled() => {
+                       return Ok(());
+                    }
+                    //new incoming batches change pending data, check it again too
+                    _ = subscription.wait(|notification| match notification {
+                        Notification::NodeStateChanged { node_name } => up_nodes.contains(node_name),
+                        Notification::DataReady { node_name: name, direction: Direction::In } => *name == node_name,
+                        _ => false,
+                    }) => {}
+                    _ = interval.tick() => {}
+                }
+
+                if let Err(err) = {
+                    let now = Instant::now();
+                    info!("backend thread start");
+                    //select for sent
+                    db_repo.revert_no_success_sent(&node_name, &Direction::Out).await
+                    .map_err(|err|anyhow!("revert data {err}"))
+                    .map(|count| info!("revert {count} SelectForSent data to Received"))?;
+
+                    //check ready if both upnodes is finish and no pending data, we think it finish
+                    if to_check_finish && !up_nodes.is_empty() {
+                        let is_all_success = try_join_all(up_nodes.iter().map(|node_name|db_repo.get_node_by_name(node_name))).await
+                        .map_err(|err|anyhow!("query node data {err}"))?
+                        .iter()
+                        .all(|node| node.state == TrackerState::Finish);
+
+                        println!(" is upnodes finish {}", is_all_success);
+                        if is_all_success && db_repo.count(&node_name,  &[&DataState::Received,&DataState::Assigned], Some(&Direction::In)).await? == 0 {
+                            db_repo.mark_incoming_finish(&node_name).await.map_err(|err|anyhow!("update node data {err}"))?;
+                            info!("incoming data was finished, not need to run backend");
+                            to_check_finish = false;
                         }
                     }
+                    info!("backend thread end {:?}", now.elapsed());
+                    anyhow::Ok(())
+                }{
+                    error!("error in run backend {err}");
                 }
-            });
+            }
+        });
         Ok(())
     }
     /// data was transfer from data container -> user container -> data container
@@ -207,7 +244,10 @@ where
             {
                 let new_data_tx = new_data_tx.clone();
                 let token = token.clone();
+                let node_name = self.name.clone();
+                let mut subscription = self.notifier.subscribe();
                 join_set.spawn(async move {
+                    //polling as fallback when notification not available
                     let mut interval = time::interval(Duration::from_secs(5));
                     loop {
                         select! {
@@ -217,6 +257,9 @@ where
                             _ = interval.tick() => {
                                 let _ = new_data_tx.send(());
                             }
+                            _ = subscription.wait(|notification| matches!(notification, Notification::DataReady { node_name: name, direction: Direction::Out } if *name == node_name)) => {
+                                let _ = new_data_tx.send(());
+                            }
                         }
                     }
                 });
@@ -224,12 +267,15 @@ where
 
             for _ in 0..10 {
                 let downstreams = self.outgoing_streams.clone();
-                let mut multi_sender = MultiSender::new(downstreams.clone());
+                let mut multi_sender =
+                    MultiSender::new(downstreams.clone()).set_metrics(self.metrics.send.clone());
                 let data_cache = self.data_cache.clone();
                 let token = token.clone();
                 let node_name = self.name.clone();
                 let db_repo = self.repo.clone();
                 let machine_name = machine_name.clone();
+                let local_state = self.local_state.clone();
+                let metrics = self.metrics.clone();
 
                 let mut new_data_rx = new_data_tx.subscribe();
 
@@ -244,6 +290,10 @@ where
                                     //drop fast to provent exceed channel capacity
                                     continue;
                                 }
+                                //downstream reject data when job is cancelling
+                                if *local_state.read().await == TrackerState::Stop {
+                                    continue;
+                                }
                                 //reconstruct batch
                                 //TODO combine multiple batch
                                 loop {
@@ -269,6 +319,7 @@ where
                                             if new_batch.size >0 && !downstreams.is_empty() {
                                                 info!("start to send data {} {:?}", &req.id, now.elapsed());
                                                 let sent_nodes: Vec<_>=  req.sent.iter().map(|v|v.as_str()).collect();
+                                                let bytes = batch_bytes(&new_batch);
                                                 if let Err(sent_nodes) =  multi_sender.send(new_batch, &sent_nodes).await {
                                                     if let Err(err) = db_repo.update_state(&node_name, &req.id, &Direction::Out,  &DataState::PartialSent, Some(sent_nodes.iter().map(|key|key.as_str()).collect())).await{
                                                         error!("revert data state fail {err}");
@@ -277,6 +328,8 @@ where
                                                     break;
                                                 }
 
+                                                metrics.batches_sent.inc(&[]);
+                                                metrics.bytes.inc_by(&["out"], bytes as f64);
                                                 info!("send data to downnstream successfully {} {:?}", &req.id, now.elapsed());
                                             }
 
@@ -318,6 +371,8 @@ where
             let buf_size = self.buf_size;
             let token = token.clone();
             let local_state = self.local_state.clone();
+            let metrics = self.metrics.clone();
+            let mut subscription = self.notifier.subscribe();
 
             join_set.spawn(async move {
                 loop {
@@ -341,7 +396,12 @@ where
                                 }
                             }){
                                 warn!("fail with limit {err}");
-                                sleep(Duration::from_secs(10)).await;
+                                let wait = Instant::now();
+                                select! {
+                                    _ = sleep(Duration::from_secs(10)) => {}
+                                    _ = subscription.wait(|notification| is_delivered(notification, &node_name)) => {}
+                                }
+                                metrics.buf_limit_wait.inc_by(&[], wait.elapsed().as_secs_f64());
                                 continue;
                             }
                             break;
@@ -390,6 +450,7 @@ where
             let token = token.clone();
             let local_state = self.local_state.clone();
             let machine_name = machine_name.clone();
+            let metrics = self.metrics.clone();
 
             join_set.spawn(async move {
                 loop {
@@ -435,6 +496,15 @@ where
                                 continue;
                             }
 
+                            //job is cancelling, dont assign new data and let user container drain
+                            if *local_state.read().await == TrackerState::Stop {
+                                resp.send(Err(IPCError::NodeError {
+                                    code: ErrorNumber::NoAvaiableData,
+                                    msg: "node is stopping".to_string(),
+                                })).expect("channel send failed: channel can only be read once");
+                                continue;
+                            }
+
                             let result = {
                                 // if a pod take a task but crash or some reason not complete it, this data will hang up.
                                 // TODO, also record who take this task, pod must pick this task first when restart.
@@ -465,6 +535,7 @@ where
                            Ok(_) =>{
                                     // respose with nothing
                                     resp.send(Ok(())).expect("channel only read once");
+                                    metrics.batches_processed.inc(&[]);
                                     if let Err(err) = data_cache.remove(&req.id).await {
                                         error!("remove tmp fs fail {}", err);
                                         continue;
@@ -485,6 +556,7 @@ where
             let db_repo = self.repo.clone();
             let node_name = self.name.clone();
             let token = token.clone();
+            let mut subscription = self.notifier.subscribe();
             join_set.spawn(async move {
                 loop {
                     select! {
@@ -510,7 +582,10 @@ where
                                     },
                                     Err(err) => error!("query node state {err}")
                                 }
-                                sleep(Duration::from_secs(5)).await;
+                                select! {
+                                    _ = sleep(Duration::from_secs(5)) => {}
+                                    _ = subscription.wait(|notification| is_delivered(notification, &node_name)) => {}
+                                }
                         }
 
                         match db_repo.update_node_by_name(&node_name, TrackerState::Finish).await{
@@ -536,6 +611,8 @@ where
             let buf_size = self.buf_size;
             let data_cache = self.data_cache.clone();
             let outgoing_streams = self.outgoing_streams.clone();
+            let local_state = self.local_state.clone();
+            let metrics = self.metrics.clone();
 
             let token = token.clone();
             join_set.spawn(async move {
@@ -545,6 +622,12 @@ where
                             return Ok(());
                          }
                      Some((data_batch, resp)) = incoming_rx.recv() => { //make this params
+                        //reject new data when job is cancelling, upstream will keep it
+                        if *local_state.read().await == TrackerState::Stop {
+                            resp.send(Err(anyhow!("node is stopping"))).expect("request alread listen this channel");
+                            continue;
+                        }
+
                         //save to fs
                         //create input directory
                         let now = Instant::now();
@@ -581,6 +664,7 @@ where
                         }
 
                         //write batch files
+                        let bytes = batch_bytes(&data_batch);
                         if let Err(err) = data_cache.write(data_batch).await {
                             error!("write files to disk fail {}", err);
                             resp.send(Err(err)).expect("request alread listen this channel");
@@ -646,6 +730,8 @@ where
                         }
 
                         resp.send(Ok(())).expect("request alread listen this channel ");
+                        metrics.batches_received.inc(&[]);
+                        metrics.bytes.inc_by(&["in"], bytes as f64);
                         info!("insert a data batch in {} {:?}", &id, now.elapsed());
                      },
                     }
@@ -655,3 +741,8 @@ where
         Ok(join_set)
     }
 }
+
+/// batch_bytes sum size of cell data in a batch
+fn batch_bytes(batch: &DataBatch) -> usize {
+    batch.cells.iter().map(|cell| cell.data.len()).sum()
+}
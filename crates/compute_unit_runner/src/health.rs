@@ -0,0 +1,136 @@
+use crate::{
+    data_tracker::MediaDataTracker,
+    metrics::{
+        get_metrics,
+        RunnerMetrics,
+    },
+};
+use actix_web::{
+    dev::Server,
+    middleware,
+    web,
+    App,
+    HttpResponse,
+    HttpServer,
+};
+use anyhow::{
+    anyhow,
+    Result,
+};
+use jiaoziflow::{
+    core::db::{
+        JobDbRepo,
+        TrackerState,
+    },
+    utils::health::{
+        HealthReport,
+        TaskHealth,
+    },
+};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::info;
+
+pub const IPC_SERVER_TASK: &str = "ipc_server";
+pub const GRPC_SERVER_TASK: &str = "grpc_server";
+
+/// RunnerHealth answer probes of kubernetes
+pub struct RunnerHealth<R>
+where
+    R: JobDbRepo,
+{
+    repo: R,
+    local_state: Arc<RwLock<TrackerState>>,
+    tasks: TaskHealth,
+}
+
+impl<R> Clone for RunnerHealth<R>
+where
+    R: JobDbRepo,
+{
+    fn clone(&self) -> Self {
+        RunnerHealth {
+            repo: self.repo.clone(),
+            local_state: self.local_state.clone(),
+            tasks: self.tasks.clone(),
+        }
+    }
+}
+
+impl<R> RunnerHealth<R>
+where
+    R: JobDbRepo,
+{
+    pub fn new(program: &MediaDataTracker<R>, tasks: TaskHealth) -> Self {
+        RunnerHealth {
+            repo: program.repo.clone(),
+            local_state: program.local_state.clone(),
+            tasks,
+        }
+    }
+
+    /// liveness fail if mongo is unreachable or a server exited, restart is the only way out
+    async fn liveness(&self) -> HealthReport {
+        HealthReport::new()
+            .add_async_check("mongo", self.repo.ping())
+            .await
+            .add_check(IPC_SERVER_TASK, self.tasks.check(IPC_SERVER_TASK))
+            .add_check(GRPC_SERVER_TASK, self.tasks.check(GRPC_SERVER_TASK))
+    }
+
+    /// readiness also need node to leave Init, upstream should not send data before job run
+    async fn readiness(&self) -> HealthReport {
+        let state = self.local_state.read().await.clone();
+        let state_check = if state == TrackerState::Init {
+            Err(anyhow!("node is Init"))
+        } else {
+            Ok(())
+        };
+        self.liveness().await.add_check("state", state_check)
+    }
+}
+
+async fn healthz<R>(health: web::Data<RunnerHealth<R>>) -> HttpResponse
+where
+    R: JobDbRepo,
+{
+    health.liveness().await.into_response()
+}
+
+async fn readyz<R>(health: web::Data<RunnerHealth<R>>) -> HttpResponse
+where
+    R: JobDbRepo,
+{
+    health.readiness().await.into_response()
+}
+
+/// start_monitor_server serve /metrics, /healthz and /readyz on addr, separated from data port
+/// so that scrapes and probes dont compete with data transfer
+pub fn start_monitor_server<R>(
+    addr: &str,
+    metrics: RunnerMetrics,
+    health: RunnerHealth<R>,
+) -> Result<Server>
+where
+    R: JobDbRepo,
+{
+    info!("monitor listening on {addr}");
+    let server = HttpServer::new(move || {
+        App::new()
+            .wrap(
+                middleware::Logger::default()
+                    .exclude("/healthz")
+                    .exclude("/readyz"),
+            )
+            .app_data(web::Data::new(metrics.clone()))
+            .app_data(web::Data::new(health.clone()))
+            .route("/metrics", web::get().to(get_metrics))
+            .route("/healthz", web::get().to(healthz::<R>))
+            .route("/readyz", web::get().to(readyz::<R>))
+    })
+    .workers(1)
+    .disable_signals()
+    .bind(addr)?
+    .run();
+    Ok(server)
+}
@@ -1,4 +1,10 @@
-use crate::data_tracker::MediaDataTracker;
+use crate::{
+    data_tracker::MediaDataTracker,
+    metrics::{
+        track_ipc_request,
+        RunnerMetrics,
+    },
+};
 use actix_web::{
     dev::Server,
     error,
@@ -31,7 +37,9 @@ use std::{
     time::Duration,
 };
 use tokio::{
+    select,
     sync::{
+        futures::Notified,
         oneshot,
         RwLock,
     },
@@ -255,6 +263,14 @@ impl RequetDataReq {
     }
 }
 
+/// wait for local state change, polling is kept as fallback
+async fn wait_state_changed(notified: Notified<'_>) {
+    select! {
+        _ = notified => {}
+        _ = sleep(Duration::from_secs(5)) => {}
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Status {
     pub node_name: String,
@@ -294,9 +310,11 @@ where
         if *local_state != TrackerState::Init {
             break program.ipc_process_data_req_tx.as_ref().cloned();
         }
+        let state_changed = program.local_state_changed.clone();
+        let notified = state_changed.notified();
         drop(local_state);
         drop(program);
-        sleep(Duration::from_secs(5)).await;
+        wait_state_changed(notified).await;
     };
 
     //read request
@@ -347,9 +365,11 @@ where
         if *local_state != TrackerState::Init {
             break program.ipc_process_completed_data_tx.as_ref().cloned();
         }
+        let state_changed = program.local_state_changed.clone();
+        let notified = state_changed.notified();
         drop(local_state);
         drop(program);
-        sleep(Duration::from_secs(5)).await;
+        wait_state_changed(notified).await;
     };
 
     //read request
@@ -399,9 +419,11 @@ where
         if *local_state != TrackerState::Init {
             break program.ipc_process_submit_output_tx.as_ref().cloned();
         }
+        let state_changed = program.local_state_changed.clone();
+        let notified = state_changed.notified();
         drop(local_state);
         drop(program);
-        sleep(Duration::from_secs(5)).await;
+        wait_state_changed(notified).await;
     };
 
     //read request
@@ -446,9 +468,11 @@ where
         if *local_state != TrackerState::Init {
             break program.ipc_process_finish_state_tx.as_ref().cloned();
         }
+        let state_changed = program.local_state_changed.clone();
+        let notified = state_changed.notified();
         drop(local_state);
         drop(program);
-        sleep(Duration::from_secs(5)).await;
+        wait_state_changed(notified).await;
     };
 
     //read request
@@ -476,6 +500,7 @@ where
 pub fn start_ipc_server<R>(
     unix_socket_addr: &str,
     program: Arc<RwLock<MediaDataTracker<R>>>,
+    metrics: RunnerMetrics,
 ) -> Result<Server>
 where
     R: JobDbRepo + Clone + Send + Sync + 'static,
@@ -497,7 +522,9 @@ where
 
         App::new()
             .wrap(middleware::Logger::default())
+            .wrap(middleware::from_fn(track_ipc_request))
             .app_data(Data::new(program.clone()))
+            .app_data(Data::new(metrics.clone()))
             .service(
                 web::scope("/api/v1")
                     .service(
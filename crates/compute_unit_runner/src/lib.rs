@@ -1,5 +1,7 @@
 pub mod stream;
 
 pub mod data_tracker;
+pub mod health;
 pub mod ipc;
+pub mod metrics;
 pub mod state_controller;
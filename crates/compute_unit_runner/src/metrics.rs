@@ -0,0 +1,244 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        ServiceRequest,
+        ServiceResponse,
+    },
+    middleware::Next,
+    web,
+    HttpResponse,
+};
+use anyhow::Result;
+use jiaoziflow::utils::metrics::{
+    MetricFamily,
+    Registry,
+    CONTENT_TYPE,
+    LATENCY_BUCKETS,
+};
+use nodes_sdk::multi_sender::SendMetrics;
+use std::{
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use tokio::{
+    fs,
+    select,
+    task::JoinSet,
+    time::sleep,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+
+/// interval of measuring disk usage of cache
+const CACHE_USAGE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// RunnerMetrics are exposed by compute unit runner on its metrics port
+#[derive(Clone)]
+pub struct RunnerMetrics {
+    registry: Registry,
+    /// `jzflow_runner_batches_received_total` counter, batches accepted from upstream nodes
+    pub batches_received: Arc<MetricFamily>,
+    /// `jzflow_runner_batches_processed_total` counter, batches completed by user container
+    pub batches_processed: Arc<MetricFamily>,
+    /// `jzflow_runner_batches_sent_total` counter, batches sent to all downstream nodes
+    pub batches_sent: Arc<MetricFamily>,
+    /// `jzflow_runner_bytes_total{direction}` counter, bytes of batches received(in) and
+    /// sent(out)
+    pub bytes: Arc<MetricFamily>,
+    /// `jzflow_runner_transfer_duration_seconds{downstream}` histogram and
+    /// `jzflow_runner_send_failures_total{downstream}` counter of grpc transfers
+    pub send: SendMetrics,
+    /// `jzflow_runner_ipc_request_duration_seconds{method,endpoint}` histogram, latency of
+    /// requests from user container
+    pub ipc_request_duration: Arc<MetricFamily>,
+    /// `jzflow_runner_cache_disk_bytes` gauge, size of files in disk cache
+    pub cache_disk_bytes: Arc<MetricFamily>,
+    /// `jzflow_runner_buf_limit_wait_seconds_total` counter, time submits waited for outgoing
+    /// data to drop under buf_size
+    pub buf_limit_wait: Arc<MetricFamily>,
+}
+
+impl Default for RunnerMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RunnerMetrics {
+    pub fn new() -> Self {
+        let registry = Registry::default();
+        RunnerMetrics {
+            batches_received: registry.counter(
+                "jzflow_runner_batches_received_total",
+                "batches accepted from upstream nodes",
+                &[],
+            ),
+            batches_processed: registry.counter(
+                "jzflow_runner_batches_processed_total",
+                "batches completed by user container",
+                &[],
+            ),
+            batches_sent: registry.counter(
+                "jzflow_runner_batches_sent_total",
+                "batches sent to all downstream nodes",
+                &[],
+            ),
+            bytes: registry.counter(
+                "jzflow_runner_bytes_total",
+                "bytes of batches received and sent",
+                &["direction"],
+            ),
+            send: SendMetrics {
+                transfer_duration: registry.histogram(
+                    "jzflow_runner_transfer_duration_seconds",
+                    "latency of grpc transfers to downstream nodes",
+                    &["downstream"],
+                    &LATENCY_BUCKETS,
+                ),
+                failures: registry.counter(
+                    "jzflow_runner_send_failures_total",
+                    "failed connects and transfers to downstream nodes",
+                    &["downstream"],
+                ),
+            },
+            ipc_request_duration: registry.histogram(
+                "jzflow_runner_ipc_request_duration_seconds",
+                "latency of requests from user container",
+                &["method", "endpoint"],
+                &LATENCY_BUCKETS,
+            ),
+            cache_disk_bytes: registry.gauge(
+                "jzflow_runner_cache_disk_bytes",
+                "size of files in disk cache",
+                &[],
+            ),
+            buf_limit_wait: registry.counter(
+                "jzflow_runner_buf_limit_wait_seconds_total",
+                "time submits waited for outgoing data to drop under buf_size",
+                &[],
+            ),
+            registry,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        self.registry.render()
+    }
+
+    /// run_cache_usage measure disk usage of cache directory periodically
+    pub fn run_cache_usage(
+        &self,
+        join_set: &mut JoinSet<Result<()>>,
+        token: CancellationToken,
+        cache_path: &str,
+    ) {
+        let metrics = self.clone();
+        let cache_path = PathBuf::from(cache_path);
+        join_set.spawn(async move {
+            loop {
+                match dir_size(&cache_path).await {
+                    Ok(size) => metrics.cache_disk_bytes.set(&[], size as f64),
+                    Err(err) => error!("measure cache usage {err}"),
+                }
+                select! {
+                    _ = token.cancelled() => {
+                        return Ok(());
+                    }
+                    _ = sleep(CACHE_USAGE_INTERVAL) => {}
+                }
+            }
+        });
+    }
+}
+
+/// dir_size sum size of files under path, 0 if path not exist
+async fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            //batches may be removed while walking
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+        }
+    }
+    Ok(size)
+}
+
+/// track_ipc_request is a middleware recording latency of ipc requests by endpoint
+pub(crate) async fn track_ipc_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let metrics = req.app_data::<web::Data<RunnerMetrics>>().cloned();
+    let start = Instant::now();
+    let resp = next.call(req).await?;
+
+    if let Some(metrics) = metrics {
+        let request = resp.request();
+        let endpoint = request
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        metrics.ipc_request_duration.observe(
+            &[request.method().as_str(), &endpoint],
+            start.elapsed().as_secs_f64(),
+        );
+    }
+    Ok(resp)
+}
+
+pub(crate) async fn get_metrics(metrics: web::Data<RunnerMetrics>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(CONTENT_TYPE)
+        .body(metrics.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dir_size() {
+        let dir = std::env::temp_dir().join(format!("jzflow-cache-{}", std::process::id()));
+        fs::create_dir_all(dir.join("batch")).await.unwrap();
+        fs::write(dir.join("a"), vec![0; 10]).await.unwrap();
+        fs::write(dir.join("batch").join("b"), vec![0; 5])
+            .await
+            .unwrap();
+        assert_eq!(dir_size(&dir).await.unwrap(), 15);
+        fs::remove_dir_all(&dir).await.unwrap();
+        assert_eq!(dir_size(&dir).await.unwrap(), 0);
+
+        let metrics = RunnerMetrics::new();
+        metrics.send.failures.inc(&["http://sink-service"]);
+        metrics.bytes.inc_by(&["in"], 15.0);
+        let text = metrics.render();
+        for line in [
+            r#"jzflow_runner_send_failures_total{downstream="http://sink-service"} 1"#,
+            r#"jzflow_runner_bytes_total{direction="in"} 15"#,
+            "# TYPE jzflow_runner_batches_received_total counter",
+            "# TYPE jzflow_runner_transfer_duration_seconds histogram",
+            "# TYPE jzflow_runner_ipc_request_duration_seconds histogram",
+            "# TYPE jzflow_runner_cache_disk_bytes gauge",
+            "# TYPE jzflow_runner_buf_limit_wait_seconds_total counter",
+        ] {
+            assert!(text.lines().any(|l| l == line), "{line} not in {text}");
+        }
+    }
+}
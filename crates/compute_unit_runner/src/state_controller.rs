@@ -1,8 +1,15 @@
 use actix_web::dev::ServerHandle;
 use anyhow::Result;
-use jiaoziflow::core::db::{
-    JobDbRepo,
-    TrackerState,
+use jiaoziflow::{
+    core::{
+        db::{
+            JobDbRepo,
+            TrackerState,
+        },
+        Notification,
+        RunMode,
+    },
+    utils::k8s_helper::get_run_mode,
 };
 use std::sync::Arc;
 use tokio::{
@@ -43,6 +50,7 @@ where
         let mut interval = time::interval(time::Duration::from_secs(10));
         let mut join_set: Option<JoinSet<Result<()>>> = None;
         let program = self.program.clone();
+        let mut subscription = program.read().await.notifier.subscribe();
         loop {
             select! {
                 _ = token.cancelled() => {
@@ -55,31 +63,38 @@ where
                     }
                    return Ok(());
                 }
-                _ = interval.tick() => {
-                    match repo
-                    .get_node_by_name(name)
-                    .await{
-                        Ok(record)=> {
-                            debug!("{} fetch state from db", record.node_name);
-                            let mut program_guard = program.write().await;
-                            let mut local_state = program_guard.local_state.write().await;
-                            if *local_state == record.state {
-                                continue
-                            }
-                            let old_local_state = local_state.clone();
-                            *local_state = record.state.clone();
-                            info!("update state {:?} -> {:?}", old_local_state, local_state);
-                            drop(local_state);
-                            if  record.state!= TrackerState::Init && !record.state.is_end_state() {
-                                //start
-                                info!("start data processing");
-                                join_set = Some(program_guard.route_data(token.clone()).await?);
-                            }
-                        },
-                        Err(err)=> error!("fetch node state from db {err}")
-                    }
+                _ = subscription.wait(|notification| matches!(notification, Notification::NodeStateChanged { node_name } if node_name == name)) => {}
+                _ = interval.tick() => {}
+            }
 
+            match repo.get_node_by_name(name).await {
+                Ok(record) => {
+                    debug!("{} fetch state from db", record.node_name);
+                    let mut program_guard = program.write().await;
+                    let mut local_state = program_guard.local_state.write().await;
+                    if *local_state == record.state {
+                        continue;
+                    }
+                    let old_local_state = local_state.clone();
+                    *local_state = record.state.clone();
+                    info!("update state {:?} -> {:?}", old_local_state, local_state);
+                    drop(local_state);
+                    program_guard.local_state_changed.notify_waiters();
+                    if join_set.is_none()
+                        && record.state != TrackerState::Init
+                        && !record.state.is_end_state()
+                    {
+                        //start
+                        info!("start data processing");
+                        join_set = Some(program_guard.route_data(token.clone()).await?);
+                    }
+                    if record.state.is_end_state() && get_run_mode() == RunMode::Finite {
+                        //finite node exit to let kubernetes job complete
+                        info!("node reach end state {:?}, exit runner", record.state);
+                        token.cancel();
+                    }
                 }
+                Err(err) => error!("fetch node state from db {err}"),
             }
         }
     }
@@ -1,7 +1,11 @@
-use jiaoziflow::network::datatransfer::{
-    data_stream_client::DataStreamClient,
-    DataBatch,
+use jiaoziflow::{
+    network::datatransfer::{
+        data_stream_client::DataStreamClient,
+        DataBatch,
+    },
+    utils::metrics::MetricFamily,
 };
+use std::sync::Arc;
 use tokio::time::Instant;
 use tonic::transport::Channel;
 use tracing::{
@@ -9,22 +13,43 @@ use tracing::{
     error,
 };
 
+/// SendMetrics record transfers of MultiSender, both are labeled by downstream url
+#[derive(Clone)]
+pub struct SendMetrics {
+    /// histogram of seconds a successful grpc transfer took
+    pub transfer_duration: Arc<MetricFamily>,
+    /// counter of failed connects and transfers
+    pub failures: Arc<MetricFamily>,
+}
+
 pub struct MultiSender {
     streams: Vec<String>,
 
     connects: Vec<Option<DataStreamClient<Channel>>>,
+
+    metrics: Option<SendMetrics>,
 }
 
 impl MultiSender {
     pub fn new(streams: Vec<String>) -> Self {
         let connects = streams.iter().map(|_| None).collect();
-        MultiSender { streams, connects }
+        MultiSender {
+            streams,
+            connects,
+            metrics: None,
+        }
+    }
+
+    pub fn set_metrics(mut self, metrics: SendMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
     }
 }
 
 impl MultiSender {
     pub async fn send(&mut self, val: DataBatch, sent_nodes: &[&str]) -> Result<(), Vec<String>> {
         let mut sent = vec![];
+        let metrics = self.metrics.as_ref();
         for (index, stream) in self.connects.iter_mut().enumerate() {
             let url = &self.streams[index];
             if sent_nodes.contains(&url.as_str()) {
@@ -43,6 +68,9 @@ impl MultiSender {
                     }
                     Err(err) => {
                         error!("connect data streams {url} {err}");
+                        if let Some(metrics) = metrics {
+                            metrics.failures.inc(&[url]);
+                        }
                         continue;
                     }
                 }
@@ -52,8 +80,16 @@ impl MultiSender {
             let now = Instant::now();
             if let Err(err) = client.transfer_media_data(val.clone()).await {
                 error!("send reqeust will try next time {url} {err}");
+                if let Some(metrics) = metrics {
+                    metrics.failures.inc(&[url]);
+                }
                 continue;
             }
+            if let Some(metrics) = metrics {
+                metrics
+                    .transfer_duration
+                    .observe(&[url], now.elapsed().as_secs_f64());
+            }
             println!("send one success {:?}", now.elapsed());
             sent.push(url.to_string());
         }
@@ -4,6 +4,10 @@ use anyhow::{
     anyhow,
     Result,
 };
+use jiaoziflow::{
+    core::RunMode,
+    utils::k8s_helper::get_run_mode,
+};
 use tokio::{
     task::JoinSet,
     time::sleep,
@@ -31,6 +35,10 @@ pub async fn monitor_tasks(join_set: &mut JoinSet<Result<()>>) -> Result<()> {
 
     if !has_err {
         info!("Gracefully shutting down...");
+        if get_run_mode() == RunMode::Finite {
+            // finite node run as kubernetes job, exit to mark pod completed
+            return Ok(());
+        }
         // Prevent StatefulSet from restarting by sleeping for a long duration
         sleep(Duration::from_days(364 * 100)).await;
         Ok(())
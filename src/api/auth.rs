@@ -0,0 +1,373 @@
+use super::error::ApiError;
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        Payload,
+        ServiceRequest,
+        ServiceResponse,
+    },
+    http::header,
+    middleware::Next,
+    web,
+    FromRequest,
+    HttpMessage,
+    HttpRequest,
+    ResponseError,
+};
+use anyhow::{
+    anyhow,
+    Result,
+};
+use base64::{
+    engine::general_purpose::URL_SAFE_NO_PAD,
+    Engine,
+};
+use chrono::Utc;
+use hmac::{
+    Hmac,
+    Mac,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    future::{
+        ready,
+        Ready,
+    },
+    str::FromStr,
+    sync::Arc,
+};
+
+/// Role grant permissions, every role include permissions of roles before it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// read jobs, schedules and triggers
+    Viewer,
+    /// create jobs, schedules and triggers, and manage the ones it owns
+    Submitter,
+    /// manage everything
+    Admin,
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "submitter" => Ok(Role::Submitter),
+            "admin" => Ok(Role::Admin),
+            _ => Err(anyhow!("role must be viewer, submitter or admin")),
+        }
+    }
+}
+
+/// Principal is the authenticated caller of a request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+impl Principal {
+    /// anonymous is used for every request when no auth provider is configured
+    pub fn anonymous() -> Self {
+        Principal {
+            name: "anonymous".to_string(),
+            role: Role::Admin,
+        }
+    }
+
+    /// require fail with 403 if principal dont have role
+    pub fn require(&self, role: Role) -> Result<(), ApiError> {
+        if self.role < role {
+            return Err(ApiError::forbidden(format!(
+                "{} need {role:?} role, has {:?}",
+                self.name, self.role
+            )));
+        }
+        Ok(())
+    }
+
+    /// require_owner fail with 403 unless principal is admin or submitter owning the resource
+    pub fn require_owner(&self, owner: Option<&str>) -> Result<(), ApiError> {
+        self.require(Role::Submitter)?;
+        if self.role == Role::Admin || owner == Some(self.name.as_str()) {
+            return Ok(());
+        }
+        Err(ApiError::forbidden(format!(
+            "{} is not owner of this resource",
+            self.name
+        )))
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Principal>()
+                .cloned()
+                .ok_or_else(|| ApiError::unauthorized("request not authenticated")),
+        )
+    }
+}
+
+/// AuthProvider verify bearer tokens, return None if token is not recognized by this provider
+pub trait AuthProvider: Send + Sync {
+    fn authenticate(&self, token: &str) -> Result<Option<Principal>>;
+}
+
+/// StaticTokens map long-lived api tokens to principals
+#[derive(Default)]
+pub struct StaticTokens {
+    tokens: HashMap<String, Principal>,
+}
+
+impl StaticTokens {
+    /// set_token parse token in format token:name:role
+    pub fn set_token(mut self, token: &str) -> Result<Self> {
+        let mut fields = token.rsplitn(3, ':');
+        let (Some(role), Some(name), Some(token)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(anyhow!("api token must be token:name:role"));
+        };
+        let principal = Principal {
+            name: name.to_string(),
+            role: Role::from_str(role)?,
+        };
+        self.tokens.insert(token.to_string(), principal);
+        Ok(self)
+    }
+}
+
+impl AuthProvider for StaticTokens {
+    fn authenticate(&self, token: &str) -> Result<Option<Principal>> {
+        Ok(self.tokens.get(token).cloned())
+    }
+}
+
+/// JwtClaims is the payload of tokens accepted by Jwt
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JwtClaims {
+    pub sub: String,
+    pub role: Role,
+    /// unix time token expires
+    pub exp: i64,
+}
+
+/// Jwt verify HS256 signed json web tokens
+pub struct Jwt {
+    secret: String,
+}
+
+impl Jwt {
+    pub fn new(secret: &str) -> Self {
+        Jwt {
+            secret: secret.to_string(),
+        }
+    }
+
+    fn mac(&self, content: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("hmac accept any key size");
+        mac.update(content.as_bytes());
+        mac
+    }
+
+    /// sign issue a token carrying claims
+    pub fn sign(&self, claims: &JwtClaims) -> Result<String> {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?);
+        let content = format!("{header}.{payload}");
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&content).finalize().into_bytes());
+        Ok(format!("{content}.{signature}"))
+    }
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+impl AuthProvider for Jwt {
+    fn authenticate(&self, token: &str) -> Result<Option<Principal>> {
+        let mut parts = token.split('.');
+        let (Some(encoded_header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Ok(None);
+        };
+
+        let header: JwtHeader = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(encoded_header)?)?;
+        if header.alg != "HS256" {
+            return Err(anyhow!("unsupported jwt algorithm {}", header.alg));
+        }
+        self.mac(&format!("{encoded_header}.{payload}"))
+            .verify_slice(&URL_SAFE_NO_PAD.decode(signature)?)
+            .map_err(|_| anyhow!("invalid jwt signature"))?;
+
+        let claims: JwtClaims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload)?)?;
+        if claims.exp <= Utc::now().timestamp() {
+            return Err(anyhow!("jwt expired"));
+        }
+        Ok(Some(Principal {
+            name: claims.sub,
+            role: claims.role,
+        }))
+    }
+}
+
+/// Authenticator try providers in order, requests are anonymous admin if no provider is added
+#[derive(Default, Clone)]
+pub struct Authenticator {
+    providers: Vec<Arc<dyn AuthProvider>>,
+}
+
+impl Authenticator {
+    pub fn add_provider(mut self, provider: impl AuthProvider + 'static) -> Self {
+        self.providers.push(Arc::new(provider));
+        self
+    }
+
+    pub fn authenticate(&self, authorization: Option<&str>) -> Result<Principal> {
+        if self.providers.is_empty() {
+            return Ok(Principal::anonymous());
+        }
+
+        let token = authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(anyhow!("missing bearer token"))?;
+        for provider in self.providers.iter() {
+            if let Some(principal) = provider.authenticate(token)? {
+                return Ok(principal);
+            }
+        }
+        Err(anyhow!("unknown token"))
+    }
+}
+
+/// authenticate is a middleware attaching the principal of request, 401 if it can not be
+/// authenticated
+pub(super) async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let authenticator = req
+        .app_data::<web::Data<Authenticator>>()
+        .cloned()
+        .unwrap_or_default();
+    let authorization = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+
+    match authenticator.authenticate(authorization) {
+        Ok(principal) => {
+            req.extensions_mut().insert(principal);
+            next.call(req).await.map(|resp| resp.map_into_left_body())
+        }
+        Err(err) => Ok(req
+            .into_response(ApiError::unauthorized(err.to_string()).error_response())
+            .map_into_right_body()),
+    }
+}
+
+/// read_token pick api token from flag, JZFLOW_TOKEN env or token field of config file
+pub fn read_token(flag: Option<&str>, config_path: Option<&str>) -> Result<Option<String>> {
+    if let Some(token) = flag {
+        return Ok(Some(token.to_string()));
+    }
+    if let Ok(token) = std::env::var("JZFLOW_TOKEN") {
+        return Ok(Some(token));
+    }
+    let Some(path) = config_path.filter(|path| std::path::Path::new(path).exists()) else {
+        return Ok(None);
+    };
+
+    #[derive(Deserialize)]
+    struct ClientConfig {
+        token: Option<String>,
+    }
+    let content = std::fs::read_to_string(path)?;
+    let config: ClientConfig =
+        serde_json::from_str(&content).map_err(|err| anyhow!("parse config {path} {err}"))?;
+    Ok(config.token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_tokens() {
+        let authenticator = Authenticator::default();
+        assert_eq!(
+            authenticator.authenticate(None).unwrap(),
+            Principal::anonymous()
+        );
+
+        let authenticator = authenticator.add_provider(
+            StaticTokens::default()
+                .set_token("s3cr:et:alice:submitter")
+                .unwrap(),
+        );
+        assert_eq!(
+            authenticator.authenticate(Some("Bearer s3cr:et")).unwrap(),
+            Principal {
+                name: "alice".to_string(),
+                role: Role::Submitter,
+            }
+        );
+        assert!(authenticator.authenticate(Some("Bearer other")).is_err());
+        assert!(authenticator.authenticate(None).is_err());
+        assert!(StaticTokens::default().set_token("alice:owner").is_err());
+    }
+
+    #[test]
+    fn test_jwt() {
+        let jwt = Jwt::new("secret");
+        let token = jwt
+            .sign(&JwtClaims {
+                sub: "bob".to_string(),
+                role: Role::Viewer,
+                exp: Utc::now().timestamp() + 60,
+            })
+            .unwrap();
+        let principal = jwt.authenticate(&token).unwrap().unwrap();
+        assert_eq!(principal.name, "bob");
+        assert!(principal.require(Role::Viewer).is_ok());
+        assert!(principal.require(Role::Submitter).is_err());
+
+        assert!(Jwt::new("other").authenticate(&token).is_err());
+        assert!(jwt.authenticate("not-a-jwt").unwrap().is_none());
+        let expired = jwt
+            .sign(&JwtClaims {
+                sub: "bob".to_string(),
+                role: Role::Admin,
+                exp: Utc::now().timestamp() - 1,
+            })
+            .unwrap();
+        assert!(jwt.authenticate(&expired).is_err());
+    }
+
+    #[test]
+    fn test_require_owner() {
+        let alice = Principal {
+            name: "alice".to_string(),
+            role: Role::Submitter,
+        };
+        assert!(alice.require_owner(Some("alice")).is_ok());
+        assert!(alice.require_owner(Some("bob")).is_err());
+        assert!(alice.require_owner(None).is_err());
+        assert!(Principal::anonymous().require_owner(Some("bob")).is_ok());
+    }
+}
@@ -0,0 +1,128 @@
+use super::api_error;
+use crate::{
+    core::db::{
+        DataList,
+        DataRecord,
+        ListDataParams,
+        NodeDataSummary,
+    },
+    utils::StdIntoAnyhowResult,
+};
+use anyhow::Result;
+use mongodb::bson::oid::ObjectId;
+use reqwest::{
+    Client,
+    Url,
+};
+use serde::de::DeserializeOwned;
+use serde_variant::to_variant_name;
+
+pub struct DataClient {
+    pub(crate) client: Client,
+    pub(crate) base_uri: Url,
+}
+
+impl DataClient {
+    async fn get_json<T: DeserializeOwned>(&self, uri: Url) -> Result<T> {
+        let resp = self.client.get(uri).send().await.anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        resp.bytes()
+            .await
+            .anyhow()
+            .and_then(|body| serde_json::from_slice(&body).anyhow())
+            .anyhow()
+    }
+
+    /// summary count data of every node of job by direction and state
+    pub async fn summary(&self, job_id: &ObjectId) -> Result<Vec<NodeDataSummary>> {
+        let uri = self
+            .base_uri
+            .clone()
+            .join("job/data/")?
+            .join(job_id.to_hex().as_str())?;
+        self.get_json(uri).await
+    }
+
+    pub async fn list(
+        &self,
+        job_id: &ObjectId,
+        node_name: &str,
+        list_data_params: &ListDataParams,
+    ) -> Result<DataList> {
+        let mut uri = self
+            .base_uri
+            .clone()
+            .join("job/data/")?
+            .join(&format!("{}/{node_name}", job_id.to_hex()))?;
+        {
+            let mut query = uri.query_pairs_mut();
+            for (key, value) in [
+                (
+                    "direction",
+                    list_data_params
+                        .direction
+                        .as_ref()
+                        .map(to_variant_name)
+                        .transpose()?
+                        .map(str::to_string),
+                ),
+                (
+                    "state",
+                    list_data_params
+                        .state
+                        .as_ref()
+                        .map(to_variant_name)
+                        .transpose()?
+                        .map(str::to_string),
+                ),
+                ("machine", list_data_params.machine.clone()),
+                (
+                    "priority",
+                    list_data_params
+                        .priority
+                        .map(|priority| priority.to_string()),
+                ),
+                (
+                    "created_after",
+                    list_data_params.created_after.map(|tm| tm.to_string()),
+                ),
+                (
+                    "created_before",
+                    list_data_params.created_before.map(|tm| tm.to_string()),
+                ),
+                (
+                    "updated_after",
+                    list_data_params.updated_after.map(|tm| tm.to_string()),
+                ),
+                (
+                    "updated_before",
+                    list_data_params.updated_before.map(|tm| tm.to_string()),
+                ),
+                (
+                    "limit",
+                    list_data_params.limit.map(|limit| limit.to_string()),
+                ),
+                ("cursor", list_data_params.cursor.clone()),
+            ] {
+                if let Some(value) = value {
+                    query.append_pair(key, &value);
+                }
+            }
+        }
+        self.get_json(uri).await
+    }
+
+    /// get_batch list records of a data batch in all nodes of job, in the order it went through
+    pub async fn get_batch(&self, job_id: &ObjectId, data_id: &str) -> Result<Vec<DataRecord>> {
+        let uri = self
+            .base_uri
+            .clone()
+            .join("job/batch/")?
+            .join(&format!("{}/{data_id}", job_id.to_hex()))?;
+        self.get_json(uri).await
+    }
+}
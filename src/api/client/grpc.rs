@@ -0,0 +1,190 @@
+use crate::{
+    api::error::ApiError,
+    core::db::{
+        Job,
+        JobList,
+        ListJobParams,
+    },
+    job::{
+        job_mgr::JobDetails,
+        watch::JobEvent,
+    },
+    network::jobservice::{
+        self as pb,
+        job_service_client::JobServiceClient,
+    },
+};
+use anyhow::Result;
+use mongodb::bson::oid::ObjectId;
+use std::time::Duration;
+use tonic::{
+    metadata::{
+        Ascii,
+        MetadataValue,
+    },
+    transport::Channel,
+    Request,
+    Status,
+    Streaming,
+};
+
+/// status_error convert grpc status to ApiError, downcast the returned error to match on its code
+fn status_error(status: Status) -> anyhow::Error {
+    ApiError::from(status).into()
+}
+
+/// JobGrpcEventStream read events of a job watched over grpc
+pub struct JobGrpcEventStream {
+    stream: Streaming<pb::JobEvent>,
+}
+
+impl JobGrpcEventStream {
+    /// next return None when server closed the stream
+    pub async fn next(&mut self) -> Result<Option<JobEvent>> {
+        match self.stream.message().await.map_err(status_error)? {
+            Some(event) => Ok(Some(JobEvent::try_from(event)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// JobGrpcClient call JobService of daemon, same operations as JobClient over grpc
+#[derive(Clone)]
+pub struct JobGrpcClient {
+    client: JobServiceClient<Channel>,
+    authorization: Option<MetadataValue<Ascii>>,
+}
+
+impl JobGrpcClient {
+    pub async fn connect(addr: &str) -> Result<Self> {
+        let client = JobServiceClient::connect(addr.to_string()).await?;
+        Ok(JobGrpcClient {
+            client,
+            authorization: None,
+        })
+    }
+
+    /// set_token send token as bearer authorization with every request
+    pub fn set_token(mut self, token: &str) -> Result<Self> {
+        let mut value = MetadataValue::try_from(format!("Bearer {token}"))?;
+        value.set_sensitive(true);
+        self.authorization = Some(value);
+        Ok(self)
+    }
+
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(value) = self.authorization.as_ref() {
+            request
+                .metadata_mut()
+                .insert("authorization", value.clone());
+        }
+        request
+    }
+
+    fn job_ref(id: &str) -> pb::JobRef {
+        pb::JobRef { id: id.to_string() }
+    }
+
+    pub async fn create(&self, job: &Job) -> Result<Job> {
+        let resp = self
+            .client
+            .clone()
+            .create(self.request(pb::Job::from(job.clone())))
+            .await
+            .map_err(status_error)?;
+        Ok(Job::try_from(resp.into_inner())?)
+    }
+
+    /// get job by id or name
+    pub async fn get(&self, name_or_id: &str) -> Result<Job> {
+        let resp = self
+            .client
+            .clone()
+            .get(self.request(Self::job_ref(name_or_id)))
+            .await
+            .map_err(status_error)?;
+        Ok(Job::try_from(resp.into_inner())?)
+    }
+
+    pub async fn list(&self, list_job_params: &ListJobParams) -> Result<JobList> {
+        let resp = self
+            .client
+            .clone()
+            .list(self.request(pb::ListJobsRequest::from(list_job_params.clone())))
+            .await
+            .map_err(status_error)?
+            .into_inner();
+        let jobs = resp
+            .jobs
+            .into_iter()
+            .map(Job::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(JobList {
+            jobs,
+            next_cursor: resp.next_cursor,
+            queue_positions: resp
+                .queue_positions
+                .into_iter()
+                .map(|(id, position)| (id, position as usize))
+                .collect(),
+        })
+    }
+
+    pub async fn run_job(&self, job_id: &ObjectId) -> Result<()> {
+        self.client
+            .clone()
+            .run(self.request(Self::job_ref(&job_id.to_hex())))
+            .await
+            .map_err(status_error)?;
+        Ok(())
+    }
+
+    /// cancel_job use grace period of daemon if not set
+    pub async fn cancel_job(
+        &self,
+        job_id: &ObjectId,
+        grace_period: Option<Duration>,
+    ) -> Result<()> {
+        let request = pb::CancelJobRequest {
+            id: job_id.to_hex(),
+            grace_period: grace_period.map(|period| period.as_secs()),
+        };
+        self.client
+            .clone()
+            .cancel(self.request(request))
+            .await
+            .map_err(status_error)?;
+        Ok(())
+    }
+
+    pub async fn clean_job(&self, job_id: &ObjectId) -> Result<()> {
+        self.client
+            .clone()
+            .clean(self.request(Self::job_ref(&job_id.to_hex())))
+            .await
+            .map_err(status_error)?;
+        Ok(())
+    }
+
+    pub async fn get_job_detail(&self, job_id: &ObjectId) -> Result<JobDetails> {
+        let resp = self
+            .client
+            .clone()
+            .details(self.request(Self::job_ref(&job_id.to_hex())))
+            .await
+            .map_err(status_error)?;
+        Ok(JobDetails::try_from(resp.into_inner())?)
+    }
+
+    pub async fn watch(&self, job_id: &ObjectId) -> Result<JobGrpcEventStream> {
+        let stream = self
+            .client
+            .clone()
+            .watch(self.request(Self::job_ref(&job_id.to_hex())))
+            .await
+            .map_err(status_error)?
+            .into_inner();
+        Ok(JobGrpcEventStream { stream })
+    }
+}
@@ -1,24 +1,65 @@
+use super::api_error;
 use crate::{
     core::db::{
+        CancelJobParams,
         GetJobParams,
+        HookDelivery,
         Job,
+        JobList,
         JobUpdateInfo,
+        ListJobParams,
+        SortOrder,
+    },
+    driver::Plan,
+    job::{
+        job_mgr::JobDetails,
+        watch::JobEvent,
     },
-    job::job_mgr::JobDetails,
     utils::StdIntoAnyhowResult,
 };
-use anyhow::{
-    anyhow,
-    Result,
-};
+use anyhow::Result;
 
 use mongodb::bson::oid::ObjectId;
 use reqwest::{
     Client,
+    Response,
     StatusCode,
     Url,
 };
 
+/// JobEventStream read events of a watched job
+pub struct JobEventStream {
+    resp: Response,
+    buf: Vec<u8>,
+}
+
+impl JobEventStream {
+    /// next return None when server closed the stream
+    pub async fn next(&mut self) -> Result<Option<JobEvent>> {
+        loop {
+            if let Some(pos) = self.buf.windows(2).position(|window| window == b"\n\n") {
+                let frame: Vec<u8> = self.buf.drain(..pos + 2).collect();
+                let frame = String::from_utf8(frame).anyhow()?;
+                let data: String = frame
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("data:"))
+                    .map(str::trim_start)
+                    .collect();
+                //comments only keep connection alive
+                if data.is_empty() {
+                    continue;
+                }
+                return serde_json::from_str(&data).anyhow().map(Some);
+            }
+
+            match self.resp.chunk().await.anyhow()? {
+                Some(chunk) => self.buf.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
 pub struct JobClient {
     pub(crate) client: Client,
     pub(crate) base_uri: Url,
@@ -35,13 +76,28 @@ impl JobClient {
             .anyhow()?;
 
         if !resp.status().is_success() {
-            let code = resp.status();
-            let err_msg = resp
-                .bytes()
-                .await
-                .anyhow()
-                .and_then(|body| String::from_utf8(body.into()).anyhow())?;
-            return Err(anyhow!("create job {code} reason {err_msg}"));
+            return Err(api_error(resp).await);
+        }
+
+        resp.bytes()
+            .await
+            .anyhow()
+            .and_then(|body| serde_json::from_slice(&body).anyhow())
+            .anyhow()
+    }
+
+    /// plan render what deploying job would create without creating it
+    pub async fn plan(&self, job: &Job) -> Result<Plan> {
+        let resp = self
+            .client
+            .post(self.base_uri.clone().join("job/plan")?)
+            .json(&job)
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
         }
 
         resp.bytes()
@@ -69,13 +125,7 @@ impl JobClient {
         }
 
         if !resp.status().is_success() {
-            let code = resp.status();
-            let err_msg = resp
-                .bytes()
-                .await
-                .anyhow()
-                .and_then(|body| String::from_utf8(body.into()).anyhow())?;
-            return Err(anyhow!("get job {code} reason {err_msg}"));
+            return Err(api_error(resp).await);
         }
 
         resp.bytes()
@@ -104,13 +154,7 @@ impl JobClient {
         }
 
         if !resp.status().is_success() {
-            let code = resp.status();
-            let err_msg = resp
-                .bytes()
-                .await
-                .anyhow()
-                .and_then(|body| String::from_utf8(body.into()).anyhow())?;
-            return Err(anyhow!("get job {code} reason {err_msg}"));
+            return Err(api_error(resp).await);
         }
 
         resp.bytes()
@@ -120,22 +164,73 @@ impl JobClient {
             .anyhow()
     }
 
-    pub async fn list(&self) -> Result<Vec<Job>> {
-        let resp = self
-            .client
-            .get(self.base_uri.clone().join("jobs")?)
-            .send()
-            .await
-            .anyhow()?;
+    pub async fn list(&self, list_job_params: &ListJobParams) -> Result<JobList> {
+        let mut uri = self.base_uri.clone().join("jobs")?;
+        {
+            let mut query = uri.query_pairs_mut();
+            if !list_job_params.states.is_empty() {
+                let states = list_job_params
+                    .states
+                    .iter()
+                    .map(|state| format!("{state:?}"))
+                    .collect::<Vec<_>>();
+                query.append_pair("states", &states.join(","));
+            }
+            if !list_job_params.labels.is_empty() {
+                query.append_pair("labels", &list_job_params.labels.join(","));
+            }
+            for (key, value) in [
+                ("name_prefix", list_job_params.name_prefix.clone()),
+                ("name_regex", list_job_params.name_regex.clone()),
+                ("owner", list_job_params.owner.clone()),
+                (
+                    "schedule_id",
+                    list_job_params.schedule_id.map(|id| id.to_hex()),
+                ),
+                (
+                    "trigger_id",
+                    list_job_params.trigger_id.map(|id| id.to_hex()),
+                ),
+                (
+                    "created_after",
+                    list_job_params.created_after.map(|tm| tm.to_string()),
+                ),
+                (
+                    "created_before",
+                    list_job_params.created_before.map(|tm| tm.to_string()),
+                ),
+                (
+                    "updated_after",
+                    list_job_params.updated_after.map(|tm| tm.to_string()),
+                ),
+                (
+                    "updated_before",
+                    list_job_params.updated_before.map(|tm| tm.to_string()),
+                ),
+                (
+                    "limit",
+                    list_job_params.limit.map(|limit| limit.to_string()),
+                ),
+                ("cursor", list_job_params.cursor.clone()),
+            ] {
+                if let Some(value) = value {
+                    query.append_pair(key, &value);
+                }
+            }
+            query.append_pair("sort", list_job_params.sort.field_name());
+            query.append_pair(
+                "order",
+                match list_job_params.order {
+                    SortOrder::Asc => "asc",
+                    SortOrder::Desc => "desc",
+                },
+            );
+        }
+
+        let resp = self.client.get(uri).send().await.anyhow()?;
 
         if !resp.status().is_success() {
-            let code = resp.status();
-            let err_msg = resp
-                .bytes()
-                .await
-                .anyhow()
-                .and_then(|body| String::from_utf8(body.into()).anyhow())?;
-            return Err(anyhow!("list job {code} reason {err_msg}"));
+            return Err(api_error(resp).await);
         }
 
         resp.bytes()
@@ -159,13 +254,7 @@ impl JobClient {
             .anyhow()?;
 
         if !resp.status().is_success() {
-            let code = resp.status();
-            let err_msg = resp
-                .bytes()
-                .await
-                .anyhow()
-                .and_then(|body| String::from_utf8(body.into()).anyhow())?;
-            return Err(anyhow!("delete job {code} reason {err_msg}"));
+            return Err(api_error(resp).await);
         }
 
         Ok(())
@@ -186,18 +275,34 @@ impl JobClient {
             .anyhow()?;
 
         if !resp.status().is_success() {
-            let code = resp.status();
-            let err_msg = resp
-                .bytes()
-                .await
-                .anyhow()
-                .and_then(|body| String::from_utf8(body.into()).anyhow())?;
-            return Err(anyhow!("request update job {code} reason {err_msg}"));
+            return Err(api_error(resp).await);
         }
 
         Ok(())
     }
 
+    /// watch stream changes of job details, first event is a snapshot
+    pub async fn watch(&self, job_id: &ObjectId) -> Result<JobEventStream> {
+        let resp = self
+            .client
+            .get(
+                self.base_uri
+                    .clone()
+                    .join("job/")?
+                    .join("watch/")?
+                    .join(job_id.to_hex().as_str())?,
+            )
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        Ok(JobEventStream { resp, buf: vec![] })
+    }
+
     pub async fn get_job_detail(&self, job_id: &ObjectId) -> Result<JobDetails> {
         let resp = self
             .client
@@ -213,13 +318,32 @@ impl JobClient {
             .anyhow()?;
 
         if !resp.status().is_success() {
-            let code = resp.status();
-            let err_msg = resp
-                .bytes()
-                .await
-                .anyhow()
-                .and_then(|body| String::from_utf8(body.into()).anyhow())?;
-            return Err(anyhow!("request job detail {code} reason {err_msg}"));
+            return Err(api_error(resp).await);
+        }
+
+        resp.bytes()
+            .await
+            .anyhow()
+            .and_then(|body| serde_json::from_slice(&body).anyhow())
+            .anyhow()
+    }
+
+    pub async fn list_deliveries(&self, job_id: &ObjectId) -> Result<Vec<HookDelivery>> {
+        let resp = self
+            .client
+            .get(
+                self.base_uri
+                    .clone()
+                    .join("job/")?
+                    .join("deliveries/")?
+                    .join(job_id.to_hex().as_str())?,
+            )
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
         }
 
         resp.bytes()
@@ -244,13 +368,50 @@ impl JobClient {
             .anyhow()?;
 
         if !resp.status().is_success() {
-            let code = resp.status();
-            let err_msg = resp
-                .bytes()
-                .await
-                .anyhow()
-                .and_then(|body| String::from_utf8(body.into()).anyhow())?;
-            return Err(anyhow!("request start job {code} reason {err_msg}"));
+            return Err(api_error(resp).await);
+        }
+
+        Ok(())
+    }
+
+    pub async fn retry_job(&self, job_id: &ObjectId) -> Result<()> {
+        let resp = self
+            .client
+            .post(
+                self.base_uri
+                    .clone()
+                    .join("job/")?
+                    .join("retry/")?
+                    .join(job_id.to_hex().as_str())?,
+            )
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        Ok(())
+    }
+
+    pub async fn cancel_job(&self, job_id: &ObjectId, params: &CancelJobParams) -> Result<()> {
+        let resp = self
+            .client
+            .post(
+                self.base_uri
+                    .clone()
+                    .join("job/")?
+                    .join("cancel/")?
+                    .join(job_id.to_hex().as_str())?,
+            )
+            .query(params)
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
         }
 
         Ok(())
@@ -270,13 +431,7 @@ impl JobClient {
             .anyhow()?;
 
         if !resp.status().is_success() {
-            let code = resp.status();
-            let err_msg = resp
-                .bytes()
-                .await
-                .anyhow()
-                .and_then(|body| String::from_utf8(body.into()).anyhow())?;
-            return Err(anyhow!("request start job {code} reason {err_msg}"));
+            return Err(api_error(resp).await);
         }
 
         Ok(())
@@ -1,12 +1,28 @@
+mod data;
+mod grpc;
 mod job;
+mod schedule;
+mod trigger;
 
+use super::error::{
+    ApiError,
+    ErrorCode,
+};
 use anyhow::Result;
+use data::DataClient;
+pub use grpc::{
+    JobGrpcClient,
+    JobGrpcEventStream,
+};
 use job::JobClient;
 use reqwest::{
     header,
     Client,
+    Response,
     Url,
 };
+use schedule::ScheduleClient;
+use trigger::TriggerClient;
 #[derive(Clone)]
 pub struct JzFlowClient {
     client: Client,
@@ -15,15 +31,31 @@ pub struct JzFlowClient {
 
 impl JzFlowClient {
     pub fn new(base_uri: &str) -> Result<Self> {
+        let client = Client::builder()
+            .default_headers(Self::default_headers())
+            .build()?;
+        let base_uri = Url::parse(base_uri)?.join("/api/v1/")?;
+        Ok(JzFlowClient { client, base_uri })
+    }
+
+    fn default_headers() -> header::HeaderMap {
         let mut headers = header::HeaderMap::new();
         headers.insert(
             "Content-Type",
             header::HeaderValue::from_static("application/json"),
         );
+        headers
+    }
 
-        let client = Client::builder().default_headers(headers).build()?;
-        let base_uri = Url::parse(base_uri)?.join("/api/v1/")?;
-        Ok(JzFlowClient { client, base_uri })
+    /// set_token send token as bearer authorization with every request
+    pub fn set_token(mut self, token: &str) -> Result<Self> {
+        let mut headers = Self::default_headers();
+        let mut value = header::HeaderValue::from_str(&format!("Bearer {token}"))?;
+        value.set_sensitive(true);
+        headers.insert(header::AUTHORIZATION, value);
+
+        self.client = Client::builder().default_headers(headers).build()?;
+        Ok(self)
     }
 
     pub fn job(&self) -> JobClient {
@@ -32,4 +64,43 @@ impl JzFlowClient {
             base_uri: self.base_uri.clone(),
         }
     }
+
+    pub fn data(&self) -> DataClient {
+        DataClient {
+            client: self.client.clone(),
+            base_uri: self.base_uri.clone(),
+        }
+    }
+
+    pub fn schedule(&self) -> ScheduleClient {
+        ScheduleClient {
+            client: self.client.clone(),
+            base_uri: self.base_uri.clone(),
+        }
+    }
+
+    pub fn trigger(&self) -> TriggerClient {
+        TriggerClient {
+            client: self.client.clone(),
+            base_uri: self.base_uri.clone(),
+        }
+    }
+}
+
+/// api_error decode body of failed response, downcast the returned error to ApiError to match on
+/// its code
+pub(crate) async fn api_error(resp: Response) -> anyhow::Error {
+    let status = resp.status();
+    let body = match resp.bytes().await {
+        Ok(body) => body,
+        Err(err) => return err.into(),
+    };
+    serde_json::from_slice::<ApiError>(&body)
+        .unwrap_or_else(|_| {
+            ApiError::new(
+                ErrorCode::from_status(status.as_u16()),
+                String::from_utf8_lossy(&body),
+            )
+        })
+        .into()
 }
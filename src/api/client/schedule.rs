@@ -0,0 +1,140 @@
+use super::api_error;
+use crate::{
+    core::db::{
+        GetScheduleParams,
+        Schedule,
+    },
+    utils::StdIntoAnyhowResult,
+};
+use anyhow::Result;
+
+use mongodb::bson::oid::ObjectId;
+use reqwest::{
+    Client,
+    StatusCode,
+    Url,
+};
+
+pub struct ScheduleClient {
+    pub(crate) client: Client,
+    pub(crate) base_uri: Url,
+}
+
+impl ScheduleClient {
+    pub async fn create(&self, schedule: &Schedule) -> Result<Schedule> {
+        let resp = self
+            .client
+            .post(self.base_uri.clone().join("schedule")?)
+            .json(&schedule)
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        resp.bytes()
+            .await
+            .anyhow()
+            .and_then(|body| serde_json::from_slice(&body).anyhow())
+            .anyhow()
+    }
+
+    pub async fn get(&self, params: &GetScheduleParams) -> Result<Option<Schedule>> {
+        let mut uri = self.base_uri.clone().join("schedule")?;
+
+        if let Some(id) = params.id.as_ref() {
+            uri.query_pairs_mut()
+                .append_pair("id", id.to_string().as_str());
+        }
+
+        if let Some(name) = params.name.as_ref() {
+            uri.query_pairs_mut().append_pair("name", name.as_str());
+        }
+
+        let resp = self.client.get(uri).send().await.anyhow()?;
+
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        resp.bytes()
+            .await
+            .anyhow()
+            .and_then(|body| serde_json::from_slice(&body).anyhow())
+            .anyhow()
+    }
+
+    pub async fn list(&self) -> Result<Vec<Schedule>> {
+        let resp = self
+            .client
+            .get(self.base_uri.clone().join("schedules")?)
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        resp.bytes()
+            .await
+            .anyhow()
+            .and_then(|body| serde_json::from_slice(&body).anyhow())
+            .anyhow()
+    }
+
+    pub async fn pause(&self, schedule_id: &ObjectId) -> Result<()> {
+        self.post_action("pause/", schedule_id).await
+    }
+
+    pub async fn resume(&self, schedule_id: &ObjectId) -> Result<()> {
+        self.post_action("resume/", schedule_id).await
+    }
+
+    async fn post_action(&self, action: &str, schedule_id: &ObjectId) -> Result<()> {
+        let resp = self
+            .client
+            .post(
+                self.base_uri
+                    .clone()
+                    .join("schedule/")?
+                    .join(action)?
+                    .join(schedule_id.to_hex().as_str())?,
+            )
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete(&self, schedule_id: &ObjectId) -> Result<()> {
+        let resp = self
+            .client
+            .delete(
+                self.base_uri
+                    .clone()
+                    .join("schedule/")?
+                    .join(schedule_id.to_hex().as_str())?,
+            )
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use super::api_error;
+use crate::{
+    core::db::{
+        GetTriggerParams,
+        Trigger,
+    },
+    utils::StdIntoAnyhowResult,
+};
+use anyhow::Result;
+
+use mongodb::bson::oid::ObjectId;
+use reqwest::{
+    Client,
+    StatusCode,
+    Url,
+};
+
+pub struct TriggerClient {
+    pub(crate) client: Client,
+    pub(crate) base_uri: Url,
+}
+
+impl TriggerClient {
+    pub async fn create(&self, trigger: &Trigger) -> Result<Trigger> {
+        let resp = self
+            .client
+            .post(self.base_uri.clone().join("trigger")?)
+            .json(&trigger)
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        resp.bytes()
+            .await
+            .anyhow()
+            .and_then(|body| serde_json::from_slice(&body).anyhow())
+            .anyhow()
+    }
+
+    pub async fn get(&self, params: &GetTriggerParams) -> Result<Option<Trigger>> {
+        let mut uri = self.base_uri.clone().join("trigger")?;
+
+        if let Some(id) = params.id.as_ref() {
+            uri.query_pairs_mut()
+                .append_pair("id", id.to_string().as_str());
+        }
+
+        if let Some(name) = params.name.as_ref() {
+            uri.query_pairs_mut().append_pair("name", name.as_str());
+        }
+
+        let resp = self.client.get(uri).send().await.anyhow()?;
+
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        resp.bytes()
+            .await
+            .anyhow()
+            .and_then(|body| serde_json::from_slice(&body).anyhow())
+            .anyhow()
+    }
+
+    pub async fn list(&self) -> Result<Vec<Trigger>> {
+        let resp = self
+            .client
+            .get(self.base_uri.clone().join("triggers")?)
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        resp.bytes()
+            .await
+            .anyhow()
+            .and_then(|body| serde_json::from_slice(&body).anyhow())
+            .anyhow()
+    }
+
+    pub async fn delete(&self, trigger_id: &ObjectId) -> Result<()> {
+        let resp = self
+            .client
+            .delete(
+                self.base_uri
+                    .clone()
+                    .join("trigger/")?
+                    .join(trigger_id.to_hex().as_str())?,
+            )
+            .send()
+            .await
+            .anyhow()?;
+
+        if !resp.status().is_success() {
+            return Err(api_error(resp).await);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use super::{
+    auth::{
+        Principal,
+        Role,
+    },
+    error::ApiError,
+    job_api::find_job,
+    openapi::api_resource,
+};
+use crate::{
+    core::db::{
+        JobDbRepo,
+        ListDataParams,
+        MainDbRepo,
+    },
+    driver::Driver,
+    job::job_mgr::JobManager,
+};
+use actix_web::{
+    web,
+    HttpResponse,
+};
+
+async fn data_summary<D, MAINR, JOBR>(
+    db_repo: web::Data<MAINR>,
+    job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let job = find_job(db_repo.as_ref(), &path).await?;
+    let summary = job_manager.data_summary(&job).await?;
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+async fn list_data<D, MAINR, JOBR>(
+    db_repo: web::Data<MAINR>,
+    job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
+    path: web::Path<(String, String)>,
+    query: web::Query<ListDataParams>,
+) -> Result<HttpResponse, ApiError>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let (name_or_id, node_name) = path.into_inner();
+    let job = find_job(db_repo.as_ref(), &name_or_id).await?;
+    let data_list = job_manager
+        .list_data(&job, &node_name, &query.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(data_list))
+}
+
+async fn get_batch<D, MAINR, JOBR>(
+    db_repo: web::Data<MAINR>,
+    job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, ApiError>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let (name_or_id, data_id) = path.into_inner();
+    let job = find_job(db_repo.as_ref(), &name_or_id).await?;
+    let records = job_manager.batch_history(&job, &data_id).await?;
+    if records.is_empty() {
+        return Err(ApiError::not_found(format!("data {data_id} not found")));
+    }
+    Ok(HttpResponse::Ok().json(records))
+}
+
+pub(super) fn data_route_config<D, MAINR, JOBR>(cfg: &mut web::ServiceConfig)
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    cfg.service(
+        api_resource("/job/data/{id}").route(web::get().to(data_summary::<D, MAINR, JOBR>)),
+    )
+    .service(
+        api_resource("/job/data/{id}/{node}").route(web::get().to(list_data::<D, MAINR, JOBR>)),
+    )
+    .service(
+        api_resource("/job/batch/{id}/{data_id}").route(web::get().to(get_batch::<D, MAINR, JOBR>)),
+    );
+}
@@ -0,0 +1,185 @@
+use crate::{
+    core::db::{
+        InvalidSpec,
+        InvalidTransition,
+        NoJobDb,
+    },
+    dbrepo::is_duplicate_key,
+};
+use actix_web::{
+    http::StatusCode,
+    HttpResponse,
+    ResponseError,
+};
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_variant::to_variant_name;
+use std::fmt;
+
+/// ErrorCode is the stable kind of an api error, clients should match on it instead of message
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    /// job state can not move to the requested one, details carry allowed transitions
+    InvalidTransition,
+    UnsupportedMediaType,
+    Unprocessable,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::BadRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Conflict | ErrorCode::InvalidTransition => StatusCode::CONFLICT,
+            ErrorCode::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ErrorCode::Unprocessable => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// from_status guess code of responses not produced by the api, eg. by a proxy
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            400 => ErrorCode::BadRequest,
+            401 => ErrorCode::Unauthorized,
+            403 => ErrorCode::Forbidden,
+            404 => ErrorCode::NotFound,
+            409 => ErrorCode::Conflict,
+            415 => ErrorCode::UnsupportedMediaType,
+            422 => ErrorCode::Unprocessable,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
+/// ApiError is the body of every failed api response
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiError {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn set_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::BadRequest, message)
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Unauthorized, message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Forbidden, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = to_variant_name(&self.code).map_err(|_| fmt::Error)?;
+        write!(f, "{code}: {}", self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.code.status()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(self)
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        if let Some(api_error) = err.downcast_ref::<ApiError>() {
+            return api_error.clone();
+        }
+        if let Some(transition) = err.downcast_ref::<InvalidTransition>() {
+            let api_error = ApiError::new(ErrorCode::InvalidTransition, transition.to_string());
+            return match serde_json::to_value(transition) {
+                Ok(details) => api_error.set_details(details),
+                Err(_) => api_error,
+            };
+        }
+        if let Some(spec) = err.downcast_ref::<InvalidSpec>() {
+            return ApiError::new(ErrorCode::Unprocessable, spec.to_string());
+        }
+        if let Some(no_job_db) = err.downcast_ref::<NoJobDb>() {
+            return ApiError::new(ErrorCode::Conflict, no_job_db.to_string());
+        }
+        if is_duplicate_key(&err) {
+            return ApiError::new(ErrorCode::Conflict, err.to_string());
+        }
+        ApiError::new(ErrorCode::Internal, err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::JobState;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_api_error() {
+        let err: ApiError =
+            anyhow::Error::from(InvalidTransition::new(JobState::Finish, JobState::Running)).into();
+        assert_eq!(err.code, ErrorCode::InvalidTransition);
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+        assert_eq!(
+            serde_json::to_value(&err).unwrap()["details"]["allowed"],
+            serde_json::json!(["Clean"])
+        );
+
+        let err: ApiError = anyhow::Error::from(ApiError::not_found("job a not found")).into();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(err.to_string(), "not_found: job a not found");
+
+        let err: ApiError = anyhow::Error::from(NoJobDb {
+            name: "a".to_string(),
+            state: JobState::Clean,
+        })
+        .into();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+
+        let err: ApiError = anyhow!("boom").into();
+        assert_eq!(
+            serde_json::to_string(&err).unwrap(),
+            r#"{"code":"internal","message":"boom"}"#
+        );
+    }
+}
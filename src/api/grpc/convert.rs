@@ -0,0 +1,594 @@
+use crate::{
+    api::error::{
+        ApiError,
+        ErrorCode,
+    },
+    core::db::{
+        Hook,
+        Job,
+        JobAttempt,
+        JobSortField,
+        JobState,
+        ListJobParams,
+        NodeSummary,
+        RunSummary,
+        SortOrder,
+        TimeoutAction,
+        TrackerState,
+    },
+    driver::{
+        NodeStatus,
+        PodStauts,
+    },
+    job::{
+        job_mgr::JobDetails,
+        watch::JobEvent,
+    },
+    network::{
+        common::Empty,
+        jobservice as pb,
+    },
+};
+use mongodb::bson::oid::ObjectId;
+use std::str::FromStr;
+use tonic::{
+    Code,
+    Status,
+};
+
+/// enum_map convert between a domain enum and its protobuf enum, protobuf enums arrive as i32
+/// and unknown values are rejected
+macro_rules! enum_map {
+    ($domain:ident, $proto:ident, { $($from:ident => $to:ident),+ $(,)? }) => {
+        impl From<$domain> for pb::$proto {
+            fn from(value: $domain) -> Self {
+                match value {
+                    $($domain::$from => pb::$proto::$to,)+
+                }
+            }
+        }
+
+        impl TryFrom<i32> for $domain {
+            type Error = ApiError;
+
+            fn try_from(value: i32) -> Result<Self, ApiError> {
+                match pb::$proto::try_from(value) {
+                    $(Ok(pb::$proto::$to) => Ok($domain::$from),)+
+                    Err(_) => Err(ApiError::bad_request(format!(
+                        "unknown {} {value}",
+                        stringify!($proto)
+                    ))),
+                }
+            }
+        }
+    };
+}
+
+enum_map!(JobState, JobState, {
+    Created => Created,
+    Queued => Queued,
+    Selected => Selected,
+    Deployed => Deployed,
+    Running => Running,
+    Error => Error,
+    Finish => Finish,
+    Clean => Clean,
+    Cancelling => Cancelling,
+    Cancelled => Cancelled,
+    Paused => Paused,
+});
+
+enum_map!(TimeoutAction, TimeoutAction, {
+    Fail => Fail,
+    Cancel => Cancel,
+    Pause => Pause,
+    Notify => Notify,
+});
+
+enum_map!(JobSortField, JobSortField, {
+    CreatedAt => CreatedAt,
+    UpdatedAt => UpdatedAt,
+    Name => Name,
+});
+
+enum_map!(SortOrder, SortOrder, {
+    Asc => Asc,
+    Desc => Desc,
+});
+
+enum_map!(TrackerState, TrackerState, {
+    Init => Init,
+    Ready => Ready,
+    Stop => Stop,
+    Stopped => Stopped,
+    InComingFinish => IncomingFinish,
+    Finish => Finish,
+    Error => Error,
+});
+
+fn parse_object_id(field: &str, value: &str) -> Result<ObjectId, ApiError> {
+    ObjectId::from_str(value)
+        .map_err(|err| ApiError::bad_request(format!("invalid {field} {value}: {err}")))
+}
+
+fn parse_optional_object_id(
+    field: &str,
+    value: Option<String>,
+) -> Result<Option<ObjectId>, ApiError> {
+    value
+        .map(|value| parse_object_id(field, &value))
+        .transpose()
+}
+
+impl From<Hook> for pb::Hook {
+    fn from(hook: Hook) -> Self {
+        let kind = match hook {
+            Hook::Webhook { url, secret } => pb::hook::Kind::Webhook(pb::Webhook { url, secret }),
+            Hook::Command { program, args } => {
+                pb::hook::Kind::Command(pb::Command { program, args })
+            }
+        };
+        pb::Hook { kind: Some(kind) }
+    }
+}
+
+impl TryFrom<pb::Hook> for Hook {
+    type Error = ApiError;
+
+    fn try_from(hook: pb::Hook) -> Result<Self, Self::Error> {
+        match hook.kind {
+            Some(pb::hook::Kind::Webhook(pb::Webhook { url, secret })) => {
+                Ok(Hook::Webhook { url, secret })
+            }
+            Some(pb::hook::Kind::Command(pb::Command { program, args })) => {
+                Ok(Hook::Command { program, args })
+            }
+            None => Err(ApiError::bad_request("hook must be webhook or command")),
+        }
+    }
+}
+
+impl From<JobAttempt> for pb::JobAttempt {
+    fn from(attempt: JobAttempt) -> Self {
+        pb::JobAttempt {
+            state: pb::JobState::from(attempt.state).into(),
+            started_at: attempt.started_at,
+            ended_at: attempt.ended_at,
+        }
+    }
+}
+
+impl TryFrom<pb::JobAttempt> for JobAttempt {
+    type Error = ApiError;
+
+    fn try_from(attempt: pb::JobAttempt) -> Result<Self, Self::Error> {
+        Ok(JobAttempt {
+            state: attempt.state.try_into()?,
+            started_at: attempt.started_at,
+            ended_at: attempt.ended_at,
+        })
+    }
+}
+
+impl From<NodeSummary> for pb::NodeSummary {
+    fn from(summary: NodeSummary) -> Self {
+        pb::NodeSummary {
+            node_name: summary.node_name,
+            wall_time: summary.wall_time,
+            batches_in: summary.batches_in,
+            batches_out: summary.batches_out,
+            files_in: summary.files_in,
+            files_out: summary.files_out,
+            errors: summary.errors,
+            peak_backlog: summary.peak_backlog,
+            avg_batch_latency: summary.avg_batch_latency,
+        }
+    }
+}
+
+impl From<pb::NodeSummary> for NodeSummary {
+    fn from(summary: pb::NodeSummary) -> Self {
+        NodeSummary {
+            node_name: summary.node_name,
+            wall_time: summary.wall_time,
+            batches_in: summary.batches_in,
+            batches_out: summary.batches_out,
+            files_in: summary.files_in,
+            files_out: summary.files_out,
+            errors: summary.errors,
+            peak_backlog: summary.peak_backlog,
+            avg_batch_latency: summary.avg_batch_latency,
+        }
+    }
+}
+
+impl From<Job> for pb::Job {
+    fn from(job: Job) -> Self {
+        pb::Job {
+            id: job.id.to_hex(),
+            name: job.name,
+            graph_json: job.graph_json,
+            state: pb::JobState::from(job.state).into(),
+            manual_run: job.manual_run,
+            priority: job.priority,
+            labels: job.labels,
+            timeout: job.timeout,
+            stall_timeout: job.stall_timeout,
+            timeout_action: pb::TimeoutAction::from(job.timeout_action).into(),
+            started_at: job.started_at,
+            active_secs: job.active_secs,
+            reason: job.reason,
+            hooks: job.hooks.into_iter().map(Into::into).collect(),
+            attempts: job.attempts.into_iter().map(Into::into).collect(),
+            cancel_deadline: job.cancel_deadline,
+            completed_batches: job.completed_batches,
+            schedule_id: job.schedule_id.map(|id| id.to_hex()),
+            trigger_id: job.trigger_id.map(|id| id.to_hex()),
+            owner: job.owner,
+            holder: job.holder,
+            summary: job.summary.map(|summary| pb::RunSummary {
+                nodes: summary.nodes.into_iter().map(Into::into).collect(),
+                created_at: summary.created_at,
+            }),
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+        }
+    }
+}
+
+impl TryFrom<pb::Job> for Job {
+    type Error = ApiError;
+
+    /// empty id is replaced by a new one, so that jobs to create need no id
+    fn try_from(job: pb::Job) -> Result<Self, Self::Error> {
+        let id = if job.id.is_empty() {
+            ObjectId::new()
+        } else {
+            parse_object_id("id", &job.id)?
+        };
+        Ok(Job {
+            id,
+            name: job.name,
+            graph_json: job.graph_json,
+            state: job.state.try_into()?,
+            manual_run: job.manual_run,
+            priority: job.priority,
+            labels: job.labels,
+            timeout: job.timeout,
+            stall_timeout: job.stall_timeout,
+            timeout_action: job.timeout_action.try_into()?,
+            started_at: job.started_at,
+            active_secs: job.active_secs,
+            reason: job.reason,
+            hooks: job
+                .hooks
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+            attempts: job
+                .attempts
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+            cancel_deadline: job.cancel_deadline,
+            completed_batches: job.completed_batches,
+            schedule_id: parse_optional_object_id("schedule_id", job.schedule_id)?,
+            trigger_id: parse_optional_object_id("trigger_id", job.trigger_id)?,
+            owner: job.owner,
+            holder: job.holder,
+            summary: job.summary.map(|summary| RunSummary {
+                nodes: summary.nodes.into_iter().map(Into::into).collect(),
+                created_at: summary.created_at,
+            }),
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+        })
+    }
+}
+
+impl From<ListJobParams> for pb::ListJobsRequest {
+    fn from(params: ListJobParams) -> Self {
+        pb::ListJobsRequest {
+            states: params
+                .states
+                .into_iter()
+                .map(|state| pb::JobState::from(state).into())
+                .collect(),
+            name_prefix: params.name_prefix,
+            name_regex: params.name_regex,
+            labels: params.labels,
+            owner: params.owner,
+            schedule_id: params.schedule_id.map(|id| id.to_hex()),
+            trigger_id: params.trigger_id.map(|id| id.to_hex()),
+            created_after: params.created_after,
+            created_before: params.created_before,
+            updated_after: params.updated_after,
+            updated_before: params.updated_before,
+            sort: pb::JobSortField::from(params.sort).into(),
+            order: pb::SortOrder::from(params.order).into(),
+            limit: params.limit,
+            cursor: params.cursor,
+        }
+    }
+}
+
+impl TryFrom<pb::ListJobsRequest> for ListJobParams {
+    type Error = ApiError;
+
+    fn try_from(request: pb::ListJobsRequest) -> Result<Self, Self::Error> {
+        Ok(ListJobParams {
+            states: request
+                .states
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+            name_prefix: request.name_prefix,
+            name_regex: request.name_regex,
+            labels: request.labels,
+            owner: request.owner,
+            schedule_id: parse_optional_object_id("schedule_id", request.schedule_id)?,
+            trigger_id: parse_optional_object_id("trigger_id", request.trigger_id)?,
+            created_after: request.created_after,
+            created_before: request.created_before,
+            updated_after: request.updated_after,
+            updated_before: request.updated_before,
+            sort: request.sort.try_into()?,
+            order: request.order.try_into()?,
+            limit: request.limit,
+            cursor: request.cursor,
+        })
+    }
+}
+
+impl From<NodeStatus> for pb::NodeStatus {
+    fn from(status: NodeStatus) -> Self {
+        pb::NodeStatus {
+            name: status.name,
+            state: pb::TrackerState::from(status.state).into(),
+            data_count: status.data_count as u64,
+            replicas: status.replicas,
+            storage: status.storage,
+            pods: status
+                .pods
+                .into_iter()
+                .map(|(name, pod)| {
+                    let pod = pb::PodStatus {
+                        state: pod.state,
+                        cpu_usage: pod.cpu_usage,
+                        memory_usage: pod.memory_usage,
+                    };
+                    (name, pod)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::NodeStatus> for NodeStatus {
+    type Error = ApiError;
+
+    fn try_from(status: pb::NodeStatus) -> Result<Self, Self::Error> {
+        Ok(NodeStatus {
+            name: status.name,
+            state: status.state.try_into()?,
+            data_count: status.data_count as usize,
+            replicas: status.replicas,
+            storage: status.storage,
+            pods: status
+                .pods
+                .into_iter()
+                .map(|(name, pod)| {
+                    let pod = PodStauts {
+                        state: pod.state,
+                        cpu_usage: pod.cpu_usage,
+                        memory_usage: pod.memory_usage,
+                    };
+                    (name, pod)
+                })
+                .collect(),
+        })
+    }
+}
+
+impl From<JobDetails> for pb::JobDetails {
+    fn from(details: JobDetails) -> Self {
+        pb::JobDetails {
+            job: Some(details.job.into()),
+            node_status: details.node_status.map(|nodes| pb::NodeStatusList {
+                nodes: nodes.into_iter().map(Into::into).collect(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<pb::JobDetails> for JobDetails {
+    type Error = ApiError;
+
+    fn try_from(details: pb::JobDetails) -> Result<Self, Self::Error> {
+        Ok(JobDetails {
+            job: details
+                .job
+                .ok_or_else(|| ApiError::bad_request("job of details is not set"))?
+                .try_into()?,
+            node_status: details
+                .node_status
+                .map(|list| {
+                    list.nodes
+                        .into_iter()
+                        .map(TryInto::try_into)
+                        .collect::<Result<_, _>>()
+                })
+                .transpose()?,
+        })
+    }
+}
+
+impl From<JobEvent> for pb::JobEvent {
+    fn from(event: JobEvent) -> Self {
+        let event = match event {
+            JobEvent::Snapshot { details } => pb::job_event::Event::Snapshot(details.into()),
+            JobEvent::JobChanged { job } => pb::job_event::Event::JobChanged(job.into()),
+            JobEvent::NodeChanged { node } => pb::job_event::Event::NodeChanged(node.into()),
+            JobEvent::NodesCleared => pb::job_event::Event::NodesCleared(Empty {}),
+        };
+        pb::JobEvent { event: Some(event) }
+    }
+}
+
+impl TryFrom<pb::JobEvent> for JobEvent {
+    type Error = ApiError;
+
+    fn try_from(event: pb::JobEvent) -> Result<Self, Self::Error> {
+        match event.event {
+            Some(pb::job_event::Event::Snapshot(details)) => Ok(JobEvent::Snapshot {
+                details: details.try_into()?,
+            }),
+            Some(pb::job_event::Event::JobChanged(job)) => Ok(JobEvent::JobChanged {
+                job: job.try_into()?,
+            }),
+            Some(pb::job_event::Event::NodeChanged(node)) => Ok(JobEvent::NodeChanged {
+                node: node.try_into()?,
+            }),
+            Some(pb::job_event::Event::NodesCleared(_)) => Ok(JobEvent::NodesCleared),
+            None => Err(ApiError::bad_request("event is not set")),
+        }
+    }
+}
+
+impl ErrorCode {
+    pub fn grpc_code(&self) -> Code {
+        match self {
+            ErrorCode::BadRequest | ErrorCode::UnsupportedMediaType | ErrorCode::Unprocessable => {
+                Code::InvalidArgument
+            }
+            ErrorCode::Unauthorized => Code::Unauthenticated,
+            ErrorCode::Forbidden => Code::PermissionDenied,
+            ErrorCode::NotFound => Code::NotFound,
+            ErrorCode::Conflict => Code::AlreadyExists,
+            ErrorCode::InvalidTransition => Code::FailedPrecondition,
+            ErrorCode::Internal => Code::Internal,
+        }
+    }
+
+    /// from_grpc_code guess code of statuses without ApiError in details
+    pub fn from_grpc_code(code: Code) -> Self {
+        match code {
+            Code::InvalidArgument => ErrorCode::BadRequest,
+            Code::Unauthenticated => ErrorCode::Unauthorized,
+            Code::PermissionDenied => ErrorCode::Forbidden,
+            Code::NotFound => ErrorCode::NotFound,
+            Code::AlreadyExists => ErrorCode::Conflict,
+            Code::FailedPrecondition => ErrorCode::InvalidTransition,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
+/// ApiError is carried as json in details of status, so grpc clients get the same stable code
+/// as rest clients
+impl From<ApiError> for Status {
+    fn from(err: ApiError) -> Self {
+        let details = serde_json::to_vec(&err).unwrap_or_default();
+        Status::with_details(err.code.grpc_code(), err.message, details.into())
+    }
+}
+
+impl From<Status> for ApiError {
+    fn from(status: Status) -> Self {
+        serde_json::from_slice(status.details()).unwrap_or_else(|_| {
+            ApiError::new(ErrorCode::from_grpc_code(status.code()), status.message())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_job_round_trip() {
+        let job = Job {
+            name: "job-a".to_string(),
+            graph_json: "{}".to_string(),
+            state: JobState::Cancelled,
+            labels: HashMap::from([("team".to_string(), "ml".to_string())]),
+            timeout_action: TimeoutAction::Pause,
+            hooks: vec![
+                Hook::Webhook {
+                    url: "http://hook".to_string(),
+                    secret: None,
+                },
+                Hook::Command {
+                    program: "/notify".to_string(),
+                    args: vec!["-v".to_string()],
+                },
+            ],
+            attempts: vec![JobAttempt {
+                state: JobState::Error,
+                started_at: 1,
+                ended_at: 2,
+            }],
+            schedule_id: Some(ObjectId::new()),
+            summary: Some(RunSummary {
+                nodes: vec![NodeSummary {
+                    node_name: "node-a".to_string(),
+                    avg_batch_latency: Some(0.5),
+                    ..Default::default()
+                }],
+                created_at: 3,
+            }),
+            created_at: 1,
+            updated_at: 2,
+            ..Default::default()
+        };
+        let decoded = Job::try_from(pb::Job::from(job.clone())).unwrap();
+        assert_eq!(decoded, job);
+
+        let details = JobDetails {
+            job: job.clone(),
+            node_status: Some(vec![NodeStatus {
+                name: "node-a".to_string(),
+                state: TrackerState::InComingFinish,
+                data_count: 3,
+                replicas: 1,
+                storage: "1Gi".to_string(),
+                pods: HashMap::from([("pod-0".to_string(), PodStauts::default())]),
+            }]),
+        };
+        for event in [
+            JobEvent::Snapshot {
+                details: details.clone(),
+            },
+            JobEvent::Snapshot {
+                details: JobDetails {
+                    node_status: None,
+                    ..details
+                },
+            },
+            JobEvent::NodesCleared,
+        ] {
+            let decoded = JobEvent::try_from(pb::JobEvent::from(event.clone())).unwrap();
+            assert_eq!(decoded, event);
+        }
+
+        let mut invalid = pb::Job::from(job);
+        invalid.state = 100;
+        let err = Job::try_from(invalid).unwrap_err();
+        assert_eq!(err.code, ErrorCode::BadRequest);
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        let err = ApiError::not_found("job a not found");
+        let status = Status::from(err.clone());
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(ApiError::from(status), err);
+
+        let status = Status::permission_denied("denied by proxy");
+        assert_eq!(
+            ApiError::from(status),
+            ApiError::new(ErrorCode::Forbidden, "denied by proxy")
+        );
+    }
+}
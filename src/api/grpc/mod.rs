@@ -0,0 +1,4 @@
+mod convert;
+mod server;
+
+pub use server::*;
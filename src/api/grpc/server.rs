@@ -0,0 +1,245 @@
+use crate::{
+    api::{
+        auth::{
+            Authenticator,
+            Principal,
+            Role,
+        },
+        error::ApiError,
+        job_api::{
+            authorize_job,
+            check_hooks,
+            check_priority,
+            find_job,
+            page_queue_positions,
+            set_submitter,
+        },
+    },
+    core::db::{
+        GetJobParams,
+        Job,
+        JobDbRepo,
+        ListJobParams,
+        MainDbRepo,
+    },
+    driver::Driver,
+    job::{
+        hooks::WebhookPolicy,
+        job_mgr::{
+            JobManager,
+            DEFAULT_CANCEL_GRACE_PERIOD,
+        },
+        watch::job_events,
+    },
+    network::{
+        common::Empty,
+        jobservice::{
+            self as pb,
+            job_service_server::{
+                JobService,
+                JobServiceServer,
+            },
+        },
+    },
+};
+use anyhow::Result;
+use chrono::Utc;
+use futures::{
+    Stream,
+    StreamExt,
+};
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    time::Duration,
+};
+use tokio_util::sync::CancellationToken;
+use tonic::{
+    transport::Server,
+    Request,
+    Response,
+    Status,
+};
+use tracing::info;
+
+/// JobServiceImpl serve job routes over grpc, it share job manager and auth with rest api
+pub struct JobServiceImpl<D, MAINR, JOBR>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    db_repo: MAINR,
+    job_manager: JobManager<D, MAINR, JOBR>,
+    authenticator: Authenticator,
+    webhook_policy: WebhookPolicy,
+}
+
+impl<D, MAINR, JOBR> JobServiceImpl<D, MAINR, JOBR>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    pub fn new(
+        db_repo: MAINR,
+        job_manager: JobManager<D, MAINR, JOBR>,
+        authenticator: Authenticator,
+        webhook_policy: WebhookPolicy,
+    ) -> Self {
+        JobServiceImpl {
+            db_repo,
+            job_manager,
+            authenticator,
+            webhook_policy,
+        }
+    }
+
+    /// principal authenticate bearer token in authorization metadata
+    fn principal<T>(&self, request: &Request<T>) -> Result<Principal, ApiError> {
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+        self.authenticator
+            .authenticate(authorization)
+            .map_err(|err| ApiError::unauthorized(err.to_string()))
+    }
+}
+
+#[tonic::async_trait]
+impl<D, MAINR, JOBR> JobService for JobServiceImpl<D, MAINR, JOBR>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    async fn create(&self, request: Request<pb::Job>) -> Result<Response<pb::Job>, Status> {
+        let principal = self.principal(&request)?;
+        principal.require(Role::Submitter)?;
+        let mut job = Job::try_from(request.into_inner())?.submitted(Utc::now().timestamp());
+        check_hooks(&job, &principal, &self.webhook_policy)?;
+        check_priority(job.priority, &principal)?;
+        set_submitter(&mut job, &principal);
+        let job = self.db_repo.insert(&job).await.map_err(ApiError::from)?;
+        Ok(Response::new(job.redacted().into()))
+    }
+
+    async fn get(&self, request: Request<pb::JobRef>) -> Result<Response<pb::Job>, Status> {
+        self.principal(&request)?.require(Role::Viewer)?;
+        let job = find_job(&self.db_repo, &request.get_ref().id).await?;
+        Ok(Response::new(job.redacted().into()))
+    }
+
+    async fn list(
+        &self,
+        request: Request<pb::ListJobsRequest>,
+    ) -> Result<Response<pb::ListJobsResponse>, Status> {
+        self.principal(&request)?.require(Role::Viewer)?;
+        let params = ListJobParams::try_from(request.into_inner())?;
+        let jobs = self
+            .db_repo
+            .list_jobs(&params)
+            .await
+            .map_err(ApiError::from)?;
+        let next_cursor = params.next_cursor(&jobs).map_err(ApiError::from)?;
+        let queue_positions = page_queue_positions(&self.db_repo, &jobs)
+            .await?
+            .into_iter()
+            .map(|(id, position)| (id, position as u64))
+            .collect();
+        Ok(Response::new(pb::ListJobsResponse {
+            jobs: jobs.into_iter().map(|job| job.redacted().into()).collect(),
+            next_cursor,
+            queue_positions,
+        }))
+    }
+
+    async fn run(&self, request: Request<pb::JobRef>) -> Result<Response<Empty>, Status> {
+        let principal = self.principal(&request)?;
+        let job = authorize_job(&self.db_repo, &principal, &request.get_ref().id).await?;
+        self.job_manager
+            .start_job(&GetJobParams::new().set_id(job.id))
+            .await
+            .map_err(ApiError::from)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn cancel(
+        &self,
+        request: Request<pb::CancelJobRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        let principal = self.principal(&request)?;
+        let job = authorize_job(&self.db_repo, &principal, &request.get_ref().id).await?;
+        let grace_period = request
+            .get_ref()
+            .grace_period
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_CANCEL_GRACE_PERIOD);
+        self.job_manager
+            .cancel_job(&GetJobParams::new().set_id(job.id), grace_period)
+            .await
+            .map_err(ApiError::from)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn clean(&self, request: Request<pb::JobRef>) -> Result<Response<Empty>, Status> {
+        let principal = self.principal(&request)?;
+        let job = authorize_job(&self.db_repo, &principal, &request.get_ref().id).await?;
+        self.job_manager
+            .clean_job(&GetJobParams::new().set_id(job.id))
+            .await
+            .map_err(ApiError::from)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn details(
+        &self,
+        request: Request<pb::JobRef>,
+    ) -> Result<Response<pb::JobDetails>, Status> {
+        self.principal(&request)?.require(Role::Viewer)?;
+        let job = find_job(&self.db_repo, &request.get_ref().id).await?;
+        let details = self
+            .job_manager
+            .get_job_details(&GetJobParams::new().set_id(job.id))
+            .await
+            .map_err(ApiError::from)?;
+        Ok(Response::new(details.redacted().into()))
+    }
+
+    type WatchStream = Pin<Box<dyn Stream<Item = Result<pb::JobEvent, Status>> + Send>>;
+
+    async fn watch(
+        &self,
+        request: Request<pb::JobRef>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        self.principal(&request)?.require(Role::Viewer)?;
+        let job = find_job(&self.db_repo, &request.get_ref().id).await?;
+        let events = job_events(self.job_manager.watch_job(job.id))
+            .map(|event| Ok(pb::JobEvent::from(event.redacted())));
+        Ok(Response::new(Box::pin(events)))
+    }
+}
+
+/// start_grpc_server serve JobService on addr until token is cancelled
+pub async fn start_grpc_server<D, MAINR, JOBR>(
+    addr: SocketAddr,
+    db_repo: MAINR,
+    job_manager: JobManager<D, MAINR, JOBR>,
+    authenticator: Authenticator,
+    webhook_policy: WebhookPolicy,
+    token: CancellationToken,
+) -> Result<()>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    info!("grpc job service listening on {addr}");
+    let service = JobServiceImpl::new(db_repo, job_manager, authenticator, webhook_policy);
+    Server::builder()
+        .add_service(JobServiceServer::new(service))
+        .serve_with_shutdown(addr, token.cancelled())
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use crate::{
+    core::db::MainDbRepo,
+    utils::health::{
+        HealthReport,
+        TaskHealth,
+    },
+};
+use actix_web::{
+    web,
+    HttpResponse,
+};
+use anyhow::Result;
+use std::future::Future;
+
+pub const BACKEND_TASK: &str = "job_backend";
+pub const GRPC_SERVER_TASK: &str = "grpc_server";
+
+/// daemon_health check mongo and the tasks serving jobs besides the rest api
+async fn daemon_health(ping: impl Future<Output = Result<()>>, tasks: &TaskHealth) -> HealthReport {
+    HealthReport::new()
+        .add_async_check("mongo", ping)
+        .await
+        .add_check(BACKEND_TASK, tasks.check(BACKEND_TASK))
+        .add_check(GRPC_SERVER_TASK, tasks.check(GRPC_SERVER_TASK))
+}
+
+/// probe serve both /healthz and /readyz, daemon is useless without mongo, job backend or grpc
+/// server so that it should neither receive requests nor keep running when any of them failed
+pub(super) async fn probe<MAINR>(
+    db_repo: web::Data<MAINR>,
+    tasks: web::Data<TaskHealth>,
+) -> HttpResponse
+where
+    MAINR: MainDbRepo,
+{
+    daemon_health(db_repo.ping(), &tasks).await.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_daemon_health() {
+        let tasks = TaskHealth::default();
+        let report = daemon_health(async { Ok(()) }, &tasks).await;
+        assert!(!report.healthy);
+        assert_eq!(report.checks[BACKEND_TASK], "job_backend not started");
+
+        tasks.set_running(BACKEND_TASK, true);
+        tasks.set_running(GRPC_SERVER_TASK, true);
+        let report = daemon_health(async { Ok(()) }, &tasks).await;
+        assert!(report.healthy);
+
+        tasks.set_running(GRPC_SERVER_TASK, false);
+        let report = daemon_health(async { Ok(()) }, &tasks).await;
+        assert!(!report.healthy);
+        assert_eq!(report.checks["mongo"], "ok");
+        assert_eq!(report.checks[GRPC_SERVER_TASK], "grpc_server exited");
+    }
+}
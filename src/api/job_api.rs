@@ -1,136 +1,423 @@
-use std::str::FromStr;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    time::Duration,
+};
 
+use super::{
+    auth::{
+        Principal,
+        Role,
+    },
+    error::{
+        ApiError,
+        ErrorCode,
+    },
+    openapi::api_resource,
+};
 use crate::{
     core::db::{
+        CancelJobParams,
         GetJobParams,
+        Hook,
+        HookDelivery,
         Job,
         JobDbRepo,
+        JobList,
+        JobState,
         JobUpdateInfo,
         ListJobParams,
         MainDbRepo,
     },
     driver::Driver,
-    job::job_mgr::JobManager,
+    job::{
+        hooks::WebhookPolicy,
+        job_mgr::{
+            JobManager,
+            DEFAULT_CANCEL_GRACE_PERIOD,
+        },
+        queue::queue_positions,
+        watch::{
+            job_events,
+            JobEvent,
+        },
+    },
 };
 use actix_web::{
     web,
+    web::Bytes,
     HttpResponse,
 };
+use chrono::Utc;
+use futures::{
+    stream,
+    StreamExt,
+};
 use mongodb::bson::oid::ObjectId;
+use tokio::time::timeout;
 
-//TODO change to use route macro after https://github.com/actix/actix-web/issues/2866  resolved
-async fn create<MAINR>(db_repo: web::Data<MAINR>, data: web::Json<Job>) -> HttpResponse
+/// find_job get job by id or name, 404 if job not exist
+pub(super) async fn find_job<MAINR>(db_repo: &MAINR, name_or_id: &str) -> Result<Job, ApiError>
 where
     MAINR: MainDbRepo,
 {
-    match db_repo.insert(&data.0).await {
-        Ok(inserted_result) => HttpResponse::Ok().json(&inserted_result),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
-    }
+    let params = match ObjectId::from_str(name_or_id) {
+        Ok(id) => GetJobParams::new().set_id(id),
+        Err(_) => GetJobParams::new().set_name(name_or_id.to_string()),
+    };
+    db_repo
+        .get(&params)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("job {name_or_id} not found")))
 }
 
-async fn get_by_id<MAINR>(db_repo: web::Data<MAINR>, path: web::Path<ObjectId>) -> HttpResponse
+/// authorize_job find job principal can manage
+pub(super) async fn authorize_job<MAINR>(
+    db_repo: &MAINR,
+    principal: &Principal,
+    name_or_id: &str,
+) -> Result<Job, ApiError>
 where
     MAINR: MainDbRepo,
 {
-    match db_repo
-        .get(&GetJobParams::new().set_id(path.into_inner()))
-        .await
-    {
-        Ok(Some(inserted_result)) => HttpResponse::Ok().json(&inserted_result),
-        Ok(None) => HttpResponse::NotFound().finish(),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+    let job = find_job(db_repo, name_or_id).await?;
+    principal.require_owner(job.owner.as_deref())?;
+    Ok(job)
+}
+
+/// check_hooks reject command hooks of job, they would run programs on daemon host, and
+/// webhooks not allowed by policy. admins may post to any host
+pub(super) fn check_hooks(
+    job: &Job,
+    principal: &Principal,
+    policy: &WebhookPolicy,
+) -> Result<(), ApiError> {
+    for hook in job.hooks.iter() {
+        let Hook::Webhook { url, .. } = hook else {
+            return Err(ApiError::new(
+                ErrorCode::Unprocessable,
+                "job hooks must be webhooks, command hooks can only be set on daemon",
+            ));
+        };
+        policy
+            .check(url, principal.role == Role::Admin)
+            .map_err(|err| ApiError::new(ErrorCode::Unprocessable, err.to_string()))?;
     }
+    Ok(())
 }
 
-async fn get<MAINR>(db_repo: web::Data<MAINR>, query: web::Query<GetJobParams>) -> HttpResponse
+/// check_priority only allow admin to raise priority above default, otherwise every submitter
+/// could jump the queue
+pub(super) fn check_priority(priority: i32, principal: &Principal) -> Result<(), ApiError> {
+    if priority > 0 && principal.role < Role::Admin {
+        return Err(ApiError::forbidden(format!(
+            "{} need Admin role to set priority above 0",
+            principal.name
+        )));
+    }
+    Ok(())
+}
+
+/// check_raw_update reject states which need resources released by job manager, a raw update
+/// would leak namespace and job database of a live job
+fn check_raw_update(info: &JobUpdateInfo) -> Result<(), ApiError> {
+    match info.state {
+        Some(JobState::Clean) => Err(ApiError::new(
+            ErrorCode::Unprocessable,
+            "clean job with DELETE /job/{id} instead",
+        )),
+        Some(JobState::Cancelling) => Err(ApiError::new(
+            ErrorCode::Unprocessable,
+            "cancel job with POST /job/cancel/{id} instead",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// set_submitter record principal as owner of job, so per user limits cant be bypassed by
+/// submitting as others
+pub(super) fn set_submitter(job: &mut Job, principal: &Principal) {
+    job.owner = Some(principal.name.clone());
+}
+
+/// page_queue_positions return positions of queued jobs in page, counted over all queued jobs
+/// as the page may be filtered
+pub(super) async fn page_queue_positions<MAINR>(
+    db_repo: &MAINR,
+    jobs: &[Job],
+) -> Result<HashMap<String, usize>, ApiError>
 where
     MAINR: MainDbRepo,
 {
-    match db_repo.get(&query.into_inner()).await {
-        Ok(Some(inserted_result)) => HttpResponse::Ok().json(&inserted_result),
-        Ok(None) => HttpResponse::NotFound().finish(),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+    if !jobs.iter().any(|job| job.state == JobState::Queued) {
+        return Ok(HashMap::new());
     }
+    let queued_jobs_params = ListJobParams {
+        states: vec![JobState::Queued],
+        ..Default::default()
+    };
+    let positions = queue_positions(&db_repo.list_jobs(&queued_jobs_params).await?);
+    Ok(jobs
+        .iter()
+        .filter_map(|job| {
+            positions
+                .get(&job.id)
+                .map(|position| (job.id.to_hex(), *position))
+        })
+        .collect())
 }
 
-async fn list<MAINR>(db_repo: web::Data<MAINR>) -> HttpResponse
+//TODO change to use route macro after https://github.com/actix/actix-web/issues/2866  resolved
+async fn create<MAINR>(
+    db_repo: web::Data<MAINR>,
+    webhook_policy: web::Data<WebhookPolicy>,
+    principal: Principal,
+    data: web::Json<Job>,
+) -> Result<HttpResponse, ApiError>
 where
     MAINR: MainDbRepo,
 {
-    let list_job_params = &ListJobParams { state: None };
-    match db_repo.list_jobs(list_job_params).await {
-        Ok(jobs) => HttpResponse::Ok().json(&jobs),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
-    }
+    principal.require(Role::Submitter)?;
+    let mut job = data.into_inner().submitted(Utc::now().timestamp());
+    check_hooks(&job, &principal, &webhook_policy)?;
+    check_priority(job.priority, &principal)?;
+    set_submitter(&mut job, &principal);
+    let inserted_result = db_repo.insert(&job).await?;
+    Ok(HttpResponse::Ok().json(inserted_result.redacted()))
+}
+
+async fn get_by_id<MAINR>(
+    db_repo: web::Data<MAINR>,
+    principal: Principal,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let job = find_job(db_repo.as_ref(), &path).await?;
+    Ok(HttpResponse::Ok().json(job.redacted()))
+}
+
+async fn get<MAINR>(
+    db_repo: web::Data<MAINR>,
+    principal: Principal,
+    query: web::Query<GetJobParams>,
+) -> Result<HttpResponse, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let job = db_repo
+        .get(&query.into_inner())
+        .await?
+        .ok_or_else(|| ApiError::not_found("job not found"))?;
+    Ok(HttpResponse::Ok().json(job.redacted()))
+}
+
+async fn list<MAINR>(
+    db_repo: web::Data<MAINR>,
+    principal: Principal,
+    query: web::Query<ListJobParams>,
+) -> Result<HttpResponse, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let list_job_params = query.into_inner();
+    let jobs = db_repo.list_jobs(&list_job_params).await?;
+    let next_cursor = list_job_params.next_cursor(&jobs)?;
+    let queue_positions = page_queue_positions(db_repo.as_ref(), &jobs).await?;
+    let jobs = jobs.into_iter().map(Job::redacted).collect();
+    Ok(HttpResponse::Ok().json(&JobList {
+        jobs,
+        next_cursor,
+        queue_positions,
+    }))
 }
 
 async fn clean_job<D, MAINR, JOBR>(
+    db_repo: web::Data<MAINR>,
     job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
     path: web::Path<String>,
-) -> HttpResponse
+) -> Result<HttpResponse, ApiError>
 where
     D: Driver,
     MAINR: MainDbRepo,
     JOBR: JobDbRepo,
 {
-    let id = ObjectId::from_str(&path.into_inner()).unwrap();
-    match job_manager.clean_job(&GetJobParams::new().set_id(id)).await {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
-    }
+    let job = authorize_job(db_repo.as_ref(), &principal, &path).await?;
+    let params = GetJobParams::new().set_id(job.id);
+    job_manager.clean_job(&params).await?;
+    Ok(HttpResponse::Ok().finish())
 }
 
 async fn update<MAINR>(
     db_repo: web::Data<MAINR>,
-    path: web::Path<ObjectId>,
+    principal: Principal,
+    path: web::Path<String>,
     query: web::Query<JobUpdateInfo>,
-) -> HttpResponse
+) -> Result<HttpResponse, ApiError>
 where
     MAINR: MainDbRepo,
 {
-    match db_repo
-        .update(&path.into_inner(), &query.into_inner())
-        .await
-    {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
-    }
+    //raw state change bypass the checks done by job manager
+    principal.require(Role::Admin)?;
+    let info = query.into_inner();
+    check_raw_update(&info)?;
+    let job = find_job(db_repo.as_ref(), &path).await?;
+    db_repo.update(&job.id, &info).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn list_deliveries<MAINR>(
+    db_repo: web::Data<MAINR>,
+    principal: Principal,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let job = find_job(db_repo.as_ref(), &path).await?;
+    let deliveries: Vec<_> = db_repo
+        .list_deliveries(&job.id)
+        .await?
+        .into_iter()
+        .map(HookDelivery::redacted)
+        .collect();
+    Ok(HttpResponse::Ok().json(&deliveries))
+}
+
+async fn plan_job<D, MAINR, JOBR>(
+    job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
+    data: web::Json<Job>,
+) -> Result<HttpResponse, ApiError>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    principal.require(Role::Submitter)?;
+    let plan = job_manager
+        .plan_job(&data.0)
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(HttpResponse::Ok().json(plan))
 }
 
 async fn job_details<D, MAINR, JOBR>(
+    db_repo: web::Data<MAINR>,
     job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
     path: web::Path<String>,
-) -> HttpResponse
+) -> Result<HttpResponse, ApiError>
 where
     D: Driver,
     MAINR: MainDbRepo,
     JOBR: JobDbRepo,
 {
-    let id = ObjectId::from_str(&path.into_inner()).unwrap();
-    match job_manager
-        .get_job_details(&GetJobParams::new().set_id(id))
-        .await
-    {
-        Ok(detail) => HttpResponse::Ok().json(detail),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
-    }
+    principal.require(Role::Viewer)?;
+    let job = find_job(db_repo.as_ref(), &path).await?;
+    let detail = job_manager
+        .get_job_details(&GetJobParams::new().set_id(job.id))
+        .await?;
+    Ok(HttpResponse::Ok().json(detail.redacted()))
 }
 
 async fn run_job<D, MAINR, JOBR>(
+    db_repo: web::Data<MAINR>,
     job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
     path: web::Path<String>,
-) -> HttpResponse
+) -> Result<HttpResponse, ApiError>
 where
     D: Driver,
     MAINR: MainDbRepo,
     JOBR: JobDbRepo,
 {
-    let id = ObjectId::from_str(&path.into_inner()).unwrap();
-    match job_manager.start_job(&GetJobParams::new().set_id(id)).await {
-        Ok(detail) => HttpResponse::Ok().json(detail),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
-    }
+    let job = authorize_job(db_repo.as_ref(), &principal, &path).await?;
+    let params = GetJobParams::new().set_id(job.id);
+    job_manager.start_job(&params).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn retry_job<D, MAINR, JOBR>(
+    db_repo: web::Data<MAINR>,
+    job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    let job = authorize_job(db_repo.as_ref(), &principal, &path).await?;
+    let params = GetJobParams::new().set_id(job.id);
+    job_manager.retry_job(&params).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn cancel_job<D, MAINR, JOBR>(
+    db_repo: web::Data<MAINR>,
+    job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
+    path: web::Path<String>,
+    query: web::Query<CancelJobParams>,
+) -> Result<HttpResponse, ApiError>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    let job = authorize_job(db_repo.as_ref(), &principal, &path).await?;
+    let params = GetJobParams::new().set_id(job.id);
+    let grace_period = query
+        .grace_period
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CANCEL_GRACE_PERIOD);
+    job_manager.cancel_job(&params, grace_period).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// interval of keep alive comments sent to idle watchers
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// watch_job stream changes of job details as server-sent events, first event is a snapshot
+async fn watch_job<D, MAINR, JOBR>(
+    db_repo: web::Data<MAINR>,
+    job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let job = find_job(db_repo.as_ref(), &path).await?;
+
+    let events = Box::pin(job_events(job_manager.watch_job(job.id)).map(JobEvent::redacted));
+    let frames = stream::unfold(events, |mut events| async move {
+        //unfold keep the pending event when timeout drop next, so no change is lost
+        let frame = match timeout(KEEP_ALIVE_INTERVAL, events.next()).await {
+            Ok(Some(event)) => {
+                serde_json::to_string(&event).map(|data| Bytes::from(format!("data: {data}\n\n")))
+            }
+            //job deleted
+            Ok(None) => return None,
+            Err(_) => Ok(Bytes::from(": keep-alive\n\n")),
+        };
+        Some((frame, events))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(frames))
 }
 
 pub(super) fn job_route_config<D, MAINR, JOBR>(cfg: &mut web::ServiceConfig)
@@ -140,17 +427,203 @@ where
     JOBR: JobDbRepo,
 {
     cfg.service(
-        web::resource("/job")
+        api_resource("/job")
             .route(web::post().to(create::<MAINR>))
             .route(web::get().to(get::<MAINR>)),
     )
+    //registered before /job/{id} which would match it too
+    .service(api_resource("/job/plan").route(web::post().to(plan_job::<D, MAINR, JOBR>)))
     .service(
-        web::resource("/job/{id}")
+        api_resource("/job/{id}")
             .route(web::get().to(get_by_id::<MAINR>))
             .route(web::post().to(update::<MAINR>))
             .route(web::delete().to(clean_job::<D, MAINR, JOBR>)),
     )
-    .service(web::resource("/jobs").route(web::get().to(list::<MAINR>)))
-    .service(web::resource("/job/detail/{id}").route(web::get().to(job_details::<D, MAINR, JOBR>)))
-    .service(web::resource("/job/run/{id}").route(web::post().to(run_job::<D, MAINR, JOBR>)));
+    .service(api_resource("/jobs").route(web::get().to(list::<MAINR>)))
+    .service(api_resource("/job/detail/{id}").route(web::get().to(job_details::<D, MAINR, JOBR>)))
+    .service(api_resource("/job/watch/{id}").route(web::get().to(watch_job::<D, MAINR, JOBR>)))
+    .service(api_resource("/job/deliveries/{id}").route(web::get().to(list_deliveries::<MAINR>)))
+    .service(api_resource("/job/run/{id}").route(web::post().to(run_job::<D, MAINR, JOBR>)))
+    .service(api_resource("/job/retry/{id}").route(web::post().to(retry_job::<D, MAINR, JOBR>)))
+    .service(api_resource("/job/cancel/{id}").route(web::post().to(cancel_job::<D, MAINR, JOBR>)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dbrepo::{
+            MemMainDbRepo,
+            MongoMainDbRepo,
+            MongoRunDbRepo,
+        },
+        driver::kube::KubeDriver,
+    };
+    use actix_web::{
+        http::StatusCode,
+        test::{
+            call_service,
+            init_service,
+            TestRequest,
+        },
+        App,
+        HttpMessage,
+    };
+
+    #[actix_web::test]
+    async fn test_create_route() {
+        let db_repo = MemMainDbRepo::default();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(db_repo.clone()))
+                .app_data(web::Data::new(WebhookPolicy::default()))
+                .service(api_resource("/job").route(web::post().to(create::<MemMainDbRepo>))),
+        )
+        .await;
+        //state and fields owned by server are not taken from client
+        let posted = Job {
+            name: "a".to_string(),
+            state: JobState::Finish,
+            holder: Some("daemon-0".to_string()),
+            reason: Some("done".to_string()),
+            completed_batches: Some(3),
+            ..Default::default()
+        };
+        let req = TestRequest::post()
+            .uri("/job")
+            .set_json(&posted)
+            .to_request();
+        req.extensions_mut().insert(Principal::anonymous());
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let jobs = db_repo.jobs();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].state, JobState::Created);
+        assert_ne!(jobs[0].id, posted.id);
+        assert!(jobs[0].created_at > 0);
+        assert_eq!(jobs[0].holder, None);
+        assert_eq!(jobs[0].reason, None);
+        assert_eq!(jobs[0].completed_batches, None);
+        assert_eq!(jobs[0].owner.as_deref(), Some("anonymous"));
+    }
+
+    #[actix_web::test]
+    async fn test_plan_route() {
+        let app = init_service(App::new().configure(
+            job_route_config::<KubeDriver<MongoRunDbRepo>, MongoMainDbRepo, MongoRunDbRepo>,
+        ))
+        .await;
+        //plan must not be taken as update of job named plan
+        let req = TestRequest::post()
+            .uri("/job/plan")
+            .set_json(Job::default())
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.request().match_pattern().as_deref(), Some("/job/plan"));
+
+        let req = TestRequest::post().uri("/job/a").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.request().match_pattern().as_deref(), Some("/job/{id}"));
+    }
+
+    #[actix_web::test]
+    async fn test_update_route() {
+        let db_repo = MemMainDbRepo::default();
+        db_repo
+            .insert(&Job {
+                name: "a".to_string(),
+                state: JobState::Running,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(db_repo.clone()))
+                .service(api_resource("/job/{id}").route(web::post().to(update::<MemMainDbRepo>))),
+        )
+        .await;
+        for state in ["Clean", "Cancelling"] {
+            let req = TestRequest::post()
+                .uri(&format!("/job/a?state={state}"))
+                .to_request();
+            req.extensions_mut().insert(Principal::anonymous());
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        assert_eq!(db_repo.jobs()[0].state, JobState::Running);
+
+        let req = TestRequest::post()
+            .uri("/job/a?state=Paused&reason=check")
+            .to_request();
+        req.extensions_mut().insert(Principal::anonymous());
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        assert_eq!(db_repo.jobs()[0].state, JobState::Paused);
+    }
+
+    #[test]
+    fn test_set_submitter() {
+        let mut job = Job {
+            owner: Some("bob".to_string()),
+            ..Default::default()
+        };
+        set_submitter(&mut job, &Principal::anonymous());
+        assert_eq!(job.owner.as_deref(), Some("anonymous"));
+
+        let alice = Principal {
+            name: "alice".to_string(),
+            role: Role::Submitter,
+        };
+        set_submitter(&mut job, &alice);
+        assert_eq!(job.owner.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn test_check_priority() {
+        let bob = Principal {
+            name: "bob".to_string(),
+            role: Role::Submitter,
+        };
+        assert!(check_priority(0, &bob).is_ok());
+        assert!(check_priority(-5, &bob).is_ok());
+        assert_eq!(
+            check_priority(10, &bob).unwrap_err().code,
+            ErrorCode::Forbidden
+        );
+        assert!(check_priority(10, &Principal::anonymous()).is_ok());
+    }
+
+    #[test]
+    fn test_check_hooks() {
+        let policy = WebhookPolicy::default().add_allowed_host("hook");
+        let bob = Principal {
+            name: "bob".to_string(),
+            role: Role::Submitter,
+        };
+        let mut job = Job {
+            hooks: vec![Hook::Webhook {
+                url: "http://hook".to_string(),
+                secret: None,
+            }],
+            ..Default::default()
+        };
+        assert!(check_hooks(&job, &bob, &policy).is_ok());
+
+        job.hooks.push(Hook::Webhook {
+            url: "http://169.254.169.254/latest".to_string(),
+            secret: None,
+        });
+        let err = check_hooks(&job, &bob, &policy).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Unprocessable);
+        assert!(check_hooks(&job, &Principal::anonymous(), &policy).is_ok());
+
+        job.hooks.push(Hook::Command {
+            program: "/bin/sh".to_string(),
+            args: vec![],
+        });
+        let err = check_hooks(&job, &Principal::anonymous(), &policy).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Unprocessable);
+    }
 }
@@ -0,0 +1,87 @@
+use crate::{
+    job::metrics::DaemonMetrics,
+    utils::metrics::CONTENT_TYPE,
+};
+use actix_web::{
+    body::MessageBody,
+    dev::{
+        ServiceRequest,
+        ServiceResponse,
+    },
+    middleware::Next,
+    web,
+    HttpResponse,
+};
+use std::time::Instant;
+
+/// track_request is a middleware recording latency of requests by matched route
+pub(super) async fn track_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let metrics = req.app_data::<web::Data<DaemonMetrics>>().cloned();
+    let start = Instant::now();
+    let resp = next.call(req).await?;
+
+    if let Some(metrics) = metrics {
+        let request = resp.request();
+        //unmatched paths are not used as label to keep the number of series bounded
+        let route = request
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        metrics.request_duration.observe(
+            &[request.method().as_str(), &route, resp.status().as_str()],
+            start.elapsed().as_secs_f64(),
+        );
+    }
+    Ok(resp)
+}
+
+pub(super) async fn get_metrics(metrics: web::Data<DaemonMetrics>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(CONTENT_TYPE)
+        .body(metrics.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        middleware,
+        test,
+        App,
+    };
+
+    #[actix_web::test]
+    async fn test_track_request() {
+        let metrics = DaemonMetrics::new();
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware::from_fn(track_request))
+                .app_data(web::Data::new(metrics.clone()))
+                .route("/metrics", web::get().to(get_metrics))
+                .route("/job/{id}", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for uri in ["/job/a", "/job/b", "/other"] {
+            test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        }
+        let request_duration = &metrics.request_duration;
+        assert_eq!(
+            request_duration.get(&["GET", "/job/{id}", "200"]),
+            Some(2.0)
+        );
+        assert_eq!(
+            request_duration.get(&["GET", "unmatched", "404"]),
+            Some(1.0)
+        );
+
+        let resp =
+            test::call_service(&app, test::TestRequest::get().uri("/metrics").to_request()).await;
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert!(body.contains(
+            r#"jzflow_http_request_duration_seconds_count{method="GET",route="/job/{id}",status="200"} 2"#
+        ));
+    }
+}
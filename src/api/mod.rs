@@ -1,4 +1,13 @@
+pub mod auth;
 pub mod client;
+pub mod error;
+pub mod grpc;
+pub mod health;
+pub mod openapi;
 pub mod server;
 
+mod data_api;
 mod job_api;
+mod metrics;
+mod schedule_api;
+mod trigger_api;
@@ -0,0 +1,510 @@
+use super::error::ApiError;
+use crate::{
+    core::db::{
+        CancelJobParams,
+        DataList,
+        DataRecord,
+        GetJobParams,
+        GetScheduleParams,
+        GetTriggerParams,
+        HookDelivery,
+        Job,
+        JobList,
+        JobUpdateInfo,
+        ListDataParams,
+        ListJobParams,
+        NodeDataSummary,
+        Schedule,
+        Trigger,
+    },
+    driver::Plan,
+    job::{
+        job_mgr::JobDetails,
+        watch::JobEvent,
+    },
+};
+use actix_web::{
+    web,
+    HttpResponse,
+    Resource,
+};
+use schemars::{
+    gen::{
+        SchemaGenerator,
+        SchemaSettings,
+    },
+    schema::{
+        Schema,
+        SchemaObject,
+    },
+    JsonSchema,
+};
+use serde_json::{
+    json,
+    Map,
+    Value,
+};
+
+/// Content is the payload of a request or a successful response
+enum Content {
+    /// no body
+    Empty,
+    Json(Schema),
+    /// server-sent events, each data frame is a json of schema
+    EventStream(Schema),
+}
+
+/// Operation describe a route registered in api v1, path is relative to /api/v1
+struct Operation {
+    method: &'static str,
+    path: &'static str,
+    operation_id: &'static str,
+    summary: &'static str,
+    tag: &'static str,
+    /// struct deserialized from query string
+    query: Option<Schema>,
+    body: Option<Schema>,
+    response: Content,
+    /// whether a bearer token is needed when auth is enabled
+    secured: bool,
+}
+
+impl Operation {
+    fn new(
+        method: &'static str,
+        path: &'static str,
+        operation_id: &'static str,
+        summary: &'static str,
+    ) -> Self {
+        Operation {
+            method,
+            path,
+            operation_id,
+            summary,
+            tag: path
+                .trim_start_matches('/')
+                .split('/')
+                .next()
+                .unwrap_or_default(),
+            query: None,
+            body: None,
+            response: Content::Empty,
+            secured: true,
+        }
+    }
+
+    fn set_query<T: JsonSchema>(mut self, gen: &mut SchemaGenerator) -> Self {
+        self.query = Some(T::json_schema(gen));
+        self
+    }
+
+    fn set_body<T: JsonSchema>(mut self, gen: &mut SchemaGenerator) -> Self {
+        self.body = Some(gen.subschema_for::<T>());
+        self
+    }
+
+    fn set_response<T: JsonSchema>(mut self, gen: &mut SchemaGenerator) -> Self {
+        self.response = Content::Json(gen.subschema_for::<T>());
+        self
+    }
+
+    fn set_events<T: JsonSchema>(mut self, gen: &mut SchemaGenerator) -> Self {
+        self.response = Content::EventStream(gen.subschema_for::<T>());
+        self
+    }
+
+    fn set_public(mut self) -> Self {
+        self.secured = false;
+        self
+    }
+
+    fn parameters(&self) -> Vec<Value> {
+        let mut parameters = vec![];
+        for (name, description) in [
+            ("id", "id or name"),
+            (
+                "node",
+                "node name, for channel its node name with suffix -channel",
+            ),
+            ("data_id", "id of data batch"),
+        ] {
+            if self.path.contains(&format!("{{{name}}}")) {
+                parameters.push(json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "description": description,
+                    "schema": {"type": "string"},
+                }));
+            }
+        }
+
+        let Some(Schema::Object(SchemaObject {
+            object: Some(object),
+            ..
+        })) = self.query.as_ref()
+        else {
+            return parameters;
+        };
+        for (name, schema) in object.properties.iter() {
+            let mut parameter = json!({
+                "name": name,
+                "in": "query",
+                "required": object.required.contains(name),
+                "schema": schema,
+            });
+            //lists in query string are comma separated
+            if let Schema::Object(SchemaObject { array: Some(_), .. }) = schema {
+                parameter["explode"] = json!(false);
+            }
+            if let Some(description) = schema_description(schema) {
+                parameter["description"] = json!(description);
+            }
+            parameters.push(parameter);
+        }
+        parameters
+    }
+
+    fn to_value(&self, error: &Schema) -> Value {
+        let ok = match &self.response {
+            Content::Empty => json!({"description": "ok"}),
+            Content::Json(schema) => json!({
+                "description": "ok",
+                "content": {"application/json": {"schema": schema}},
+            }),
+            Content::EventStream(schema) => json!({
+                "description": "stream of server-sent events, data of each event is json",
+                "content": {"text/event-stream": {"schema": schema}},
+            }),
+        };
+
+        let mut operation = json!({
+            "operationId": self.operation_id,
+            "summary": self.summary,
+            "tags": [self.tag],
+            "parameters": self.parameters(),
+            "responses": {
+                "200": ok,
+                "default": {
+                    "description": "error",
+                    "content": {"application/json": {"schema": error}},
+                },
+            },
+        });
+        if let Some(body) = self.body.as_ref() {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": {"application/json": {"schema": body}},
+            });
+        }
+        if !self.secured {
+            operation["security"] = json!([]);
+        }
+        operation
+    }
+}
+
+fn schema_description(schema: &Schema) -> Option<&str> {
+    match schema {
+        Schema::Object(SchemaObject {
+            metadata: Some(metadata),
+            ..
+        }) => metadata.description.as_deref(),
+        _ => None,
+    }
+}
+
+/// operations list every route of api v1, keep it in sync with the route configs
+fn operations(gen: &mut SchemaGenerator) -> Vec<Operation> {
+    vec![
+        Operation::new("post", "/job", "createJob", "create a job owned by caller")
+            .set_body::<Job>(gen)
+            .set_response::<Job>(gen),
+        Operation::new("get", "/job", "getJob", "get a job by name or id")
+            .set_query::<GetJobParams>(gen)
+            .set_response::<Job>(gen),
+        Operation::new(
+            "post",
+            "/job/plan",
+            "planJob",
+            "render what deploy would create",
+        )
+        .set_body::<Job>(gen)
+        .set_response::<Plan>(gen),
+        Operation::new("get", "/job/{id}", "getJobById", "get a job").set_response::<Job>(gen),
+        Operation::new(
+            "post",
+            "/job/{id}",
+            "updateJob",
+            "change job fields, admin only, clean and cancel have their own routes",
+        )
+        .set_query::<JobUpdateInfo>(gen),
+        Operation::new(
+            "delete",
+            "/job/{id}",
+            "cleanJob",
+            "remove resources of a job",
+        ),
+        Operation::new("get", "/jobs", "listJobs", "list a page of jobs")
+            .set_query::<ListJobParams>(gen)
+            .set_response::<JobList>(gen),
+        Operation::new(
+            "get",
+            "/job/detail/{id}",
+            "getJobDetails",
+            "get job with node status",
+        )
+        .set_response::<JobDetails>(gen),
+        Operation::new(
+            "get",
+            "/job/watch/{id}",
+            "watchJob",
+            "stream changes of job details",
+        )
+        .set_events::<JobEvent>(gen),
+        Operation::new(
+            "get",
+            "/job/deliveries/{id}",
+            "listJobDeliveries",
+            "list hook deliveries",
+        )
+        .set_response::<Vec<HookDelivery>>(gen),
+        Operation::new(
+            "get",
+            "/job/data/{id}",
+            "getJobDataSummary",
+            "count data of every node by state",
+        )
+        .set_response::<Vec<NodeDataSummary>>(gen),
+        Operation::new(
+            "get",
+            "/job/data/{id}/{node}",
+            "listJobData",
+            "list a page of data of a node",
+        )
+        .set_query::<ListDataParams>(gen)
+        .set_response::<DataList>(gen),
+        Operation::new(
+            "get",
+            "/job/batch/{id}/{data_id}",
+            "getBatchHistory",
+            "list records of a data batch in all nodes",
+        )
+        .set_response::<Vec<DataRecord>>(gen),
+        Operation::new(
+            "post",
+            "/job/run/{id}",
+            "runJob",
+            "start a deployed job or resume a paused job",
+        ),
+        Operation::new("post", "/job/retry/{id}", "retryJob", "retry a failed job"),
+        Operation::new(
+            "post",
+            "/job/cancel/{id}",
+            "cancelJob",
+            "cancel a running job",
+        )
+        .set_query::<CancelJobParams>(gen),
+        Operation::new("post", "/schedule", "createSchedule", "create a schedule")
+            .set_body::<Schedule>(gen)
+            .set_response::<Schedule>(gen),
+        Operation::new(
+            "get",
+            "/schedule",
+            "getSchedule",
+            "get a schedule by name or id",
+        )
+        .set_query::<GetScheduleParams>(gen)
+        .set_response::<Schedule>(gen),
+        Operation::new(
+            "delete",
+            "/schedule/{id}",
+            "deleteSchedule",
+            "delete a schedule",
+        ),
+        Operation::new("get", "/schedules", "listSchedules", "list schedules")
+            .set_response::<Vec<Schedule>>(gen),
+        Operation::new(
+            "post",
+            "/schedule/pause/{id}",
+            "pauseSchedule",
+            "stop creating jobs",
+        ),
+        Operation::new(
+            "post",
+            "/schedule/resume/{id}",
+            "resumeSchedule",
+            "resume a schedule",
+        ),
+        Operation::new("post", "/trigger", "createTrigger", "create a trigger")
+            .set_body::<Trigger>(gen)
+            .set_response::<Trigger>(gen),
+        Operation::new(
+            "get",
+            "/trigger",
+            "getTrigger",
+            "get a trigger by name or id",
+        )
+        .set_query::<GetTriggerParams>(gen)
+        .set_response::<Trigger>(gen),
+        Operation::new(
+            "delete",
+            "/trigger/{id}",
+            "deleteTrigger",
+            "delete a trigger",
+        ),
+        Operation::new("get", "/triggers", "listTriggers", "list triggers")
+            .set_response::<Vec<Trigger>>(gen),
+        Operation::new("get", "/openapi.json", "getOpenapi", "get this document").set_public(),
+    ]
+}
+
+/// openapi build the OpenAPI 3 document of api v1
+pub fn openapi() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let error = gen.subschema_for::<ApiError>();
+
+    let mut paths = Map::new();
+    for operation in operations(&mut gen) {
+        let path = paths.entry(operation.path).or_insert_with(|| json!({}));
+        path[operation.method] = operation.to_value(&error);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "jz-flow",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{"url": "/api/v1"}],
+        "security": [{"bearerAuth": []}],
+        "paths": paths,
+        "components": {
+            "schemas": gen.definitions(),
+            "securitySchemes": {
+                "bearerAuth": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "api token or HS256 jwt",
+                },
+            },
+        },
+    })
+}
+
+pub(super) async fn get_openapi() -> HttpResponse {
+    HttpResponse::Ok().json(openapi())
+}
+
+/// api_resource create a resource of api v1, path is relative to /api/v1 and should be documented
+/// in operations
+pub(super) fn api_resource(path: &'static str) -> Resource {
+    //tests collect registered paths to check they are all documented
+    #[cfg(test)]
+    tests::REGISTERED.with_borrow_mut(|paths| paths.insert(path));
+    web::resource(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::server::config,
+        dbrepo::{
+            MongoMainDbRepo,
+            MongoRunDbRepo,
+        },
+        driver::kube::KubeDriver,
+    };
+    use actix_web::{
+        http::{
+            Method,
+            StatusCode,
+        },
+        test,
+        web,
+        App,
+    };
+    use std::{
+        cell::RefCell,
+        collections::HashSet,
+        str::FromStr,
+    };
+
+    thread_local! {
+        /// paths passed to api_resource on this thread
+        pub(super) static REGISTERED: RefCell<HashSet<&'static str>> = RefCell::new(HashSet::new());
+    }
+
+    const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+    #[actix_web::test]
+    async fn test_openapi_match_routes() {
+        let spec = openapi();
+        let paths = spec["paths"].as_object().unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .configure(config::<KubeDriver<MongoRunDbRepo>, MongoMainDbRepo, MongoRunDbRepo>)
+                .default_service(web::to(|| async {
+                    Err::<HttpResponse, _>(ApiError::not_found("route not found"))
+                })),
+        )
+        .await;
+
+        //every route registered is documented, spec itself is served outside the authenticated
+        // scope
+        let registered: HashSet<String> = REGISTERED.with_borrow(|registered| {
+            registered
+                .iter()
+                .map(|path| path.to_string())
+                .chain(["/openapi.json".to_string()])
+                .collect()
+        });
+        let documented: HashSet<String> = paths.keys().cloned().collect();
+        assert_eq!(registered, documented);
+
+        //every documented path resolves to its own route, every documented method is served and
+        //no other method is
+        for (path, item) in paths {
+            for method in METHODS {
+                let uri = format!(
+                    "/api/v1{}",
+                    path.replace("{id}", "a")
+                        .replace("{node}", "a")
+                        .replace("{data_id}", "a")
+                );
+                let req = test::TestRequest::default()
+                    .method(Method::from_str(&method.to_uppercase()).unwrap())
+                    .uri(&uri)
+                    .to_request();
+                let resp = test::call_service(&app, req).await;
+                assert_eq!(
+                    resp.request().match_pattern(),
+                    Some(format!("/api/v1{path}")),
+                    "{method} {uri} matched another route"
+                );
+                let status = resp.status();
+                let body = test::read_body(resp).await;
+                let served = status != StatusCode::METHOD_NOT_ALLOWED
+                    && !body.ends_with(b"route not found\"}");
+                assert_eq!(
+                    served,
+                    item.get(method).is_some(),
+                    "{method} {path} responded {status}"
+                );
+            }
+        }
+
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        for name in ["Job", "JobDetails", "NodeStatus", "ApiError", "ErrorCode"] {
+            assert!(schemas.contains_key(name), "{name} not in spec");
+        }
+        let update = &paths["/job/{id}"]["post"]["parameters"];
+        assert!(update
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|parameter| parameter["name"] == "state"));
+    }
+}
@@ -0,0 +1,165 @@
+use std::str::FromStr;
+
+use super::{
+    auth::{
+        Principal,
+        Role,
+    },
+    error::ApiError,
+    job_api::check_priority,
+    openapi::api_resource,
+};
+use crate::{
+    core::db::{
+        GetScheduleParams,
+        JobDbRepo,
+        MainDbRepo,
+        Schedule,
+    },
+    driver::Driver,
+    job::job_mgr::JobManager,
+};
+use actix_web::{
+    web,
+    HttpResponse,
+};
+use mongodb::bson::oid::ObjectId;
+
+/// find_schedule get schedule by id or name, 404 if schedule not exist
+async fn find_schedule<MAINR>(db_repo: &MAINR, name_or_id: &str) -> Result<Schedule, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    let params = match ObjectId::from_str(name_or_id) {
+        Ok(id) => GetScheduleParams::new().set_id(id),
+        Err(_) => GetScheduleParams::new().set_name(name_or_id.to_string()),
+    };
+    db_repo
+        .get_schedule(&params)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("schedule {name_or_id} not found")))
+}
+
+/// authorize_schedule find schedule principal can manage
+async fn authorize_schedule<MAINR>(
+    db_repo: &MAINR,
+    principal: &Principal,
+    name_or_id: &str,
+) -> Result<Schedule, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    let schedule = find_schedule(db_repo, name_or_id).await?;
+    principal.require_owner(schedule.created_by.as_deref())?;
+    Ok(schedule)
+}
+
+async fn create<D, MAINR, JOBR>(
+    job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
+    data: web::Json<Schedule>,
+) -> Result<HttpResponse, ApiError>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    principal.require(Role::Submitter)?;
+    let mut schedule = data.into_inner();
+    check_priority(schedule.job_options.priority, &principal)?;
+    schedule.created_by = Some(principal.name);
+    let inserted_result = job_manager.create_schedule(schedule).await?;
+    Ok(HttpResponse::Ok().json(&inserted_result))
+}
+
+async fn get<MAINR>(
+    db_repo: web::Data<MAINR>,
+    principal: Principal,
+    query: web::Query<GetScheduleParams>,
+) -> Result<HttpResponse, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let schedule = db_repo
+        .get_schedule(&query.into_inner())
+        .await?
+        .ok_or_else(|| ApiError::not_found("schedule not found"))?;
+    Ok(HttpResponse::Ok().json(&schedule))
+}
+
+async fn list<MAINR>(
+    db_repo: web::Data<MAINR>,
+    principal: Principal,
+) -> Result<HttpResponse, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let schedules = db_repo.list_schedules().await?;
+    Ok(HttpResponse::Ok().json(&schedules))
+}
+
+async fn delete<MAINR>(
+    db_repo: web::Data<MAINR>,
+    principal: Principal,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    let schedule = authorize_schedule(db_repo.as_ref(), &principal, &path).await?;
+    db_repo.delete_schedule(&schedule.id).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn pause<D, MAINR, JOBR>(
+    db_repo: web::Data<MAINR>,
+    job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    let schedule = authorize_schedule(db_repo.as_ref(), &principal, &path).await?;
+    let params = GetScheduleParams::new().set_id(schedule.id);
+    job_manager.set_schedule_paused(&params, true).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn resume<D, MAINR, JOBR>(
+    db_repo: web::Data<MAINR>,
+    job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    let schedule = authorize_schedule(db_repo.as_ref(), &principal, &path).await?;
+    let params = GetScheduleParams::new().set_id(schedule.id);
+    job_manager.set_schedule_paused(&params, false).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub(super) fn schedule_route_config<D, MAINR, JOBR>(cfg: &mut web::ServiceConfig)
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    cfg.service(
+        api_resource("/schedule")
+            .route(web::post().to(create::<D, MAINR, JOBR>))
+            .route(web::get().to(get::<MAINR>)),
+    )
+    .service(api_resource("/schedule/{id}").route(web::delete().to(delete::<MAINR>)))
+    .service(api_resource("/schedules").route(web::get().to(list::<MAINR>)))
+    .service(api_resource("/schedule/pause/{id}").route(web::post().to(pause::<D, MAINR, JOBR>)))
+    .service(api_resource("/schedule/resume/{id}").route(web::post().to(resume::<D, MAINR, JOBR>)));
+}
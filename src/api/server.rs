@@ -4,8 +4,15 @@ use crate::{
         MainDbRepo,
     },
     driver::Driver,
-    job::job_mgr::JobManager,
-    utils::IntoAnyhowResult,
+    job::{
+        hooks::WebhookPolicy,
+        job_mgr::JobManager,
+        metrics::DaemonMetrics,
+    },
+    utils::{
+        health::TaskHealth,
+        IntoAnyhowResult,
+    },
 };
 use actix_web::{
     dev::Server,
@@ -23,7 +30,26 @@ use actix_web::{
 use anyhow::Result;
 use reqwest::Url;
 
-use super::job_api::job_route_config;
+use super::{
+    auth::{
+        authenticate,
+        Authenticator,
+    },
+    data_api::data_route_config,
+    error::{
+        ApiError,
+        ErrorCode,
+    },
+    health::probe,
+    job_api::job_route_config,
+    metrics::{
+        get_metrics,
+        track_request,
+    },
+    openapi::get_openapi,
+    schedule_api::schedule_route_config,
+    trigger_api::trigger_route_config,
+};
 
 fn v1_route<D, MAINR, JOBR>(cfg: &mut web::ServiceConfig)
 where
@@ -32,21 +58,38 @@ where
     JOBR: JobDbRepo,
 {
     job_route_config::<D, MAINR, JOBR>(cfg);
+    data_route_config::<D, MAINR, JOBR>(cfg);
+    schedule_route_config::<D, MAINR, JOBR>(cfg);
+    trigger_route_config::<D, MAINR, JOBR>(cfg);
 }
 
-fn config<D, MAINR, JOBR>(cfg: &mut web::ServiceConfig)
+pub(super) fn config<D, MAINR, JOBR>(cfg: &mut web::ServiceConfig)
 where
     D: Driver,
     MAINR: MainDbRepo,
     JOBR: JobDbRepo,
 {
-    cfg.service(web::scope("/api/v1").configure(v1_route::<D, MAINR, JOBR>));
+    //probes, metrics and spec are readable without token, so that kubelet, scrapers and client
+    //generators need no credentials
+    cfg.service(web::resource("/healthz").route(web::get().to(probe::<MAINR>)))
+        .service(web::resource("/readyz").route(web::get().to(probe::<MAINR>)))
+        .service(web::resource("/metrics").route(web::get().to(get_metrics)))
+        .service(web::resource("/api/v1/openapi.json").route(web::get().to(get_openapi)))
+        .service(
+            web::scope("/api/v1")
+                .wrap(middleware::from_fn(authenticate))
+                .configure(v1_route::<D, MAINR, JOBR>),
+        );
 }
 
 pub fn start_rpc_server<D, MAINR, JOBR>(
     addr: &str,
     main_db_repo: MAINR,
     job_manager: JobManager<D, MAINR, JOBR>,
+    authenticator: Authenticator,
+    metrics: DaemonMetrics,
+    tasks: TaskHealth,
+    webhook_policy: WebhookPolicy,
 ) -> Result<Server>
 where
     D: Driver,
@@ -64,23 +107,38 @@ where
         fn json_error_handler(err: error::JsonPayloadError, _req: &HttpRequest) -> error::Error {
             use actix_web::error::JsonPayloadError;
 
-            let detail = err.to_string();
-            let resp = match &err {
-                JsonPayloadError::ContentType => HttpResponse::UnsupportedMediaType().body(detail),
+            let code = match &err {
+                JsonPayloadError::ContentType => ErrorCode::UnsupportedMediaType,
                 JsonPayloadError::Deserialize(json_err) if json_err.is_data() => {
-                    HttpResponse::UnprocessableEntity().body(detail)
+                    ErrorCode::Unprocessable
                 }
-                _ => HttpResponse::BadRequest().body(detail),
+                _ => ErrorCode::BadRequest,
             };
-            error::InternalError::from_response(err, resp).into()
+            ApiError::new(code, err.to_string()).into()
         }
 
         App::new()
+            .wrap(middleware::from_fn(track_request))
             .wrap(middleware::Logger::default())
             .app_data(Data::new(main_db_repo.clone()))
             .app_data(Data::new(job_manager.clone()))
+            .app_data(Data::new(authenticator.clone()))
+            .app_data(Data::new(metrics.clone()))
+            .app_data(Data::new(tasks.clone()))
+            .app_data(Data::new(webhook_policy.clone()))
             .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+            .app_data(
+                web::PathConfig::default()
+                    .error_handler(|err, _| ApiError::bad_request(err.to_string()).into()),
+            )
+            .app_data(
+                web::QueryConfig::default()
+                    .error_handler(|err, _| ApiError::bad_request(err.to_string()).into()),
+            )
             .configure(config::<D, MAINR, JOBR>)
+            .default_service(web::to(|| async {
+                Err::<HttpResponse, _>(ApiError::not_found("route not found"))
+            }))
     })
     .disable_signals()
     .bind(host_port)?
@@ -0,0 +1,129 @@
+use std::str::FromStr;
+
+use super::{
+    auth::{
+        Principal,
+        Role,
+    },
+    error::ApiError,
+    job_api::check_priority,
+    openapi::api_resource,
+};
+use crate::{
+    core::db::{
+        GetTriggerParams,
+        JobDbRepo,
+        MainDbRepo,
+        Trigger,
+    },
+    driver::Driver,
+    job::job_mgr::JobManager,
+};
+use actix_web::{
+    web,
+    HttpResponse,
+};
+use mongodb::bson::oid::ObjectId;
+
+/// find_trigger get trigger by id or name, 404 if trigger not exist
+async fn find_trigger<MAINR>(db_repo: &MAINR, name_or_id: &str) -> Result<Trigger, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    let params = match ObjectId::from_str(name_or_id) {
+        Ok(id) => GetTriggerParams::new().set_id(id),
+        Err(_) => GetTriggerParams::new().set_name(name_or_id.to_string()),
+    };
+    db_repo
+        .get_trigger(&params)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("trigger {name_or_id} not found")))
+}
+
+/// authorize_trigger find trigger principal can manage
+async fn authorize_trigger<MAINR>(
+    db_repo: &MAINR,
+    principal: &Principal,
+    name_or_id: &str,
+) -> Result<Trigger, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    let trigger = find_trigger(db_repo, name_or_id).await?;
+    principal.require_owner(trigger.created_by.as_deref())?;
+    Ok(trigger)
+}
+
+async fn create<D, MAINR, JOBR>(
+    job_manager: web::Data<JobManager<D, MAINR, JOBR>>,
+    principal: Principal,
+    data: web::Json<Trigger>,
+) -> Result<HttpResponse, ApiError>
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    principal.require(Role::Submitter)?;
+    let mut trigger = data.into_inner();
+    check_priority(trigger.job_options.priority, &principal)?;
+    trigger.created_by = Some(principal.name);
+    let inserted_result = job_manager.create_trigger(trigger).await?;
+    Ok(HttpResponse::Ok().json(&inserted_result))
+}
+
+async fn get<MAINR>(
+    db_repo: web::Data<MAINR>,
+    principal: Principal,
+    query: web::Query<GetTriggerParams>,
+) -> Result<HttpResponse, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let trigger = db_repo
+        .get_trigger(&query.into_inner())
+        .await?
+        .ok_or_else(|| ApiError::not_found("trigger not found"))?;
+    Ok(HttpResponse::Ok().json(&trigger))
+}
+
+async fn list<MAINR>(
+    db_repo: web::Data<MAINR>,
+    principal: Principal,
+) -> Result<HttpResponse, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    principal.require(Role::Viewer)?;
+    let triggers = db_repo.list_triggers().await?;
+    Ok(HttpResponse::Ok().json(&triggers))
+}
+
+async fn delete<MAINR>(
+    db_repo: web::Data<MAINR>,
+    principal: Principal,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError>
+where
+    MAINR: MainDbRepo,
+{
+    let trigger = authorize_trigger(db_repo.as_ref(), &principal, &path).await?;
+    db_repo.delete_trigger(&trigger.id).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub(super) fn trigger_route_config<D, MAINR, JOBR>(cfg: &mut web::ServiceConfig)
+where
+    D: Driver,
+    MAINR: MainDbRepo,
+    JOBR: JobDbRepo,
+{
+    cfg.service(
+        api_resource("/trigger")
+            .route(web::post().to(create::<D, MAINR, JOBR>))
+            .route(web::get().to(get::<MAINR>)),
+    )
+    .service(api_resource("/trigger/{id}").route(web::delete().to(delete::<MAINR>)))
+    .service(api_resource("/triggers").route(web::get().to(list::<MAINR>)));
+}
@@ -1,13 +1,36 @@
-use std::str::FromStr;
+use std::{
+    env,
+    str::FromStr,
+    time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{
+    anyhow,
+    Result,
+};
 use clap::Args;
 
 use jiaoziflow::{
-    api::server::start_rpc_server,
+    api::{
+        auth::{
+            Authenticator,
+            Jwt,
+            StaticTokens,
+        },
+        grpc::start_grpc_server,
+        health::{
+            BACKEND_TASK,
+            GRPC_SERVER_TASK,
+        },
+        server::start_rpc_server,
+    },
     core::{
-        db::MainDbRepo,
+        db::{
+            Hook,
+            MainDbRepo,
+        },
         AccessMode,
+        PullPolicy,
     },
     dbrepo::{
         MongoMainDbRepo,
@@ -17,8 +40,10 @@ use jiaoziflow::{
         KubeDriver,
         KubeOptions,
     },
+    utils::health::TaskHealth,
 };
 use kube::Client;
+use mongodb::bson::oid::ObjectId;
 use tokio::{
     select,
     signal::unix::{
@@ -33,7 +58,21 @@ use tracing::{
     info,
 };
 
-use jiaoziflow::job::job_mgr::JobManager;
+use jiaoziflow::job::{
+    hooks::{
+        HookDispatcher,
+        WebhookPolicy,
+    },
+    job_mgr::JobManager,
+    leader::LeaderElector,
+    metrics::{
+        DaemonMetrics,
+        MetricsCollector,
+    },
+    queue::QueueLimits,
+    trigger::TriggerPoller,
+};
+use jiaozifs_client_rs::apis::configuration::Configuration;
 
 use crate::global::GlobalOptions;
 
@@ -59,6 +98,145 @@ pub(super) struct DaemonArgs {
         help = "specify storage class name"
     )]
     storage_class_name: String,
+
+    #[arg(
+        long,
+        default_value = "gitdatateam/compute_unit_runner:latest",
+        help = "image of compute unit runner"
+    )]
+    runner_image: String,
+
+    #[arg(
+        long,
+        default_value = "IfNotPresent",
+        help = "image pull policy of node pods(Always, IfNotPresent, Never)"
+    )]
+    pull_policy: String,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "image pull secrets used by node pods, separated by comma"
+    )]
+    image_pull_secrets: Vec<String>,
+
+    #[arg(
+        long,
+        default_value = "debug",
+        help = "log level of compute unit runner"
+    )]
+    runner_log_level: String,
+
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        help = "extra args passed to compute unit runner, can be specified multiple times"
+    )]
+    runner_args: Vec<String>,
+
+    #[arg(
+        long,
+        help = "directory contains claim.tpl, statefulset.tpl, job.tpl, service.tpl and networkpolicy.tpl to override the built-in templates"
+    )]
+    template_dir: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "create network policies to isolate node pods of every job"
+    )]
+    network_policy: bool,
+
+    #[arg(
+        long,
+        help = "jiaozifs api url used by triggers, eg. http://127.0.0.1:34913/api/v1"
+    )]
+    jiaozifs_url: Option<String>,
+
+    #[arg(long, default_value = "", help = "username of jiaozifs")]
+    jiaozifs_username: String,
+
+    #[arg(long, default_value = "", help = "password of jiaozifs")]
+    jiaozifs_password: String,
+
+    #[arg(
+        long,
+        default_value = "30",
+        help = "seconds between two polls of trigger branches"
+    )]
+    trigger_poll_interval: u64,
+
+    #[arg(
+        long,
+        help = "max number of jobs deploying or running at the same time"
+    )]
+    max_running_jobs: Option<usize>,
+
+    #[arg(long, help = "max number of jobs deploying or running of one owner")]
+    max_running_jobs_per_user: Option<usize>,
+
+    #[arg(
+        long,
+        help = "max number of jobs deploying or running with a label, format key=value:limit, can be specified multiple times"
+    )]
+    max_running_jobs_per_label: Vec<String>,
+
+    #[arg(
+        long,
+        help = "url notified of state changes of every job, can be specified multiple times"
+    )]
+    webhook: Vec<String>,
+
+    #[arg(long, help = "secret to sign body of global webhooks")]
+    webhook_secret: Option<String>,
+
+    #[arg(
+        long,
+        help = "host non-admin callers may set as webhook of their jobs, can be specified multiple times"
+    )]
+    webhook_allowed_host: Vec<String>,
+
+    #[arg(
+        long,
+        help = "program run on state changes of every job, can be specified multiple times"
+    )]
+    hook_command: Vec<String>,
+
+    #[arg(
+        long,
+        help = "name of this daemon instance recorded on jobs it hold, default to hostname"
+    )]
+    instance_id: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "15",
+        help = "seconds the backend lease is valid, another daemon take over jobs if the leader fail to renew it in time"
+    )]
+    lease_duration: u64,
+
+    #[arg(
+        long,
+        help = "api token in format token:name:role(viewer, submitter, admin), can be specified multiple times"
+    )]
+    api_token: Vec<String>,
+
+    #[arg(long, help = "secret to verify HS256 signed jwt bearer tokens")]
+    jwt_secret: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "15",
+        help = "seconds between two collections of job and backlog metrics served at /metrics"
+    )]
+    metrics_interval: u64,
+
+    #[arg(
+        long,
+        default_value = "0.0.0.0:45132",
+        help = "address of grpc job service served next to rest api"
+    )]
+    grpc_listen: String,
 }
 
 pub(super) async fn run_daemon(global_opts: GlobalOptions, args: DaemonArgs) -> Result<()> {
@@ -69,10 +247,76 @@ pub(super) async fn run_daemon(global_opts: GlobalOptions, args: DaemonArgs) ->
     let db_repo = MongoMainDbRepo::new(db_url.as_str()).await?;
     let client = Client::try_default().await.unwrap();
 
-    let kube_opts = KubeOptions::default()
+    let mut kube_opts = KubeOptions::default()
         .set_db_url(&args.mongo_url)
         .set_storage_class(&args.storage_class_name)
-        .set_access_mode(AccessMode::from_str(&args.access_mode)?);
+        .set_access_mode(AccessMode::from_str(&args.access_mode)?)
+        .set_runner_image(&args.runner_image)
+        .set_pull_policy(PullPolicy::from_str(&args.pull_policy)?)
+        .set_image_pull_secrets(args.image_pull_secrets)
+        .set_runner_log_level(&args.runner_log_level)
+        .set_runner_args(args.runner_args)
+        .set_network_policy(args.network_policy);
+    if let Some(template_dir) = args.template_dir.as_ref() {
+        kube_opts = kube_opts.set_template_dir(template_dir);
+    }
+
+    let mut queue_limits = QueueLimits::default();
+    if let Some(max_running) = args.max_running_jobs {
+        queue_limits = queue_limits.set_max_running(max_running);
+    }
+    if let Some(max_running) = args.max_running_jobs_per_user {
+        queue_limits = queue_limits.set_max_running_per_user(max_running);
+    }
+    for limit in args.max_running_jobs_per_label.iter() {
+        queue_limits = queue_limits.set_max_running_per_label(limit)?;
+    }
+
+    let hooks = args
+        .webhook
+        .into_iter()
+        .map(|url| Hook::Webhook {
+            url,
+            secret: args.webhook_secret.clone(),
+        })
+        .chain(args.hook_command.into_iter().map(|program| Hook::Command {
+            program,
+            args: vec![],
+        }))
+        .collect();
+
+    let webhook_policy = args
+        .webhook_allowed_host
+        .iter()
+        .fold(WebhookPolicy::default(), |policy, host| {
+            policy.add_allowed_host(host)
+        });
+
+    //api is open to anyone if neither tokens nor jwt secret is set
+    let mut authenticator = Authenticator::default();
+    if !args.api_token.is_empty() {
+        let mut static_tokens = StaticTokens::default();
+        for api_token in args.api_token.iter() {
+            static_tokens = static_tokens.set_token(api_token)?;
+        }
+        authenticator = authenticator.add_provider(static_tokens);
+    }
+    if let Some(secret) = args.jwt_secret.as_ref() {
+        authenticator = authenticator.add_provider(Jwt::new(secret));
+    }
+
+    //every replica serve api, only the lease holder run backend loops
+    let instance_id = args
+        .instance_id
+        .or_else(|| env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| ObjectId::new().to_hex());
+    let elector = LeaderElector::new(
+        db_repo.clone(),
+        &instance_id,
+        Duration::from_secs(args.lease_duration),
+    );
+    let leadership = elector.leadership();
+    let metrics = DaemonMetrics::new();
 
     let driver = KubeDriver::new(client.clone(), kube_opts).await?;
     let job_manager =
@@ -81,11 +325,99 @@ pub(super) async fn run_daemon(global_opts: GlobalOptions, args: DaemonArgs) ->
             &args.mongo_url,
             driver,
             db_repo.clone(),
+            db_repo.notifier().clone(),
         )
-        .await?;
+        .await?
+        .set_queue_limits(queue_limits)
+        .set_hooks(hooks)
+        .set_leadership(leadership.clone())
+        .set_metrics(metrics.clone());
+
+    {
+        let db_repo = db_repo.clone();
+        let token = token.clone();
+        join_set.spawn(async move { db_repo.watch(token).await });
+    }
 
-    job_manager.run_backend(&mut join_set, token.clone())?;
-    let server = start_rpc_server(&global_opts.listen, db_repo, job_manager)?;
+    //probes fail once job backend or grpc server exited
+    let tasks = TaskHealth::default();
+
+    elector.run_backend(&mut join_set, token.clone())?;
+    {
+        let mut backend_set = JoinSet::new();
+        job_manager.run_backend(&mut backend_set, token.clone())?;
+        let tasks = tasks.clone();
+        tasks.set_running(BACKEND_TASK, true);
+        join_set.spawn(async move {
+            let result = match backend_set.join_next().await {
+                Some(Ok(result)) => result,
+                Some(Err(err)) => Err(anyhow!("job backend panicked {err}")),
+                None => Ok(()),
+            };
+            tasks.set_running(BACKEND_TASK, false);
+            result
+        });
+    }
+    HookDispatcher::new(db_repo.clone())
+        .set_leadership(leadership.clone())
+        .set_metrics(metrics.clone())
+        .run_backend(&mut join_set, token.clone())?;
+    MetricsCollector::new(
+        db_repo.clone(),
+        &args.mongo_url,
+        metrics.clone(),
+        Duration::from_secs(args.metrics_interval),
+    )
+    .set_leadership(leadership.clone())
+    .run_backend(&mut join_set, token.clone())?;
+    if let Some(jiaozifs_url) = args.jiaozifs_url {
+        let configuration = Configuration {
+            base_path: jiaozifs_url,
+            basic_auth: Some((args.jiaozifs_username, Some(args.jiaozifs_password))),
+            ..Default::default()
+        };
+        let poller = TriggerPoller::new(
+            db_repo.clone(),
+            configuration,
+            Duration::from_secs(args.trigger_poll_interval),
+        )
+        .set_leadership(leadership)
+        .set_metrics(metrics.clone());
+        poller.run_backend(&mut join_set, token.clone())?;
+    }
+    {
+        let grpc_addr = args.grpc_listen.parse()?;
+        let db_repo = db_repo.clone();
+        let job_manager = job_manager.clone();
+        let authenticator = authenticator.clone();
+        let webhook_policy = webhook_policy.clone();
+        let token = token.clone();
+        let tasks = tasks.clone();
+        tasks.set_running(GRPC_SERVER_TASK, true);
+        join_set.spawn(async move {
+            let result = tokio::spawn(start_grpc_server(
+                grpc_addr,
+                db_repo,
+                job_manager,
+                authenticator,
+                webhook_policy,
+                token,
+            ))
+            .await
+            .unwrap_or_else(|err| Err(anyhow!("grpc server panicked {err}")));
+            tasks.set_running(GRPC_SERVER_TASK, false);
+            result
+        });
+    }
+    let server = start_rpc_server(
+        &global_opts.listen,
+        db_repo,
+        job_manager,
+        authenticator,
+        metrics,
+        tasks,
+        webhook_policy,
+    )?;
     let handler = server.handle();
     {
         let token = token.clone();
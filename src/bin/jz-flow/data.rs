@@ -0,0 +1,248 @@
+use std::str::FromStr;
+
+use crate::{
+    global::GlobalOptions,
+    job::parse_time,
+};
+use anyhow::Result;
+use chrono::DateTime;
+use clap::{
+    Args,
+    Parser,
+};
+use jiaoziflow::{
+    core::db::{
+        DataRecord,
+        DataState,
+        Direction,
+        GetJobParams,
+        Job,
+        ListDataParams,
+        NodeDataSummary,
+    },
+    utils::{
+        sizefmt::SmartSize,
+        IntoAnyhowResult,
+    },
+};
+use mongodb::bson::oid::ObjectId;
+use prettytable::{
+    Row,
+    Table,
+};
+use serde_variant::to_variant_name;
+
+#[derive(Debug, Parser)]
+pub(super) enum DataCommands {
+    /// count data of every node, or list data of a node
+    List(Box<ListDataArgs>),
+    /// show records of a data batch in all nodes
+    Get(GetDataArgs),
+}
+
+pub(super) async fn run_data_subcommand(
+    global_opts: GlobalOptions,
+    command: DataCommands,
+) -> Result<()> {
+    match command {
+        DataCommands::List(args) => list_data(global_opts, *args).await,
+        DataCommands::Get(args) => get_data(global_opts, args).await,
+    }
+}
+
+async fn find_job(global_opts: &GlobalOptions, name_or_id: &str) -> Result<Job> {
+    let get_job_params = match ObjectId::from_str(name_or_id) {
+        Ok(id) => GetJobParams::new().set_id(id),
+        Err(_) => GetJobParams::new().set_name(name_or_id.to_string()),
+    };
+    global_opts
+        .client()?
+        .job()
+        .get(&get_job_params)
+        .await?
+        .anyhow("job not exit")
+}
+
+#[derive(Debug, Args)]
+pub(super) struct ListDataArgs {
+    #[arg(index = 1, help = "job name or id")]
+    pub(super) name_or_id: String,
+
+    #[arg(
+        index = 2,
+        help = "node name, for channel its node name with suffix -channel, count data of every node if not set"
+    )]
+    pub(super) node_name: Option<String>,
+
+    #[arg(long, default_value = "table", help = "format json/table")]
+    pub(super) format: String,
+
+    #[arg(long, help = "only list data of direction in/out")]
+    pub(super) direction: Option<String>,
+
+    #[arg(long, help = "only list data in state, eg. Received, Assigned, Sent")]
+    pub(super) state: Option<String>,
+
+    #[arg(long, help = "only list data handled by machine")]
+    pub(super) machine: Option<String>,
+
+    #[arg(long, help = "only list data with priority")]
+    pub(super) priority: Option<u8>,
+
+    #[arg(
+        long,
+        help = "only list data created at or after time, unix seconds or rfc3339"
+    )]
+    pub(super) created_after: Option<String>,
+
+    #[arg(
+        long,
+        help = "only list data created before time, unix seconds or rfc3339"
+    )]
+    pub(super) created_before: Option<String>,
+
+    #[arg(
+        long,
+        help = "only list data updated at or after time, unix seconds or rfc3339"
+    )]
+    pub(super) updated_after: Option<String>,
+
+    #[arg(
+        long,
+        help = "only list data updated before time, unix seconds or rfc3339"
+    )]
+    pub(super) updated_before: Option<String>,
+
+    #[arg(long, default_value = "100", help = "max number of data to list")]
+    pub(super) limit: u64,
+
+    #[arg(long, help = "cursor printed by previous list to get the next page")]
+    pub(super) cursor: Option<String>,
+}
+
+pub(super) async fn list_data(global_opts: GlobalOptions, args: ListDataArgs) -> Result<()> {
+    let job = find_job(&global_opts, &args.name_or_id).await?;
+    let client = global_opts.client()?.data();
+
+    let Some(node_name) = args.node_name.as_ref() else {
+        let summary = client.summary(&job.id).await?;
+        return print_summary(&args.format, &summary);
+    };
+
+    let list_data_params = ListDataParams {
+        direction: args
+            .direction
+            .as_deref()
+            .map(Direction::from_str)
+            .transpose()?,
+        state: args.state.as_deref().map(DataState::from_str).transpose()?,
+        machine: args.machine.clone(),
+        priority: args.priority,
+        created_after: parse_time(&args.created_after)?,
+        created_before: parse_time(&args.created_before)?,
+        updated_after: parse_time(&args.updated_after)?,
+        updated_before: parse_time(&args.updated_before)?,
+        limit: Some(args.limit),
+        cursor: args.cursor.clone(),
+    };
+    let data_list = client.list(&job.id, node_name, &list_data_params).await?;
+    if args.format == "json" {
+        println!("{}", serde_json::to_string_pretty(&data_list)?);
+        return Ok(());
+    }
+
+    print_records(&data_list.records, false)?;
+    println!("Total: {}", data_list.total);
+    if let Some(next_cursor) = data_list.next_cursor.as_ref() {
+        eprintln!("More data, list next page with --cursor {next_cursor}");
+    }
+    Ok(())
+}
+
+fn print_summary(format: &str, summary: &[NodeDataSummary]) -> Result<()> {
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.add_row(Row::from(vec!["Node", "Direction", "State", "Count"]));
+    for node in summary {
+        for count in node.counts.iter() {
+            table.add_row(Row::from(vec![
+                cell!(node.node_name),
+                cell!(to_variant_name(&count.direction)?),
+                cell!(to_variant_name(&count.state)?),
+                cell!(count.count),
+            ]));
+        }
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn print_records(records: &[DataRecord], with_node: bool) -> Result<()> {
+    let mut table = Table::new();
+    let mut header = vec![
+        "ID",
+        "Direction",
+        "State",
+        "Machine",
+        "Priority",
+        "Size",
+        "Sent",
+        "CreatedAt",
+        "UpdatedAt",
+    ];
+    if with_node {
+        header.insert(0, "Node");
+    }
+    table.add_row(Row::from(header));
+
+    for record in records {
+        let mut row = vec![
+            cell!(record.id),
+            cell!(to_variant_name(&record.direction)?),
+            cell!(to_variant_name(&record.state)?),
+            cell!(record.machine),
+            cell!(record.priority),
+            cell!((record.size as u64).to_smart_string()),
+            cell!(record.sent.join(",")),
+            cell!(DateTime::from_timestamp(record.created_at, 0).unwrap()),
+            cell!(DateTime::from_timestamp(record.updated_at, 0).unwrap()),
+        ];
+        if with_node {
+            row.insert(0, cell!(record.node_name));
+        }
+        table.add_row(Row::new(row));
+    }
+    table.printstd();
+    Ok(())
+}
+
+#[derive(Debug, Args)]
+pub(super) struct GetDataArgs {
+    #[arg(index = 1, help = "job name or id")]
+    pub(super) name_or_id: String,
+
+    #[arg(index = 2, help = "id of data batch")]
+    pub(super) id: String,
+
+    #[arg(long, default_value = "table", help = "format json/table")]
+    pub(super) format: String,
+}
+
+pub(super) async fn get_data(global_opts: GlobalOptions, args: GetDataArgs) -> Result<()> {
+    let job = find_job(&global_opts, &args.name_or_id).await?;
+    let records = global_opts
+        .client()?
+        .data()
+        .get_batch(&job.id, &args.id)
+        .await?;
+
+    if args.format == "json" {
+        println!("{}", serde_json::to_string_pretty(&records)?);
+        return Ok(());
+    }
+    print_records(&records, true)
+}
@@ -1,4 +1,9 @@
+use anyhow::Result;
 use clap::Args;
+use jiaoziflow::api::{
+    auth::read_token,
+    client::JzFlowClient,
+};
 
 #[derive(Debug, Args)]
 pub(super) struct GlobalOptions {
@@ -15,4 +20,32 @@ pub(super) struct GlobalOptions {
         help = "set api address"
     )]
     pub(super) listen: String,
+
+    #[arg(
+        long,
+        help = "api token, fallback to JZFLOW_TOKEN env and token field of config file"
+    )]
+    pub(super) token: Option<String>,
+
+    #[arg(
+        long,
+        help = "client config file, default to $HOME/.jz-flow/config.json"
+    )]
+    pub(super) config: Option<String>,
+}
+
+impl GlobalOptions {
+    /// client build api client carrying token if any
+    pub(super) fn client(&self) -> Result<JzFlowClient> {
+        let config = self.config.clone().or_else(|| {
+            std::env::var("HOME")
+                .ok()
+                .map(|home| format!("{home}/.jz-flow/config.json"))
+        });
+        let client = JzFlowClient::new(&self.listen)?;
+        match read_token(self.token.as_deref(), config.as_deref())? {
+            Some(token) => client.set_token(&token),
+            None => Ok(client),
+        }
+    }
 }
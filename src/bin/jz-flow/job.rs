@@ -1,7 +1,14 @@
-use std::str::FromStr;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    time::Duration,
+};
 
 use crate::global::GlobalOptions;
-use anyhow::Result;
+use anyhow::{
+    anyhow,
+    Result,
+};
 use chrono::{
     DateTime,
     Utc,
@@ -11,12 +18,24 @@ use clap::{
     Parser,
 };
 use jiaoziflow::{
-    api::client::JzFlowClient,
     core::db::{
+        CancelJobParams,
         GetJobParams,
+        Hook,
         Job,
+        JobList,
+        JobOptions,
+        JobSortField,
+        JobState,
+        ListJobParams,
+        SortOrder,
+        TimeoutAction,
     },
     dag::Dag,
+    job::{
+        job_mgr::JobDetails,
+        watch::apply_event,
+    },
     utils::{
         sizefmt::SmartSize,
         IntoAnyhowResult,
@@ -28,16 +47,23 @@ use prettytable::{
     Table,
 };
 use serde_variant::to_variant_name;
-use tokio::fs;
+use tokio::{
+    fs,
+    time::sleep,
+};
 
 #[derive(Debug, Parser)]
 pub(super) enum JobCommands {
     /// Adds files to myapp
-    Create(JobCreateArgs),
+    Create(Box<JobCreateArgs>),
+    /// start a deployed job or resume a paused job
     Run(RunJobArgs),
-    List(ListJobArgs),
+    List(Box<ListJobArgs>),
     Detail(JobDetailArgs),
+    Watch(WatchJobArgs),
     Clean(CleanJobArgs),
+    Retry(RetryJobArgs),
+    Cancel(CancelJobArgs),
 }
 
 pub(super) async fn run_job_subcommand(
@@ -45,11 +71,14 @@ pub(super) async fn run_job_subcommand(
     command: JobCommands,
 ) -> Result<()> {
     match command {
-        JobCommands::Create(args) => create_job(global_opts, args).await,
+        JobCommands::Create(args) => create_job(global_opts, *args).await,
         JobCommands::Run(args) => run_job(global_opts, args).await,
-        JobCommands::List(args) => list_job(global_opts, args).await,
+        JobCommands::List(args) => list_job(global_opts, *args).await,
         JobCommands::Detail(args) => get_job_details(global_opts, args).await,
+        JobCommands::Watch(args) => watch_job(global_opts, args).await,
         JobCommands::Clean(args) => clean_job(global_opts, args).await,
+        JobCommands::Retry(args) => retry_job(global_opts, args).await,
+        JobCommands::Cancel(args) => cancel_job(global_opts, args).await,
     }
 }
 
@@ -63,12 +92,102 @@ pub(super) struct JobCreateArgs {
 
     #[arg(long, help = "only deploy not run immediately")]
     pub(super) manual_run: bool,
+
+    #[command(flatten)]
+    pub(super) options: JobOptionArgs,
+
+    #[arg(
+        long,
+        help = "url notified of state changes of this job, its host must be allowed by daemon unless caller is admin, can be specified multiple times"
+    )]
+    pub(super) webhook: Vec<String>,
+
+    #[arg(long, help = "secret to sign body of webhooks")]
+    pub(super) webhook_secret: Option<String>,
+
+    #[arg(
+        long,
+        help = "print resources and node records job would create without creating it"
+    )]
+    pub(super) dry_run: bool,
+
+    #[arg(
+        short,
+        long,
+        default_value = "yaml",
+        help = "output format of dry run, yaml/json"
+    )]
+    pub(super) output: String,
+}
+
+/// JobOptionArgs is the priority, labels and timeouts of jobs, shared by job, schedule and
+/// trigger creation
+#[derive(Debug, Args)]
+pub(super) struct JobOptionArgs {
+    #[arg(
+        long,
+        default_value = "0",
+        allow_hyphen_values = true,
+        help = "job with higher priority is deployed first, only admin may set it above 0"
+    )]
+    pub(super) priority: i32,
+
+    #[arg(long = "label", help = "label of job, format key=value")]
+    pub(super) labels: Vec<String>,
+
+    #[arg(long, help = "seconds job may run before it is expired")]
+    pub(super) timeout: Option<u64>,
+
+    #[arg(
+        long,
+        help = "seconds a node may make no progress while it has data to process"
+    )]
+    pub(super) stall_timeout: Option<u64>,
+
+    #[arg(
+        long,
+        default_value = "fail",
+        help = "what to do when job expired, fail/cancel/pause/notify"
+    )]
+    pub(super) timeout_action: String,
+}
+
+impl JobOptionArgs {
+    pub(super) fn job_options(&self) -> Result<JobOptions> {
+        let labels = self
+            .labels
+            .iter()
+            .map(|label| {
+                label
+                    .split_once('=')
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .ok_or(anyhow!("label {label} must be key=value"))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        Ok(JobOptions {
+            priority: self.priority,
+            labels,
+            timeout: self.timeout,
+            stall_timeout: self.stall_timeout,
+            timeout_action: TimeoutAction::from_str(&self.timeout_action)?,
+        })
+    }
 }
 
 pub(super) async fn create_job(global_opts: GlobalOptions, args: JobCreateArgs) -> Result<()> {
-    let client = JzFlowClient::new(&global_opts.listen)?.job();
+    let client = global_opts.client()?.job();
     let dag_config = fs::read_to_string(&args.path).await?;
     let _ = Dag::from_json(dag_config.as_str())?;
+
+    let hooks = args
+        .webhook
+        .into_iter()
+        .map(|url| Hook::Webhook {
+            url,
+            secret: args.webhook_secret.clone(),
+        })
+        .collect();
+
     let tm = Utc::now().timestamp();
     let job = Job {
         name: args.name.clone(),
@@ -76,8 +195,20 @@ pub(super) async fn create_job(global_opts: GlobalOptions, args: JobCreateArgs)
         created_at: tm,
         updated_at: tm,
         manual_run: args.manual_run,
+        hooks,
         ..Default::default()
-    };
+    }
+    .set_options(&args.options.job_options()?);
+
+    if args.dry_run {
+        let plan = client.plan(&job).await?;
+        match args.output.as_str() {
+            "yaml" => print!("{}", serde_yaml::to_string(&plan)?),
+            "json" => println!("{}", serde_json::to_string_pretty(&plan)?),
+            output => return Err(anyhow!("unsupported output format {output}")),
+        }
+        return Ok(());
+    }
 
     let created_job = client.create(&job).await?;
 
@@ -89,14 +220,135 @@ pub(super) async fn create_job(global_opts: GlobalOptions, args: JobCreateArgs)
 pub(super) struct ListJobArgs {
     #[arg(long, default_value = "table", help = "format json/table")]
     pub(super) format: String,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "only list jobs in these states, separated by comma"
+    )]
+    pub(super) state: Vec<String>,
+
+    #[arg(long, help = "only list jobs whose name start with prefix")]
+    pub(super) name_prefix: Option<String>,
+
+    #[arg(long, help = "only list jobs whose name match regex")]
+    pub(super) name_regex: Option<String>,
+
+    #[arg(
+        long,
+        help = "only list jobs with label, format key=value, can be specified multiple times"
+    )]
+    pub(super) label: Vec<String>,
+
+    #[arg(long, help = "only list jobs created by owner")]
+    pub(super) owner: Option<String>,
+
+    #[arg(
+        long,
+        help = "only list jobs created at or after time, unix seconds or rfc3339"
+    )]
+    pub(super) created_after: Option<String>,
+
+    #[arg(
+        long,
+        help = "only list jobs created before time, unix seconds or rfc3339"
+    )]
+    pub(super) created_before: Option<String>,
+
+    #[arg(
+        long,
+        help = "only list jobs updated at or after time, unix seconds or rfc3339"
+    )]
+    pub(super) updated_after: Option<String>,
+
+    #[arg(
+        long,
+        help = "only list jobs updated before time, unix seconds or rfc3339"
+    )]
+    pub(super) updated_before: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "created_at",
+        help = "sort jobs by created_at/updated_at/name"
+    )]
+    pub(super) sort: String,
+
+    #[arg(long, default_value = "false", help = "sort jobs in descending order")]
+    pub(super) desc: bool,
+
+    #[arg(long, help = "max number of jobs to list")]
+    pub(super) limit: Option<u64>,
+
+    #[arg(long, help = "cursor printed by previous list to get the next page")]
+    pub(super) cursor: Option<String>,
+
+    #[arg(long, default_value = "false", help = "refresh list every 2 seconds")]
+    pub(super) watch: bool,
+}
+
+/// parse_time parse unix seconds or rfc3339 time
+pub(super) fn parse_time(input: &Option<String>) -> Result<Option<i64>> {
+    let Some(input) = input.as_ref() else {
+        return Ok(None);
+    };
+    if let Ok(tm) = input.parse::<i64>() {
+        return Ok(Some(tm));
+    }
+    DateTime::parse_from_rfc3339(input)
+        .map(|tm| Some(tm.timestamp()))
+        .map_err(|err| anyhow!("parse time {input} {err}"))
 }
 
 pub(super) async fn list_job(global_opts: GlobalOptions, args: ListJobArgs) -> Result<()> {
-    let client = JzFlowClient::new(&global_opts.listen)?.job();
-    let jobs = client.list().await?;
+    let client = global_opts.client()?.job();
+    let list_job_params = ListJobParams {
+        states: args
+            .state
+            .iter()
+            .map(|state| JobState::from_str(state))
+            .collect::<Result<_>>()?,
+        name_prefix: args.name_prefix.clone(),
+        name_regex: args.name_regex.clone(),
+        labels: args.label.clone(),
+        owner: args.owner.clone(),
+        created_after: parse_time(&args.created_after)?,
+        created_before: parse_time(&args.created_before)?,
+        updated_after: parse_time(&args.updated_after)?,
+        updated_before: parse_time(&args.updated_before)?,
+        sort: JobSortField::from_str(&args.sort)?,
+        order: if args.desc {
+            SortOrder::Desc
+        } else {
+            SortOrder::Asc
+        },
+        limit: args.limit,
+        cursor: args.cursor.clone(),
+        ..Default::default()
+    };
 
-    if args.format == "json" {
-        println!("{}", serde_json::to_string_pretty(&jobs)?);
+    loop {
+        let job_list = client.list(&list_job_params).await?;
+        if args.watch {
+            //clear screen before redraw
+            print!("\x1B[2J\x1B[1;1H");
+        }
+        print_jobs(&args.format, &job_list)?;
+        if let Some(next_cursor) = job_list.next_cursor.as_ref() {
+            eprintln!("More jobs, list next page with --cursor {next_cursor}");
+        }
+
+        if !args.watch {
+            return Ok(());
+        }
+        sleep(Duration::from_secs(2)).await;
+    }
+}
+
+fn print_jobs(format: &str, job_list: &JobList) -> Result<()> {
+    let jobs = &job_list.jobs;
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(jobs)?);
         return Ok(());
     }
 
@@ -107,6 +359,8 @@ pub(super) async fn list_job(global_opts: GlobalOptions, args: ListJobArgs) -> R
         "ID",
         "Name",
         "State",
+        "Priority",
+        "QueuePosition",
         "CreatedAt",
         "UpdatedAt",
     ]));
@@ -116,6 +370,12 @@ pub(super) async fn list_job(global_opts: GlobalOptions, args: ListJobArgs) -> R
             cell!(job.id),
             cell!(job.name),
             cell!(to_variant_name(&job.state).unwrap()),
+            cell!(job.priority),
+            cell!(job_list
+                .queue_positions
+                .get(&job.id.to_hex())
+                .map(|position| position.to_string())
+                .unwrap_or_default()),
             cell!(DateTime::from_timestamp(job.created_at, 0).unwrap()),
             cell!(DateTime::from_timestamp(job.updated_at, 0).unwrap()),
         ]));
@@ -135,7 +395,7 @@ pub(super) struct JobDetailArgs {
 }
 
 pub(super) async fn get_job_details(global_opts: GlobalOptions, args: JobDetailArgs) -> Result<()> {
-    let client = JzFlowClient::new(&global_opts.listen)?.job();
+    let client = global_opts.client()?.job();
     let get_job_params = match ObjectId::from_str(&args.name_or_id) {
         Ok(id) => GetJobParams::new().set_id(id),
         Err(_) => GetJobParams::new().set_name(args.name_or_id),
@@ -149,11 +409,75 @@ pub(super) async fn get_job_details(global_opts: GlobalOptions, args: JobDetailA
         return Ok(());
     }
 
+    print_job_details(&job_detail)?;
+
+    let deliveries = client.list_deliveries(&job.id).await?;
+    if !deliveries.is_empty() {
+        println!("Hook deliveries:");
+        let mut table = Table::new();
+        table.add_row(Row::from(vec![
+            "Event",
+            "Hook",
+            "State",
+            "Attempts",
+            "LastError",
+        ]));
+        for delivery in deliveries {
+            let hook = match &delivery.hook {
+                Hook::Webhook { url, .. } => url.clone(),
+                Hook::Command { program, .. } => program.clone(),
+            };
+            table.add_row(Row::from(vec![
+                cell!(delivery.event_key),
+                cell!(hook),
+                cell!(to_variant_name(&delivery.state)?),
+                cell!(delivery.attempts),
+                cell!(delivery.last_error.unwrap_or_default()),
+            ]));
+        }
+        table.printstd();
+    }
+    Ok(())
+}
+
+#[derive(Debug, Args)]
+pub(super) struct WatchJobArgs {
+    #[arg(index = 1, help = "job name or id")]
+    pub(super) name_or_id: String,
+}
+
+pub(super) async fn watch_job(global_opts: GlobalOptions, args: WatchJobArgs) -> Result<()> {
+    let client = global_opts.client()?.job();
+    let get_job_params = match ObjectId::from_str(&args.name_or_id) {
+        Ok(id) => GetJobParams::new().set_id(id),
+        Err(_) => GetJobParams::new().set_name(args.name_or_id),
+    };
+    let job: Job = client.get(&get_job_params).await?.anyhow("job not exit")?;
+
+    let mut events = client.watch(&job.id).await?;
+    let mut job_detail = None;
+    while let Some(event) = events.next().await? {
+        apply_event(&mut job_detail, event);
+        if let Some(job_detail) = job_detail.as_ref() {
+            //clear screen before redraw
+            print!("\x1B[2J\x1B[1;1H");
+            print_job_details(job_detail)?;
+        }
+    }
+    println!(
+        "Stopped watching job {}, it was deleted or daemon closed the stream",
+        job.name
+    );
+    Ok(())
+}
+
+fn print_job_details(job_detail: &JobDetails) -> Result<()> {
     let mut table = Table::new();
     table.add_row(Row::from(vec![
         "ID",
         "Name",
         "State",
+        "Attempt",
         "CreatedAt",
         "UpdatedAt",
     ]));
@@ -162,11 +486,79 @@ pub(super) async fn get_job_details(global_opts: GlobalOptions, args: JobDetailA
         cell!(job_detail.job.id),
         cell!(job_detail.job.name),
         cell!(to_variant_name(&job_detail.job.state).unwrap()),
+        cell!(job_detail.job.attempts.len() + 1),
         cell!(DateTime::from_timestamp(job_detail.job.created_at, 0).unwrap()),
         cell!(DateTime::from_timestamp(job_detail.job.updated_at, 0).unwrap()),
     ]));
     table.printstd();
 
+    if let Some(reason) = job_detail.job.reason.as_ref() {
+        println!("Reason: {reason}");
+    }
+
+    if let Some(owner) = job_detail.job.owner.as_ref() {
+        println!("Owner: {owner}");
+    }
+
+    if let Some(holder) = job_detail.job.holder.as_ref() {
+        println!("Held by daemon: {holder}");
+    }
+
+    if let Some(completed_batches) = job_detail.job.completed_batches {
+        println!("Completed batches before cancelled: {completed_batches}");
+    }
+
+    if !job_detail.job.attempts.is_empty() {
+        println!("Attempts:");
+        let mut table = Table::new();
+        table.add_row(Row::from(vec!["Attempt", "State", "StartedAt", "EndedAt"]));
+        for (index, attempt) in job_detail.job.attempts.iter().enumerate() {
+            table.add_row(Row::from(vec![
+                cell!(index + 1),
+                cell!(to_variant_name(&attempt.state)?),
+                cell!(DateTime::from_timestamp(attempt.started_at, 0).unwrap()),
+                cell!(DateTime::from_timestamp(attempt.ended_at, 0).unwrap()),
+            ]));
+        }
+        table.printstd();
+    }
+
+    if let Some(summary) = job_detail.job.summary.as_ref() {
+        println!("Run summary:");
+        let mut table = Table::new();
+        table.add_row(Row::from(vec![
+            "NodeName",
+            "WallTime",
+            "BatchesIn",
+            "BatchesOut",
+            "FilesIn",
+            "FilesOut",
+            "Errors",
+            "PeakBacklog",
+            "AvgBatchLatency",
+        ]));
+        for node in summary.nodes.iter() {
+            table.add_row(Row::from(vec![
+                cell!(node.node_name),
+                cell!(node
+                    .wall_time
+                    .map(|wall_time| format!("{wall_time}s"))
+                    .unwrap_or_default()),
+                cell!(node.batches_in),
+                cell!(node.batches_out),
+                cell!(node.files_in),
+                cell!(node.files_out),
+                cell!(node.errors),
+                cell!(node.peak_backlog),
+                cell!(node
+                    .avg_batch_latency
+                    .map(|latency| format!("{latency:.1}s"))
+                    .unwrap_or_default()),
+            ]));
+        }
+        table.printstd();
+    }
+
     if job_detail.node_status.is_none() {
         return Ok(());
     }
@@ -182,10 +574,10 @@ pub(super) async fn get_job_details(global_opts: GlobalOptions, args: JobDetailA
         "TmpStorage",
         "Pods",
     ]));
-    for status in job_detail.node_status.unwrap() {
+    for status in job_detail.node_status.iter().flatten() {
         let mut pod_table = Table::new();
         pod_table.add_row(Row::from(vec!["Name", "State", "CPU", "Memory"]));
-        for pod in status.pods {
+        for pod in status.pods.iter() {
             pod_table.add_row(Row::from(vec![
                 cell!(pod.0),
                 cell!(pod.1.state),
@@ -214,7 +606,7 @@ pub(super) struct RunJobArgs {
 }
 
 pub(super) async fn run_job(global_opts: GlobalOptions, args: RunJobArgs) -> Result<()> {
-    let client = JzFlowClient::new(&global_opts.listen)?.job();
+    let client = global_opts.client()?.job();
 
     let get_job_params = match ObjectId::from_str(&args.name_or_id) {
         Ok(id) => GetJobParams::new().set_id(id),
@@ -235,7 +627,7 @@ pub(super) struct CleanJobArgs {
 }
 
 pub(super) async fn clean_job(global_opts: GlobalOptions, args: CleanJobArgs) -> Result<()> {
-    let client = JzFlowClient::new(&global_opts.listen)?.job();
+    let client = global_opts.client()?.job();
 
     let get_job_params = match ObjectId::from_str(&args.name_or_id) {
         Ok(id) => GetJobParams::new().set_id(id),
@@ -248,3 +640,63 @@ pub(super) async fn clean_job(global_opts: GlobalOptions, args: CleanJobArgs) ->
     println!("Clean job successfully, job ID: {}", job.id);
     Ok(())
 }
+
+#[derive(Debug, Args)]
+pub(super) struct RetryJobArgs {
+    #[arg(index = 1, help = "job name or id")]
+    pub(super) name_or_id: String,
+}
+
+pub(super) async fn retry_job(global_opts: GlobalOptions, args: RetryJobArgs) -> Result<()> {
+    let client = global_opts.client()?.job();
+
+    let get_job_params = match ObjectId::from_str(&args.name_or_id) {
+        Ok(id) => GetJobParams::new().set_id(id),
+        Err(_) => GetJobParams::new().set_name(args.name_or_id),
+    };
+    let job = client.get(&get_job_params).await?.anyhow("job not exit")?;
+
+    client.retry_job(&job.id).await?;
+
+    println!(
+        "Retry job successfully, job ID: {} attempt: {}",
+        job.id,
+        job.attempts.len() + 2
+    );
+    Ok(())
+}
+
+#[derive(Debug, Args)]
+pub(super) struct CancelJobArgs {
+    #[arg(index = 1, help = "job name or id")]
+    pub(super) name_or_id: String,
+
+    #[arg(
+        long,
+        default_value = "60",
+        help = "seconds user containers have to finish assigned data"
+    )]
+    pub(super) grace_period: u64,
+}
+
+pub(super) async fn cancel_job(global_opts: GlobalOptions, args: CancelJobArgs) -> Result<()> {
+    let client = global_opts.client()?.job();
+
+    let get_job_params = match ObjectId::from_str(&args.name_or_id) {
+        Ok(id) => GetJobParams::new().set_id(id),
+        Err(_) => GetJobParams::new().set_name(args.name_or_id),
+    };
+    let job = client.get(&get_job_params).await?.anyhow("job not exit")?;
+
+    client
+        .cancel_job(
+            &job.id,
+            &CancelJobParams {
+                grace_period: Some(args.grace_period),
+            },
+        )
+        .await?;
+
+    println!("Cancel job successfully, job ID: {}", job.id);
+    Ok(())
+}
@@ -3,8 +3,11 @@
 extern crate prettytable;
 
 mod daemon;
+mod data;
 mod global;
 mod job;
+mod schedule;
+mod trigger;
 
 use anyhow::Result;
 use clap::{
@@ -16,18 +19,34 @@ use daemon::{
     run_daemon,
     DaemonArgs,
 };
+use data::{
+    run_data_subcommand,
+    DataCommands,
+};
 use global::GlobalOptions;
 use job::{
     run_job_subcommand,
     JobCommands,
 };
+use schedule::{
+    run_schedule_subcommand,
+    ScheduleCommands,
+};
+use trigger::{
+    run_trigger_subcommand,
+    TriggerCommands,
+};
 
 use jiaoziflow::{
+    api::error::ApiError,
     core::db::MainDbRepo,
     utils::StdIntoAnyhowResult,
 };
 
-use std::str::FromStr;
+use std::{
+    process::exit,
+    str::FromStr,
+};
 use tracing::Level;
 
 #[derive(Debug, Parser)]
@@ -43,10 +62,19 @@ struct Cli {
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Adds files to myapp
-    Daemon(DaemonArgs),
+    Daemon(Box<DaemonArgs>),
 
     #[command(subcommand)]
     Job(JobCommands),
+
+    #[command(subcommand)]
+    Data(DataCommands),
+
+    #[command(subcommand)]
+    Schedule(ScheduleCommands),
+
+    #[command(subcommand)]
+    Trigger(TriggerCommands),
 }
 
 #[tokio::main(flavor = "multi_thread")]
@@ -58,8 +86,29 @@ async fn main() -> Result<()> {
         .try_init()
         .anyhow()?;
 
-    match args.command {
-        Commands::Daemon(run_args) => run_daemon(args.global_opts, run_args).await,
+    let result = match args.command {
+        Commands::Daemon(run_args) => run_daemon(args.global_opts, *run_args).await,
         Commands::Job(job_commands) => run_job_subcommand(args.global_opts, job_commands).await,
+        Commands::Data(data_commands) => run_data_subcommand(args.global_opts, data_commands).await,
+        Commands::Schedule(schedule_commands) => {
+            run_schedule_subcommand(args.global_opts, schedule_commands).await
+        }
+        Commands::Trigger(trigger_commands) => {
+            run_trigger_subcommand(args.global_opts, trigger_commands).await
+        }
+    };
+
+    //print errors returned by api without debug formatting
+    if let Some(api_error) = result
+        .as_ref()
+        .err()
+        .and_then(|err| err.downcast_ref::<ApiError>())
+    {
+        eprintln!("Error: {api_error}");
+        if let Some(details) = api_error.details.as_ref() {
+            eprintln!("{}", serde_json::to_string_pretty(details)?);
+        }
+        exit(1);
     }
+    result
 }
@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+};
+
+use crate::{
+    global::GlobalOptions,
+    job::JobOptionArgs,
+};
+use anyhow::{
+    anyhow,
+    Result,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use clap::{
+    Args,
+    Parser,
+};
+use jiaoziflow::{
+    core::db::{
+        ConcurrencyPolicy,
+        GetScheduleParams,
+        Schedule,
+    },
+    utils::IntoAnyhowResult,
+};
+use mongodb::bson::oid::ObjectId;
+use prettytable::{
+    Row,
+    Table,
+};
+use serde_variant::to_variant_name;
+use tokio::fs;
+
+#[derive(Debug, Parser)]
+pub(super) enum ScheduleCommands {
+    /// Create a schedule which run a dag periodically
+    Create(ScheduleCreateArgs),
+    List(ListScheduleArgs),
+    Pause(ScheduleNameArgs),
+    Resume(ScheduleNameArgs),
+    Delete(ScheduleNameArgs),
+}
+
+pub(super) async fn run_schedule_subcommand(
+    global_opts: GlobalOptions,
+    command: ScheduleCommands,
+) -> Result<()> {
+    match command {
+        ScheduleCommands::Create(args) => create_schedule(global_opts, args).await,
+        ScheduleCommands::List(args) => list_schedule(global_opts, args).await,
+        ScheduleCommands::Pause(args) => pause_schedule(global_opts, args, true).await,
+        ScheduleCommands::Resume(args) => pause_schedule(global_opts, args, false).await,
+        ScheduleCommands::Delete(args) => delete_schedule(global_opts, args).await,
+    }
+}
+
+#[derive(Debug, Args)]
+pub(super) struct ScheduleCreateArgs {
+    #[arg(long, help = "schedule name, must be unique, runs are named by it")]
+    pub(super) name: String,
+
+    #[arg(long, help = "dag pipline definition")]
+    pub(super) path: String,
+
+    #[arg(long, help = "cron expression, eg. \"0 2 * * *\"")]
+    pub(super) cron: Option<String>,
+
+    #[arg(long, help = "seconds between two runs, used when cron is not set")]
+    pub(super) interval: Option<u64>,
+
+    #[arg(long = "param", help = "parameter fill in dag, format key=value")]
+    pub(super) params: Vec<String>,
+
+    #[arg(
+        long,
+        default_value = "allow",
+        help = "what to do when last run is active, allow/forbid/replace"
+    )]
+    pub(super) concurrency_policy: String,
+
+    #[command(flatten)]
+    pub(super) options: JobOptionArgs,
+}
+
+pub(super) async fn create_schedule(
+    global_opts: GlobalOptions,
+    args: ScheduleCreateArgs,
+) -> Result<()> {
+    let client = global_opts.client()?.schedule();
+    let graph_json = fs::read_to_string(&args.path).await?;
+
+    let parameters = args
+        .params
+        .iter()
+        .map(|param| {
+            param
+                .split_once('=')
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .ok_or(anyhow!("param {param} must be key=value"))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let schedule = Schedule {
+        name: args.name,
+        cron: args.cron,
+        interval: args.interval,
+        graph_json,
+        parameters,
+        concurrency_policy: ConcurrencyPolicy::from_str(&args.concurrency_policy)?,
+        job_options: args.options.job_options()?,
+        ..Default::default()
+    };
+
+    let created = client.create(&schedule).await?;
+    println!(
+        "Create schedule successfully, schedule ID: {} next run at: {}",
+        created.id,
+        format_time(Some(created.next_run_at))
+    );
+    Ok(())
+}
+
+fn format_time(tm: Option<i64>) -> String {
+    tm.and_then(|tm| DateTime::<Utc>::from_timestamp(tm, 0))
+        .map(|tm| tm.to_string())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Args)]
+pub(super) struct ListScheduleArgs {
+    #[arg(long, default_value = "table", help = "format json/table")]
+    pub(super) format: String,
+}
+
+pub(super) async fn list_schedule(
+    global_opts: GlobalOptions,
+    args: ListScheduleArgs,
+) -> Result<()> {
+    let client = global_opts.client()?.schedule();
+    let schedules = client.list().await?;
+
+    if args.format == "json" {
+        println!("{}", serde_json::to_string_pretty(&schedules)?);
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.add_row(Row::from(vec![
+        "ID",
+        "Name",
+        "Trigger",
+        "ConcurrencyPolicy",
+        "Paused",
+        "NextRunAt",
+        "LastRunAt",
+    ]));
+
+    for schedule in schedules {
+        let trigger = match (schedule.cron.as_ref(), schedule.interval) {
+            (Some(cron), _) => cron.clone(),
+            (None, Some(interval)) => format!("every {interval}s"),
+            _ => String::new(),
+        };
+        table.add_row(Row::from(vec![
+            cell!(schedule.id),
+            cell!(schedule.name),
+            cell!(trigger),
+            cell!(to_variant_name(&schedule.concurrency_policy)?),
+            cell!(schedule.paused),
+            cell!(format_time(Some(schedule.next_run_at))),
+            cell!(format_time(schedule.last_run_at)),
+        ]));
+    }
+
+    table.printstd();
+    Ok(())
+}
+
+#[derive(Debug, Args)]
+pub(super) struct ScheduleNameArgs {
+    #[arg(index = 1, help = "schedule name or id")]
+    pub(super) name_or_id: String,
+}
+
+async fn get_schedule(global_opts: &GlobalOptions, name_or_id: String) -> Result<Schedule> {
+    let client = global_opts.client()?.schedule();
+    let params = match ObjectId::from_str(&name_or_id) {
+        Ok(id) => GetScheduleParams::new().set_id(id),
+        Err(_) => GetScheduleParams::new().set_name(name_or_id),
+    };
+    client.get(&params).await?.anyhow("schedule not exit")
+}
+
+pub(super) async fn pause_schedule(
+    global_opts: GlobalOptions,
+    args: ScheduleNameArgs,
+    paused: bool,
+) -> Result<()> {
+    let client = global_opts.client()?.schedule();
+    let schedule = get_schedule(&global_opts, args.name_or_id).await?;
+
+    if paused {
+        client.pause(&schedule.id).await?;
+        println!("Pause schedule successfully, schedule ID: {}", schedule.id);
+    } else {
+        client.resume(&schedule.id).await?;
+        println!("Resume schedule successfully, schedule ID: {}", schedule.id);
+    }
+    Ok(())
+}
+
+pub(super) async fn delete_schedule(
+    global_opts: GlobalOptions,
+    args: ScheduleNameArgs,
+) -> Result<()> {
+    let client = global_opts.client()?.schedule();
+    let schedule = get_schedule(&global_opts, args.name_or_id).await?;
+
+    client.delete(&schedule.id).await?;
+    println!("Delete schedule successfully, schedule ID: {}", schedule.id);
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+};
+
+use crate::{
+    global::GlobalOptions,
+    job::JobOptionArgs,
+};
+use anyhow::{
+    anyhow,
+    Result,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use clap::{
+    Args,
+    Parser,
+};
+use jiaoziflow::{
+    core::db::{
+        GetTriggerParams,
+        Trigger,
+    },
+    utils::IntoAnyhowResult,
+};
+use mongodb::bson::oid::ObjectId;
+use prettytable::{
+    Row,
+    Table,
+};
+use tokio::fs;
+
+#[derive(Debug, Parser)]
+pub(super) enum TriggerCommands {
+    /// Create a trigger which run a dag when branch of jiaozifs repository has new commits
+    Create(Box<TriggerCreateArgs>),
+    List(ListTriggerArgs),
+    Delete(TriggerNameArgs),
+}
+
+pub(super) async fn run_trigger_subcommand(
+    global_opts: GlobalOptions,
+    command: TriggerCommands,
+) -> Result<()> {
+    match command {
+        TriggerCommands::Create(args) => create_trigger(global_opts, *args).await,
+        TriggerCommands::List(args) => list_trigger(global_opts, args).await,
+        TriggerCommands::Delete(args) => delete_trigger(global_opts, args).await,
+    }
+}
+
+#[derive(Debug, Args)]
+pub(super) struct TriggerCreateArgs {
+    #[arg(long, help = "trigger name, must be unique, runs are named by it")]
+    pub(super) name: String,
+
+    #[arg(
+        long,
+        help = "dag pipline definition, commit hash is filled in {{commit_hash}}"
+    )]
+    pub(super) path: String,
+
+    #[arg(long, help = "owner of jiaozifs repository")]
+    pub(super) owner: String,
+
+    #[arg(long, help = "jiaozifs repository name")]
+    pub(super) repo: String,
+
+    #[arg(long, default_value = "main", help = "branch to watch")]
+    pub(super) branch: String,
+
+    #[arg(long, help = "only run when changes touch this path in repository")]
+    pub(super) watch_path: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "60",
+        help = "seconds branch must stay unchanged before a run started"
+    )]
+    pub(super) debounce: u64,
+
+    #[arg(long = "param", help = "parameter fill in dag, format key=value")]
+    pub(super) params: Vec<String>,
+
+    #[command(flatten)]
+    pub(super) options: JobOptionArgs,
+}
+
+pub(super) async fn create_trigger(
+    global_opts: GlobalOptions,
+    args: TriggerCreateArgs,
+) -> Result<()> {
+    let client = global_opts.client()?.trigger();
+    let graph_json = fs::read_to_string(&args.path).await?;
+
+    let parameters = args
+        .params
+        .iter()
+        .map(|param| {
+            param
+                .split_once('=')
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .ok_or(anyhow!("param {param} must be key=value"))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let trigger = Trigger {
+        name: args.name,
+        owner: args.owner,
+        repo: args.repo,
+        branch: args.branch,
+        path: args.watch_path,
+        graph_json,
+        parameters,
+        debounce: args.debounce,
+        job_options: args.options.job_options()?,
+        ..Default::default()
+    };
+
+    let created = client.create(&trigger).await?;
+    println!("Create trigger successfully, trigger ID: {}", created.id);
+    Ok(())
+}
+
+fn format_time(tm: Option<i64>) -> String {
+    tm.and_then(|tm| DateTime::<Utc>::from_timestamp(tm, 0))
+        .map(|tm| tm.to_string())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Args)]
+pub(super) struct ListTriggerArgs {
+    #[arg(long, default_value = "table", help = "format json/table")]
+    pub(super) format: String,
+}
+
+pub(super) async fn list_trigger(global_opts: GlobalOptions, args: ListTriggerArgs) -> Result<()> {
+    let client = global_opts.client()?.trigger();
+    let triggers = client.list().await?;
+
+    if args.format == "json" {
+        println!("{}", serde_json::to_string_pretty(&triggers)?);
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.add_row(Row::from(vec![
+        "ID",
+        "Name",
+        "Ref",
+        "Path",
+        "Debounce",
+        "LastSeenCommit",
+        "LastRunAt",
+    ]));
+
+    for trigger in triggers {
+        table.add_row(Row::from(vec![
+            cell!(trigger.id),
+            cell!(trigger.name),
+            cell!(format!(
+                "{}/{}/{}",
+                trigger.owner, trigger.repo, trigger.branch
+            )),
+            cell!(trigger.path.unwrap_or_default()),
+            cell!(format!("{}s", trigger.debounce)),
+            cell!(trigger.cursor.last_seen_commit.unwrap_or_default()),
+            cell!(format_time(trigger.last_run_at)),
+        ]));
+    }
+
+    table.printstd();
+    Ok(())
+}
+
+#[derive(Debug, Args)]
+pub(super) struct TriggerNameArgs {
+    #[arg(index = 1, help = "trigger name or id")]
+    pub(super) name_or_id: String,
+}
+
+pub(super) async fn delete_trigger(
+    global_opts: GlobalOptions,
+    args: TriggerNameArgs,
+) -> Result<()> {
+    let client = global_opts.client()?.trigger();
+    let params = match ObjectId::from_str(&args.name_or_id) {
+        Ok(id) => GetTriggerParams::new().set_id(id),
+        Err(_) => GetTriggerParams::new().set_name(args.name_or_id),
+    };
+    let trigger = client.get(&params).await?.anyhow("trigger not exit")?;
+
+    client.delete(&trigger.id).await?;
+    println!("Delete trigger successfully, trigger ID: {}", trigger.id);
+    Ok(())
+}
@@ -0,0 +1,6 @@
+use anyhow::Result;
+
+pub trait PingRepo {
+    /// ping check whether database is reachable, used by health probes
+    fn ping(&self) -> impl std::future::Future<Output = Result<()>> + Send;
+}
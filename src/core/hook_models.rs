@@ -0,0 +1,144 @@
+use super::{
+    job_db_models::TrackerState,
+    main_db_models::{
+        JobState,
+        ObjectIdSchema,
+    },
+};
+use anyhow::Result;
+use mongodb::bson::oid::ObjectId;
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Hook is a sink notified when job or its nodes change state
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Hook {
+    /// post event as json, body is signed by hmac-sha256 of secret if set
+    Webhook { url: String, secret: Option<String> },
+    /// run program with event json in stdin and JIAOZIFLOW_EVENT
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+/// placeholder of webhook secret in api responses
+pub const REDACTED_SECRET: &str = "<redacted>";
+
+impl Hook {
+    pub fn is_webhook(&self) -> bool {
+        matches!(self, Hook::Webhook { .. })
+    }
+
+    /// redacted hide secret of webhook, for hooks returned by api
+    pub fn redacted(self) -> Hook {
+        match self {
+            Hook::Webhook { url, secret } => Hook::Webhook {
+                url,
+                secret: secret.map(|_| REDACTED_SECRET.to_string()),
+            },
+            hook => hook,
+        }
+    }
+}
+
+/// HookEvent is the payload delivered to hooks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HookEvent {
+    Job {
+        #[schemars(with = "ObjectIdSchema")]
+        job_id: ObjectId,
+        job_name: String,
+        state: JobState,
+        reason: Option<String>,
+        time: i64,
+    },
+    Node {
+        #[schemars(with = "ObjectIdSchema")]
+        job_id: ObjectId,
+        job_name: String,
+        node_name: String,
+        state: TrackerState,
+        time: i64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, JsonSchema)]
+pub enum DeliveryState {
+    #[default]
+    Pending,
+    Delivered,
+    Failed,
+}
+
+/// HookDelivery record the delivery of an event to a hook
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct HookDelivery {
+    #[serde(rename = "_id")]
+    #[schemars(with = "ObjectIdSchema")]
+    pub id: ObjectId,
+    #[schemars(with = "ObjectIdSchema")]
+    pub job_id: ObjectId,
+    /// identify the transition, an event is delivered once for every attempt of job
+    pub event_key: String,
+    pub hook: Hook,
+    pub event: HookEvent,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl HookDelivery {
+    pub fn redacted(mut self) -> HookDelivery {
+        self.hook = self.hook.redacted();
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HookDeliveryUpdateInfo {
+    pub state: DeliveryState,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: i64,
+}
+
+pub trait HookRepo {
+    fn insert_delivery(
+        &self,
+        delivery: &HookDelivery,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+
+    /// has_event return true if event was recorded for job
+    fn has_event(
+        &self,
+        job_id: &ObjectId,
+        event_key: &str,
+    ) -> impl std::future::Future<Output = Result<bool>> + Send;
+
+    /// list_due_deliveries list pending deliveries should be attempted at now
+    fn list_due_deliveries(
+        &self,
+        now: i64,
+    ) -> impl std::future::Future<Output = Result<Vec<HookDelivery>>> + Send;
+
+    fn list_deliveries(
+        &self,
+        job_id: &ObjectId,
+    ) -> impl std::future::Future<Output = Result<Vec<HookDelivery>>> + Send;
+
+    fn update_delivery(
+        &self,
+        id: &ObjectId,
+        info: &HookDeliveryUpdateInfo,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+}
@@ -1,16 +1,22 @@
-use anyhow::Result;
+use super::health_models::PingRepo;
+use anyhow::{
+    anyhow,
+    Result,
+};
+use schemars::JsonSchema;
 use serde::{
     Deserialize,
     Serialize,
 };
+use std::str::FromStr;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum NodeType {
     CoputeUnit,
     Channel,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum TrackerState {
     Init,
     Ready,
@@ -18,6 +24,7 @@ pub enum TrackerState {
     Stopped,
     InComingFinish, //mean all incoming data was processed
     Finish,
+    Error, //workload of node failed in cluster
 }
 
 impl TrackerState {
@@ -29,6 +36,7 @@ impl TrackerState {
             TrackerState::Stopped => true,
             TrackerState::InComingFinish => false,
             TrackerState::Finish => true,
+            TrackerState::Error => true,
         }
     }
 }
@@ -39,13 +47,19 @@ pub struct Graph {
     pub updated_at: i64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct Node {
     pub node_name: String,
     pub state: TrackerState,
     pub node_type: NodeType,
     pub up_nodes: Vec<String>,
     pub outgoing_streams: Vec<String>,
+    /// unix time node first became ready
+    #[serde(default)]
+    pub ready_at: Option<i64>,
+    /// unix time node finished
+    #[serde(default)]
+    pub finished_at: Option<i64>,
     pub created_at: i64,
     pub updated_at: i64,
 }
@@ -59,7 +73,7 @@ pub struct Node {
 ///
 /// for outgoing data flow of compute unit:  Received(compute unit) -> SelectForSend(compute unit)
 /// -> PartialSent(compute unit)->Sent(compute unit)
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub enum DataState {
     Received,
     Assigned,
@@ -73,13 +87,51 @@ pub enum DataState {
     Error,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+impl DataState {
+    pub const ALL: [DataState; 10] = [
+        DataState::Received,
+        DataState::Assigned,
+        DataState::Processed,
+        DataState::SelectForSend,
+        DataState::PartialSent,
+        DataState::Sent,
+        DataState::EndRecieved,
+        DataState::CleanButKeepData,
+        DataState::Clean,
+        DataState::Error,
+    ];
+}
+
+impl FromStr for DataState {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<DataState, Self::Err> {
+        DataState::ALL
+            .into_iter()
+            .find(|state| format!("{state:?}").eq_ignore_ascii_case(input))
+            .ok_or_else(|| anyhow!("unsupport data state {input}"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub enum Direction {
     In,
     Out,
 }
 
-#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+impl FromStr for Direction {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Direction, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "in" => Ok(Direction::In),
+            "out" => Ok(Direction::Out),
+            _ => Err(anyhow!("unsupport direction {input}")),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct DataFlag {
     pub is_keep_data: bool,
     pub is_transparent_data: bool,
@@ -112,7 +164,7 @@ impl DataFlag {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct DataRecord {
     /// node's for compute unit its a name,  for channel its node_name+ "-channel"
     pub node_name: String,
@@ -129,6 +181,48 @@ pub struct DataRecord {
     pub updated_at: i64,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct ListDataParams {
+    pub direction: Option<Direction>,
+    pub state: Option<DataState>,
+    pub machine: Option<String>,
+    pub priority: Option<u8>,
+    /// unix time range, after is inclusive and before is exclusive
+    pub created_after: Option<i64>,
+    pub created_before: Option<i64>,
+    pub updated_after: Option<i64>,
+    pub updated_before: Option<i64>,
+    /// max number of records returned, all records if not set
+    pub limit: Option<u64>,
+    /// next_cursor of previous page
+    pub cursor: Option<String>,
+}
+
+/// DataList is one page of data records of a node
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct DataList {
+    pub records: Vec<DataRecord>,
+    /// number of records match the filters in all pages
+    pub total: u64,
+    /// pass as cursor to get the next page, None if no more records
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+pub struct DataStateCount {
+    pub direction: Direction,
+    pub state: DataState,
+    pub count: u64,
+}
+
+/// NodeDataSummary count data records of a node by direction and state
+#[derive(Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+pub struct NodeDataSummary {
+    /// for channel its node_name+ "-channel"
+    pub node_name: String,
+    pub counts: Vec<DataStateCount>,
+}
+
 pub trait GraphRepo {
     fn insert_global_state(
         &self,
@@ -157,6 +251,9 @@ pub trait NodeRepo {
         &self,
         name: &str,
     ) -> impl std::future::Future<Output = Result<()>> + Send;
+
+    /// reset_unfinished_nodes move nodes not finished back to Init, used when job is resumed
+    fn reset_unfinished_nodes(&self) -> impl std::future::Future<Output = Result<u64>> + Send;
 }
 
 pub trait DataRepo {
@@ -182,12 +279,41 @@ pub trait DataRepo {
         direction: &Direction,
     ) -> impl std::future::Future<Output = Result<u64>> + Send;
 
+    /// revert_in_flight move Assigned and SelectForSend data of all nodes back to Received, used
+    /// when job is resumed and no one hold these data
+    fn revert_in_flight(&self) -> impl std::future::Future<Output = Result<u64>> + Send;
+
+    /// last_updated_at return the latest update time of data of node, None if node has no data
+    fn last_updated_at(
+        &self,
+        node_name: &str,
+    ) -> impl std::future::Future<Output = Result<Option<i64>>> + Send;
+
+    fn list_by_node_name(
+        &self,
+        node_name: &str,
+    ) -> impl std::future::Future<Output = Result<Vec<DataRecord>>> + Send;
+
+    /// list_by_node_name_and_state list a page of data of node match params, ordered by
+    /// insertion
     fn list_by_node_name_and_state(
         &self,
         node_name: &str,
-        state: &DataState,
+        params: &ListDataParams,
+    ) -> impl std::future::Future<Output = Result<DataList>> + Send;
+
+    /// list_by_id list records of a batch in all nodes, ordered by creation which is the path
+    /// it went through
+    fn list_by_id(
+        &self,
+        id: &str,
     ) -> impl std::future::Future<Output = Result<Vec<DataRecord>>> + Send;
 
+    /// summarize_by_node count data of every node by direction and state
+    fn summarize_by_node(
+        &self,
+    ) -> impl std::future::Future<Output = Result<Vec<NodeDataSummary>>> + Send;
+
     fn count(
         &self,
         node_name: &str,
@@ -210,7 +336,7 @@ pub trait DataRepo {
     ) -> impl std::future::Future<Output = Result<()>> + Send;
 }
 
-pub trait JobDbRepo = GraphRepo + NodeRepo + DataRepo + Clone + Send + Sync + 'static;
+pub trait JobDbRepo = GraphRepo + NodeRepo + DataRepo + PingRepo + Clone + Send + Sync + 'static;
 
 #[cfg(test)]
 mod tests {
@@ -225,4 +351,17 @@ mod tests {
         let bit_value = data.to_bit();
         assert_eq!(bit_value, 3);
     }
+
+    #[test]
+    fn test_parse_data_filters() {
+        assert_eq!(
+            DataState::from_str("selectforsend").unwrap(),
+            DataState::SelectForSend
+        );
+        assert_eq!(DataState::from_str("Error").unwrap(), DataState::Error);
+        assert!(DataState::from_str("Unknown").is_err());
+
+        assert_eq!(Direction::from_str("Out").unwrap(), Direction::Out);
+        assert!(Direction::from_str("both").is_err());
+    }
 }
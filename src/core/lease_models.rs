@@ -0,0 +1,34 @@
+use anyhow::Result;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Lease is held by one daemon instance at a time, holder must renew it before it expires
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Lease {
+    #[serde(rename = "_id")]
+    pub name: String,
+    pub holder: String,
+    pub expires_at: i64,
+    pub renewed_at: i64,
+}
+
+pub trait LeaseRepo {
+    /// acquire_lease take the lease or renew it until expires_at, return false if lease is held
+    /// by another instance and not expired at now
+    fn acquire_lease(
+        &self,
+        name: &str,
+        holder: &str,
+        now: i64,
+        expires_at: i64,
+    ) -> impl std::future::Future<Output = Result<bool>> + Send;
+
+    /// release_lease expire lease if it is held by holder, so others can take it at once
+    fn release_lease(
+        &self,
+        name: &str,
+        holder: &str,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+}
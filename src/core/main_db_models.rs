@@ -1,47 +1,580 @@
-use anyhow::Result;
-use mongodb::bson::oid::ObjectId;
+use super::{
+    health_models::PingRepo,
+    hook_models::{
+        Hook,
+        HookRepo,
+    },
+    lease_models::LeaseRepo,
+    schedule_models::ScheduleRepo,
+    trigger_models::TriggerRepo,
+};
+use anyhow::{
+    anyhow,
+    Result,
+};
+use base64::{
+    engine::general_purpose::URL_SAFE_NO_PAD,
+    Engine,
+};
+use mongodb::bson::{
+    oid::ObjectId,
+    Bson,
+};
+use schemars::JsonSchema;
 use serde::{
     Deserialize,
+    Deserializer,
     Serialize,
 };
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+};
 
-#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, JsonSchema)]
 pub enum JobState {
     #[default]
     Created,
+    /// waiting for capacity to deploy
+    Queued,
     Selected,
     Deployed,
     Running,
     Error,
     Finish,
     Clean,
+    Cancelling,
+    Cancelled,
+    /// nodes stopped by timeout action, resources are kept for inspection
+    Paused,
+}
+
+impl JobState {
+    pub const ALL: [JobState; 11] = [
+        JobState::Created,
+        JobState::Queued,
+        JobState::Selected,
+        JobState::Deployed,
+        JobState::Running,
+        JobState::Error,
+        JobState::Finish,
+        JobState::Clean,
+        JobState::Cancelling,
+        JobState::Cancelled,
+        JobState::Paused,
+    ];
+
+    /// next_states return the states job can move to from this state, all job state changes
+    /// must follow it
+    pub fn next_states(&self) -> &'static [JobState] {
+        match self {
+            JobState::Created => &[JobState::Queued, JobState::Cancelling, JobState::Clean],
+            JobState::Queued => &[JobState::Selected, JobState::Cancelling, JobState::Clean],
+            JobState::Selected => &[JobState::Deployed, JobState::Error],
+            JobState::Deployed => &[
+                JobState::Running,
+                JobState::Error,
+                JobState::Cancelling,
+                JobState::Clean,
+            ],
+            JobState::Running => &[
+                JobState::Finish,
+                JobState::Error,
+                JobState::Cancelling,
+                JobState::Paused,
+                JobState::Clean,
+            ],
+            JobState::Error => &[JobState::Created, JobState::Cancelling, JobState::Clean],
+            JobState::Finish => &[JobState::Clean],
+            JobState::Clean => &[],
+            JobState::Cancelling => &[JobState::Cancelled],
+            JobState::Cancelled => &[],
+            JobState::Paused => &[JobState::Running, JobState::Cancelling, JobState::Clean],
+        }
+    }
+
+    pub fn can_move_to(&self, next: &JobState) -> bool {
+        self.next_states().contains(next)
+    }
+
+    /// prev_states return the states can move to next
+    pub fn prev_states(next: &JobState) -> Vec<JobState> {
+        JobState::ALL
+            .into_iter()
+            .filter(|state| state.can_move_to(next))
+            .collect()
+    }
+
+    /// is_active return true if job is waiting for resources or running
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            JobState::Created
+                | JobState::Queued
+                | JobState::Selected
+                | JobState::Deployed
+                | JobState::Running
+                | JobState::Cancelling
+                | JobState::Paused
+        )
+    }
+
+    /// has_job_db return true if job database may exist, it is created by deploy and dropped
+    /// when job is cleaned
+    pub fn has_job_db(&self) -> bool {
+        matches!(
+            self,
+            JobState::Deployed
+                | JobState::Running
+                | JobState::Paused
+                | JobState::Cancelling
+                | JobState::Finish
+                | JobState::Error
+        )
+    }
+}
+
+/// InvalidTransition is returned when a job state change break the state machine
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InvalidTransition {
+    pub from: JobState,
+    pub to: JobState,
+    pub allowed: Vec<JobState>,
+}
+
+impl InvalidTransition {
+    pub fn new(from: JobState, to: JobState) -> Self {
+        let allowed = from.next_states().to_vec();
+        InvalidTransition { from, to, allowed }
+    }
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "can not move job from {:?} to {:?}, allowed transitions {:?}",
+            self.from, self.to, self.allowed
+        )
+    }
+}
+
+impl std::error::Error for InvalidTransition {}
+
+/// InvalidSpec is returned when a submitted job, schedule or trigger is not valid
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidSpec(pub String);
+
+impl fmt::Display for InvalidSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidSpec {}
+
+/// NoJobDb is returned when reading data of a job which is not deployed or already cleaned
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoJobDb {
+    pub name: String,
+    pub state: JobState,
+}
+
+impl fmt::Display for NoJobDb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "job {} has no data in state {:?}", self.name, self.state)
+    }
+}
+
+impl std::error::Error for NoJobDb {}
+
+/// TimeoutAction decide what to do when job exceed its deadline or a node stalled
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeoutAction {
+    /// move job to Error
+    #[default]
+    Fail,
+    /// cancel job with default grace period
+    Cancel,
+    /// stop nodes accepting new data and keep resources
+    Pause,
+    /// only record the reason, job keep running
+    Notify,
+}
+
+impl FromStr for JobState {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<JobState, Self::Err> {
+        JobState::ALL
+            .into_iter()
+            .find(|state| format!("{state:?}").eq_ignore_ascii_case(input))
+            .ok_or_else(|| anyhow!("unsupport job state {input}"))
+    }
+}
+
+impl FromStr for TimeoutAction {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<TimeoutAction, Self::Err> {
+        match input {
+            "fail" => Ok(TimeoutAction::Fail),
+            "cancel" => Ok(TimeoutAction::Cancel),
+            "pause" => Ok(TimeoutAction::Pause),
+            "notify" => Ok(TimeoutAction::Notify),
+            _ => Err(anyhow!("unsupport timeout action {input}")),
+        }
+    }
+}
+
+/// ObjectIdSchema describe how ObjectId is encoded in json bodies, only used by json schema
+#[derive(JsonSchema)]
+#[schemars(rename = "ObjectId")]
+pub struct ObjectIdSchema {
+    #[schemars(rename = "$oid")]
+    pub oid: String,
+}
+
+/// JobOptions is the queueing and timeout settings schedules and triggers give to the jobs they
+/// create
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct JobOptions {
+    /// only admin may set it above 0
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// seconds job may run before it is expired
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// seconds a node may make no progress while it has data to process
+    #[serde(default)]
+    pub stall_timeout: Option<u64>,
+    #[serde(default)]
+    pub timeout_action: TimeoutAction,
 }
 
-#[derive(Default, Serialize, Deserialize, Debug)]
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct Job {
     #[serde(rename = "_id")]
+    #[schemars(with = "ObjectIdSchema")]
     pub id: ObjectId,
     pub name: String,
     pub graph_json: String,
     pub state: JobState,
     pub manual_run: bool,
+    /// job with higher priority is deployed first, only admin may set it above 0
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// seconds job may run before it is expired
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// seconds a node may make no progress while it has data to process
+    #[serde(default)]
+    pub stall_timeout: Option<u64>,
+    #[serde(default)]
+    pub timeout_action: TimeoutAction,
+    /// unix time job started running or was resumed last time in current attempt
+    #[serde(default)]
+    pub started_at: Option<i64>,
+    /// seconds job ran in current attempt before started_at, time paused is not counted
+    #[serde(default)]
+    pub active_secs: u64,
+    /// why job failed, was paused or was notified
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// webhooks notified on state changes of job and its nodes, besides the global hooks
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
+    #[serde(default)]
+    pub attempts: Vec<JobAttempt>,
+    /// unix time after which a cancelling job is cleaned even if data is still processing
+    #[serde(default)]
+    pub cancel_deadline: Option<i64>,
+    /// number of batches processed before job was cancelled
+    #[serde(default)]
+    pub completed_batches: Option<u64>,
+    /// schedule which created this job
+    #[serde(default)]
+    #[schemars(with = "Option<ObjectIdSchema>")]
+    pub schedule_id: Option<ObjectId>,
+    /// trigger which created this job
+    #[serde(default)]
+    #[schemars(with = "Option<ObjectIdSchema>")]
+    pub trigger_id: Option<ObjectId>,
+    /// authenticated caller created this job, or creator of its schedule or trigger. per user
+    /// limits count jobs by owner
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// daemon instance which deployed or took over this job
+    #[serde(default)]
+    pub holder: Option<String>,
+    /// run metrics computed before job database was dropped
+    #[serde(default)]
+    pub summary: Option<RunSummary>,
     pub created_at: i64,
     pub updated_at: i64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// NodeSummary is the run metrics of a node computed from its data records
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, JsonSchema)]
+pub struct NodeSummary {
+    pub node_name: String,
+    /// seconds from node ready to finish, None if node never finished
+    pub wall_time: Option<i64>,
+    pub batches_in: u64,
+    pub batches_out: u64,
+    pub files_in: u64,
+    pub files_out: u64,
+    /// number of batches failed
+    pub errors: u64,
+    /// max number of incoming batches waiting or processing at the same time
+    pub peak_backlog: u64,
+    /// average seconds from an incoming batch received to processed
+    pub avg_batch_latency: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, JsonSchema)]
+pub struct RunSummary {
+    pub nodes: Vec<NodeSummary>,
+    pub created_at: i64,
+}
+
+/// JobAttempt record a finished run of job, a new attempt start when job is retried
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct JobAttempt {
+    pub state: JobState,
+    pub started_at: i64,
+    pub ended_at: i64,
+}
+
+impl Job {
+    /// current_attempt summarize the running attempt of job as if it ended at ended_at
+    pub fn current_attempt(&self, ended_at: i64) -> JobAttempt {
+        JobAttempt {
+            state: self.state.clone(),
+            started_at: self
+                .attempts
+                .last()
+                .map(|attempt| attempt.ended_at)
+                .unwrap_or(self.created_at),
+            ended_at,
+        }
+    }
+
+    pub fn set_options(mut self, options: &JobOptions) -> Self {
+        self.priority = options.priority;
+        self.labels = options.labels.clone();
+        self.timeout = options.timeout;
+        self.stall_timeout = options.stall_timeout;
+        self.timeout_action = options.timeout_action.clone();
+        self
+    }
+
+    /// submitted reset fields owned by server of a job posted by client, so it enter the state
+    /// machine as a new Created job
+    pub fn submitted(mut self, now: i64) -> Job {
+        self.id = ObjectId::new();
+        self.state = JobState::Created;
+        self.started_at = None;
+        self.active_secs = 0;
+        self.reason = None;
+        self.attempts = vec![];
+        self.cancel_deadline = None;
+        self.completed_batches = None;
+        self.schedule_id = None;
+        self.trigger_id = None;
+        self.owner = None;
+        self.holder = None;
+        self.summary = None;
+        self.created_at = now;
+        self.updated_at = now;
+        self
+    }
+
+    /// redacted hide secrets of job hooks, for jobs returned by api
+    pub fn redacted(mut self) -> Job {
+        self.hooks = self.hooks.into_iter().map(Hook::redacted).collect();
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, JsonSchema)]
 pub struct JobUpdateInfo {
     pub state: Option<JobState>,
+    pub started_at: Option<i64>,
+    pub active_secs: Option<u64>,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobSortField {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Name,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl JobSortField {
+    pub fn field_name(&self) -> &'static str {
+        match self {
+            JobSortField::CreatedAt => "created_at",
+            JobSortField::UpdatedAt => "updated_at",
+            JobSortField::Name => "name",
+        }
+    }
+
+    fn value_of(&self, job: &Job) -> Bson {
+        match self {
+            JobSortField::CreatedAt => Bson::Int64(job.created_at),
+            JobSortField::UpdatedAt => Bson::Int64(job.updated_at),
+            JobSortField::Name => Bson::String(job.name.clone()),
+        }
+    }
+}
+
+impl FromStr for JobSortField {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<JobSortField, Self::Err> {
+        match input {
+            "created_at" => Ok(JobSortField::CreatedAt),
+            "updated_at" => Ok(JobSortField::UpdatedAt),
+            "name" => Ok(JobSortField::Name),
+            _ => Err(anyhow!("unsupport sort field {input}")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// JobCursor is the sort value and id of the last job of a page, next page start after it
+#[derive(Debug, PartialEq)]
+pub struct JobCursor {
+    pub value: Bson,
+    pub id: ObjectId,
+}
+
+impl JobCursor {
+    /// encode cursor as canonical extended json so value keep its bson type
+    pub fn encode(&self) -> Result<String> {
+        let content =
+            Bson::Array(vec![self.value.clone(), Bson::ObjectId(self.id)]).into_canonical_extjson();
+        Ok(URL_SAFE_NO_PAD.encode(serde_json::to_vec(&content)?))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        let invalid = |err: String| anyhow!("invalid cursor {err}");
+        let content = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|err| invalid(err.to_string()))?;
+        let content: serde_json::Value =
+            serde_json::from_slice(&content).map_err(|err| invalid(err.to_string()))?;
+        match Bson::try_from(content).map_err(|err| invalid(err.to_string()))? {
+            Bson::Array(mut items) if items.len() == 2 => match items.pop() {
+                Some(Bson::ObjectId(id)) => Ok(JobCursor {
+                    value: items.remove(0),
+                    id,
+                }),
+                _ => Err(invalid("id".to_string())),
+            },
+            _ => Err(invalid("format".to_string())),
+        }
+    }
+}
+
+/// deserialize comma separated values in query string
+fn comma_separated<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = Option::<String>::deserialize(deserializer)?.unwrap_or_default();
+    value
+        .split(',')
+        .filter(|item| !item.is_empty())
+        .map(|item| T::from_str(item).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub struct ListJobParams {
-    pub state: Option<JobState>,
+    /// match jobs in any of these states
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub states: Vec<JobState>,
+    pub name_prefix: Option<String>,
+    pub name_regex: Option<String>,
+    /// labels job must have, in format key=value
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub labels: Vec<String>,
+    pub owner: Option<String>,
+    #[schemars(with = "Option<String>")]
+    pub schedule_id: Option<ObjectId>,
+    #[schemars(with = "Option<String>")]
+    pub trigger_id: Option<ObjectId>,
+    /// unix time range, after is inclusive and before is exclusive
+    pub created_after: Option<i64>,
+    pub created_before: Option<i64>,
+    pub updated_after: Option<i64>,
+    pub updated_before: Option<i64>,
+    #[serde(default)]
+    pub sort: JobSortField,
+    #[serde(default)]
+    pub order: SortOrder,
+    /// max number of jobs returned, all jobs if not set
+    pub limit: Option<u64>,
+    /// next_cursor of previous page
+    pub cursor: Option<String>,
+}
+
+impl ListJobParams {
+    /// next_cursor return cursor of the page after jobs, None if jobs is the last page
+    pub fn next_cursor(&self, jobs: &[Job]) -> Result<Option<String>> {
+        match (self.limit, jobs.last()) {
+            (Some(limit), Some(last)) if jobs.len() as u64 >= limit => JobCursor {
+                value: self.sort.value_of(last),
+                id: last.id,
+            }
+            .encode()
+            .map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// JobList is one page of jobs
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct JobList {
+    pub jobs: Vec<Job>,
+    /// pass as cursor to get the next page, None if no more jobs
+    pub next_cursor: Option<String>,
+    /// job id -> position of queued jobs in this page among all queued jobs, starting from 1
+    #[serde(default)]
+    pub queue_positions: HashMap<String, usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct CancelJobParams {
+    /// seconds user containers have to finish assigned data
+    pub grace_period: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct GetJobParams {
     pub name: Option<String>,
+    #[schemars(with = "Option<String>")]
     pub id: Option<ObjectId>,
 }
 
@@ -80,8 +613,26 @@ pub trait JobRepo {
 
     fn delete(&self, id: &ObjectId) -> impl std::future::Future<Output = Result<()>> + Send;
 
-    fn get_job_for_running(&self) -> impl std::future::Future<Output = Result<Option<Job>>> + Send;
+    /// queue_created_jobs move all created jobs to Queued, return the number of jobs moved
+    fn queue_created_jobs(&self) -> impl std::future::Future<Output = Result<u64>> + Send;
 
+    /// select_job move a queued job to Selected for deploying by holder, return None if job was
+    /// selected by others or not queued
+    fn select_job(
+        &self,
+        id: &ObjectId,
+        holder: &str,
+    ) -> impl std::future::Future<Output = Result<Option<Job>>> + Send;
+
+    /// set_holder record the daemon instance taking over job
+    fn set_holder(
+        &self,
+        id: &ObjectId,
+        holder: &str,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+
+    /// update job, state is only changed if current state can move to it, otherwise
+    /// InvalidTransition is returned
     fn update(
         &self,
         id: &ObjectId,
@@ -92,6 +643,204 @@ pub trait JobRepo {
         &self,
         list_job_params: &ListJobParams,
     ) -> impl std::future::Future<Output = Result<Vec<Job>>> + Send;
+
+    /// count_jobs return number of jobs in every state which has jobs
+    fn count_jobs(&self) -> impl std::future::Future<Output = Result<Vec<(JobState, u64)>>> + Send;
+
+    /// cancel move a job to Cancelling with a drain deadline, return InvalidTransition if job is
+    /// deploying or already ended
+    fn cancel(
+        &self,
+        id: &ObjectId,
+        deadline: i64,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+
+    /// mark_cancelled move a cancelling job to Cancelled and record processed batches
+    fn mark_cancelled(
+        &self,
+        id: &ObjectId,
+        completed_batches: u64,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+
+    fn set_summary(
+        &self,
+        id: &ObjectId,
+        summary: &RunSummary,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+
+    /// retry record the finished attempt and move a failed job back to Created, return
+    /// InvalidTransition if job is not in Error state
+    fn retry(
+        &self,
+        id: &ObjectId,
+        attempt: &JobAttempt,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
 }
 
-pub trait MainDbRepo = JobRepo + Clone + Send + Sync + 'static;
+pub trait MainDbRepo = JobRepo
+    + ScheduleRepo
+    + TriggerRepo
+    + HookRepo
+    + LeaseRepo
+    + PingRepo
+    + Clone
+    + Send
+    + Sync
+    + 'static;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::REDACTED_SECRET;
+
+    #[test]
+    fn test_current_attempt() {
+        let mut job = Job {
+            state: JobState::Error,
+            created_at: 100,
+            ..Default::default()
+        };
+        let attempt = job.current_attempt(200);
+        assert_eq!(
+            attempt,
+            JobAttempt {
+                state: JobState::Error,
+                started_at: 100,
+                ended_at: 200,
+            }
+        );
+
+        job.attempts.push(attempt);
+        assert_eq!(job.current_attempt(300).started_at, 200);
+
+        let job: Job = serde_json::from_str(
+            r#"{"_id": {"$oid": "66c9b6e0a5bd0e6b4d0a1e2f"}, "name": "a", "graph_json": "", "state": "Created", "manual_run": false, "created_at": 0, "updated_at": 0}"#,
+        )
+        .unwrap();
+        assert!(job.attempts.is_empty());
+    }
+
+    #[test]
+    fn test_set_options() {
+        let options = JobOptions {
+            priority: -1,
+            labels: HashMap::from([("team".to_string(), "a".to_string())]),
+            timeout: Some(60),
+            stall_timeout: Some(10),
+            timeout_action: TimeoutAction::Pause,
+        };
+        let job = Job {
+            owner: Some("alice".to_string()),
+            ..Default::default()
+        }
+        .set_options(&options);
+        assert_eq!(job.priority, -1);
+        assert_eq!(job.labels, options.labels);
+        assert_eq!(job.timeout, Some(60));
+        assert_eq!(job.stall_timeout, Some(10));
+        assert_eq!(job.timeout_action, TimeoutAction::Pause);
+        assert_eq!(job.owner.as_deref(), Some("alice"));
+
+        //schedules and triggers created before options existed
+        let options: JobOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options, JobOptions::default());
+    }
+
+    #[test]
+    fn test_redacted() {
+        let job = Job {
+            hooks: vec![
+                Hook::Webhook {
+                    url: "http://hook".to_string(),
+                    secret: Some("s3cret".to_string()),
+                },
+                Hook::Webhook {
+                    url: "http://hook2".to_string(),
+                    secret: None,
+                },
+            ],
+            ..Default::default()
+        };
+        let redacted = job.clone().redacted();
+        assert_eq!(
+            redacted.hooks[0],
+            Hook::Webhook {
+                url: "http://hook".to_string(),
+                secret: Some(REDACTED_SECRET.to_string()),
+            }
+        );
+        assert_eq!(redacted.hooks[1], job.hooks[1]);
+        assert!(!serde_json::to_string(&redacted).unwrap().contains("s3cret"));
+    }
+
+    #[test]
+    fn test_state_transition() {
+        assert!(JobState::Error.can_move_to(&JobState::Created));
+        assert!(!JobState::Clean.can_move_to(&JobState::Created));
+        assert!(!JobState::Finish.can_move_to(&JobState::Running));
+        assert_eq!(
+            JobState::prev_states(&JobState::Running),
+            vec![JobState::Deployed, JobState::Paused]
+        );
+        assert_eq!(
+            JobState::prev_states(&JobState::Created),
+            vec![JobState::Error]
+        );
+        assert_eq!(
+            JobState::prev_states(&JobState::Selected),
+            vec![JobState::Queued]
+        );
+        for state in JobState::ALL {
+            assert!(!state.can_move_to(&state));
+        }
+        assert!(JobState::Paused.has_job_db());
+        assert!(!JobState::Queued.has_job_db());
+        assert!(!JobState::Clean.has_job_db());
+
+        let err = InvalidTransition::new(JobState::Clean, JobState::Created);
+        assert!(err.allowed.is_empty());
+        assert_eq!(
+            serde_json::to_string(&InvalidTransition::new(JobState::Finish, JobState::Running))
+                .unwrap(),
+            r#"{"from":"Finish","to":"Running","allowed":["Clean"]}"#
+        );
+    }
+
+    #[test]
+    fn test_list_job_params() {
+        let params = actix_web::web::Query::<ListJobParams>::from_query(
+            "states=running,Queued&labels=team=a,env=dev&sort=updated_at&order=desc&limit=2",
+        )
+        .unwrap()
+        .into_inner();
+        assert_eq!(params.states, vec![JobState::Running, JobState::Queued]);
+        assert_eq!(params.labels, vec!["team=a", "env=dev"]);
+        assert_eq!(params.sort, JobSortField::UpdatedAt);
+        assert_eq!(params.order, SortOrder::Desc);
+        assert!(actix_web::web::Query::<ListJobParams>::from_query("states=unknown").is_err());
+
+        let jobs = vec![
+            Job {
+                updated_at: 10,
+                ..Default::default()
+            },
+            Job {
+                updated_at: 20,
+                ..Default::default()
+            },
+        ];
+        let cursor = params.next_cursor(&jobs).unwrap().unwrap();
+        assert_eq!(
+            JobCursor::decode(&cursor).unwrap(),
+            JobCursor {
+                value: Bson::Int64(20),
+                id: jobs[1].id,
+            }
+        );
+        assert!(params.next_cursor(&jobs[..1]).unwrap().is_none());
+        assert!(ListJobParams::default()
+            .next_cursor(&jobs)
+            .unwrap()
+            .is_none());
+    }
+}
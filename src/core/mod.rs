@@ -1,15 +1,27 @@
 mod cnode;
+mod notify;
 mod spec;
 
+mod health_models;
+mod hook_models;
 mod job_db_models;
+mod lease_models;
 mod main_db_models;
+mod schedule_models;
+mod trigger_models;
 
 pub use cnode::*;
+pub use notify::*;
 pub use spec::*;
 
 pub mod db {
     pub use super::{
+        health_models::*,
+        hook_models::*,
         job_db_models::*,
+        lease_models::*,
         main_db_models::*,
+        schedule_models::*,
+        trigger_models::*,
     };
 }
@@ -0,0 +1,101 @@
+use super::db::Direction;
+use mongodb::bson::oid::ObjectId;
+use std::future;
+use tokio::sync::broadcast::{
+    self,
+    error::RecvError,
+};
+
+/// Notification describe a change in database which someone may wait for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    /// state of node changed
+    NodeStateChanged { node_name: String },
+    /// new data batch is ready to be processed or sent
+    DataReady {
+        node_name: String,
+        direction: Direction,
+    },
+    /// job was created or its state changed
+    JobStateChanged { id: Option<ObjectId> },
+}
+
+/// Notifier fan out notifications in process. database repos publish their own writes here, and
+/// when mongo support change streams, changes made by other processes are published too.
+/// subscriber should always keep a polling fallback, notification may be lost when lagged.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    tx: broadcast::Sender<Notification>,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Notifier {
+            tx: broadcast::Sender::new(128),
+        }
+    }
+
+    pub fn notify(&self, notification: Notification) {
+        //no receiver is not an error
+        let _ = self.tx.send(notification);
+    }
+
+    pub fn subscribe(&self) -> Subscription {
+        Subscription {
+            rx: self.tx.subscribe(),
+        }
+    }
+}
+
+pub struct Subscription {
+    rx: broadcast::Receiver<Notification>,
+}
+
+impl Subscription {
+    /// wait return when a notification accepted by filter arrived or some notifications were
+    /// lost. never return if notifier was dropped, use it with a polling timer in select.
+    pub async fn wait(&mut self, filter: impl Fn(&Notification) -> bool) {
+        loop {
+            match self.rx.recv().await {
+                Ok(notification) if filter(&notification) => return,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(_)) => return,
+                Err(RecvError::Closed) => future::pending().await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_notifier() {
+        let notifier = Notifier::new();
+        notifier.notify(Notification::JobStateChanged { id: None });
+
+        let mut subscription = notifier.subscribe();
+        notifier.clone().notify(Notification::NodeStateChanged {
+            node_name: "a".to_string(),
+        });
+        notifier.notify(Notification::NodeStateChanged {
+            node_name: "b".to_string(),
+        });
+        subscription
+            .wait(|n| matches!(n, Notification::NodeStateChanged { node_name } if node_name == "b"))
+            .await;
+
+        let waiting = tokio::time::timeout(
+            std::time::Duration::from_millis(100),
+            subscription.wait(|_| true),
+        );
+        assert!(waiting.await.is_err());
+    }
+}
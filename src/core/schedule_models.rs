@@ -0,0 +1,237 @@
+use super::main_db_models::{
+    GetJobParams,
+    JobOptions,
+    ObjectIdSchema,
+};
+use crate::utils::StdIntoAnyhowResult;
+use anyhow::{
+    anyhow,
+    Result,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use handlebars::Handlebars;
+use mongodb::bson::oid::ObjectId;
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+};
+
+/// ConcurrencyPolicy decide what to do when a schedule is due but its last run is still active
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConcurrencyPolicy {
+    /// start a new run anyway
+    #[default]
+    Allow,
+    /// skip this run
+    Forbid,
+    /// cancel active runs and start a new one
+    Replace,
+}
+
+impl FromStr for ConcurrencyPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<ConcurrencyPolicy, Self::Err> {
+        match input {
+            "allow" => Ok(ConcurrencyPolicy::Allow),
+            "forbid" => Ok(ConcurrencyPolicy::Forbid),
+            "replace" => Ok(ConcurrencyPolicy::Replace),
+            _ => Err(anyhow!("unsupport concurrency policy {input}")),
+        }
+    }
+}
+
+/// Schedule create a job from graph_json every time it is due. graph_json may contain
+/// handlebars placeholders which are filled with parameters, run_name and schedule_name
+#[derive(Default, Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct Schedule {
+    #[serde(rename = "_id")]
+    #[schemars(with = "ObjectIdSchema")]
+    pub id: ObjectId,
+    pub name: String,
+    /// cron expression, five fields start from minute or six/seven fields start from second
+    pub cron: Option<String>,
+    /// seconds between two runs, used when cron is not set
+    pub interval: Option<u64>,
+    pub graph_json: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    #[serde(default)]
+    pub concurrency_policy: ConcurrencyPolicy,
+    /// priority, labels and timeouts of runs
+    #[serde(default)]
+    pub job_options: JobOptions,
+    #[serde(default)]
+    pub paused: bool,
+    pub next_run_at: i64,
+    pub last_run_at: Option<i64>,
+    /// authenticated caller created this schedule, owner of jobs it create
+    #[serde(default)]
+    pub created_by: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Schedule {
+    /// next_run_after return the first time later than after this schedule is due
+    pub fn next_run_after(&self, after: i64) -> Result<i64> {
+        match (self.cron.as_ref(), self.interval) {
+            (Some(cron), None) => {
+                //cron crate require second field
+                let expression = if cron.split_whitespace().count() == 5 {
+                    format!("0 {cron}")
+                } else {
+                    cron.clone()
+                };
+                let schedule = cron::Schedule::from_str(&expression)
+                    .map_err(|err| anyhow!("invalid cron expression {cron} {err}"))?;
+                let after = DateTime::from_timestamp(after, 0).ok_or(anyhow!("invalid time"))?;
+                schedule
+                    .after(&after)
+                    .next()
+                    .map(|next| next.timestamp())
+                    .ok_or(anyhow!("cron expression {cron} never fire again"))
+            }
+            (None, Some(interval)) if interval > 0 => Ok(after + interval as i64),
+            _ => Err(anyhow!("must set either cron or a positive interval")),
+        }
+    }
+
+    /// run_name name a run by schedule name and the time it was due
+    pub fn run_name(&self, run_at: i64) -> String {
+        timestamped_name(&self.name, run_at)
+    }
+
+    /// render_graph fill placeholders in graph_json, missing parameter is an error
+    pub fn render_graph(&self, run_name: &str) -> Result<String> {
+        let mut data = self.parameters.clone();
+        data.insert("run_name".to_string(), run_name.to_string());
+        data.insert("schedule_name".to_string(), self.name.clone());
+        render_graph_json(&self.graph_json, &data)
+            .map_err(|err| anyhow!("render graph of schedule {} {err}", self.name))
+    }
+}
+
+/// timestamped_name name a run by prefix and the time it started
+pub(crate) fn timestamped_name(prefix: &str, at: i64) -> String {
+    let at: DateTime<Utc> = DateTime::from_timestamp(at, 0).unwrap_or_default();
+    format!("{prefix}-{}", at.format("%Y%m%d%H%M%S"))
+}
+
+/// render_graph_json fill handlebars placeholders in graph_json without escaping
+pub(crate) fn render_graph_json(
+    graph_json: &str,
+    data: &HashMap<String, String>,
+) -> Result<String> {
+    let mut reg = Handlebars::new();
+    reg.set_strict_mode(true);
+    reg.register_escape_fn(handlebars::no_escape);
+    reg.render_template(graph_json, data).anyhow()
+}
+
+pub type GetScheduleParams = GetJobParams;
+
+pub trait ScheduleRepo {
+    fn insert_schedule(
+        &self,
+        schedule: &Schedule,
+    ) -> impl std::future::Future<Output = Result<Schedule>> + Send;
+
+    fn get_schedule(
+        &self,
+        params: &GetScheduleParams,
+    ) -> impl std::future::Future<Output = Result<Option<Schedule>>> + Send;
+
+    fn list_schedules(&self) -> impl std::future::Future<Output = Result<Vec<Schedule>>> + Send;
+
+    /// list_due_schedules list schedules not paused and due at now
+    fn list_due_schedules(
+        &self,
+        now: i64,
+    ) -> impl std::future::Future<Output = Result<Vec<Schedule>>> + Send;
+
+    /// advance_schedule move next run time from expected to next_run_at, return false if
+    /// schedule was advanced by others
+    fn advance_schedule(
+        &self,
+        id: &ObjectId,
+        expected: i64,
+        next_run_at: i64,
+        last_run_at: Option<i64>,
+    ) -> impl std::future::Future<Output = Result<bool>> + Send;
+
+    fn set_schedule_paused(
+        &self,
+        id: &ObjectId,
+        paused: bool,
+        next_run_at: i64,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+
+    fn delete_schedule(
+        &self,
+        id: &ObjectId,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_run_after() {
+        //2024-08-01 00:30:00 UTC
+        let now = 1722472200;
+        let mut schedule = Schedule {
+            name: "nightly".to_string(),
+            cron: Some("0 2 * * *".to_string()),
+            ..Default::default()
+        };
+        //2024-08-01 02:00:00 UTC
+        assert_eq!(schedule.next_run_after(now).unwrap(), 1722477600);
+
+        schedule.cron = Some("30 0 2 * * * *".to_string());
+        assert_eq!(schedule.next_run_after(now).unwrap(), 1722477630);
+
+        schedule.cron = None;
+        schedule.interval = Some(600);
+        assert_eq!(schedule.next_run_after(now).unwrap(), now + 600);
+
+        schedule.interval = None;
+        assert!(schedule.next_run_after(now).is_err());
+        schedule.cron = Some("not a cron".to_string());
+        assert!(schedule.next_run_after(now).is_err());
+    }
+
+    #[test]
+    fn test_render_graph() {
+        let mut schedule = Schedule {
+            name: "nightly".to_string(),
+            graph_json: r#"{"name": "{{run_name}}", "args": ["--count={{count}}"]}"#.to_string(),
+            ..Default::default()
+        };
+        let run_name = schedule.run_name(1722477600);
+        assert_eq!(run_name, "nightly-20240801020000");
+        assert!(schedule.render_graph(&run_name).is_err());
+
+        schedule
+            .parameters
+            .insert("count".to_string(), "<10>".to_string());
+        assert_eq!(
+            schedule.render_graph(&run_name).unwrap(),
+            r#"{"name": "nightly-20240801020000", "args": ["--count=<10>"]}"#
+        );
+        assert_eq!(
+            "replace".parse::<ConcurrencyPolicy>().unwrap(),
+            ConcurrencyPolicy::Replace
+        );
+    }
+}
@@ -12,6 +12,28 @@ pub enum CacheType {
     Memory,
 }
 
+/// RunMode decide how a node is deployed in cluster
+/// stream node keep running until the job is cleaned, finite node exit after its work is done
+#[derive(Serialize, PartialEq, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RunMode {
+    #[default]
+    Stream,
+    Finite,
+}
+
+impl FromStr for RunMode {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<RunMode, Self::Err> {
+        match input {
+            "stream" => Ok(RunMode::Stream),
+            "finite" => Ok(RunMode::Finite),
+            _ => Err(anyhow!("unsupport run mode {input}")),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 
 pub enum AccessMode {
@@ -31,6 +53,28 @@ impl FromStr for AccessMode {
     }
 }
 
+/// PullPolicy is imagePullPolicy of containers in node pods
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum PullPolicy {
+    Always,
+    #[default]
+    IfNotPresent,
+    Never,
+}
+
+impl FromStr for PullPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<PullPolicy, Self::Err> {
+        match input {
+            "Always" => Ok(PullPolicy::Always),
+            "IfNotPresent" => Ok(PullPolicy::IfNotPresent),
+            "Never" => Ok(PullPolicy::Never),
+            _ => Err(anyhow!("unsupport pull policy {input}")),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StorageOptions {
     pub class_name: Option<String>,
@@ -49,6 +93,16 @@ impl Default for StorageOptions {
     }
 }
 
+/// ExternalEndpoint is a destination outside of the pipeline which node is allowed to access
+/// when network isolation is enabled, empty ports means all ports
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExternalEndpoint {
+    pub cidr: String,
+
+    #[serde(default)]
+    pub ports: Vec<u16>,
+}
+
 // MachineSpec container information for deploy and running in cloud
 #[derive(Serialize, Default, Deserialize, Debug, Clone)]
 pub struct MachineSpec {
@@ -69,6 +123,20 @@ pub struct MachineSpec {
 
     #[serde(default)]
     pub storage: StorageOptions,
+
+    #[serde(default)]
+    pub run_mode: RunMode,
+
+    #[serde(default)]
+    pub external_endpoints: Vec<ExternalEndpoint>,
+
+    /// seconds node may run after job started before it is expired
+    #[serde(default)]
+    pub timeout: Option<u64>,
+
+    /// seconds node may make no progress while it has data to process, override the one of job
+    #[serde(default)]
+    pub stall_timeout: Option<u64>,
 }
 
 fn default_replicas() -> u32 {
@@ -86,5 +154,20 @@ mod tests {
         assert_eq!(spec.cache_type, CacheType::Disk);
         assert!(spec.storage.class_name.is_none());
         assert!(spec.storage.capacity.is_none());
+        assert_eq!(spec.run_mode, RunMode::Stream);
+
+        let spec: MachineSpec = serde_json::from_str(r#"{"run_mode": "finite"}"#).unwrap();
+        assert_eq!(spec.run_mode, RunMode::Finite);
+        assert!(spec.external_endpoints.is_empty());
+
+        let spec: MachineSpec =
+            serde_json::from_str(r#"{"external_endpoints": [{"cidr": "10.1.0.0/16"}]}"#).unwrap();
+        assert_eq!(
+            spec.external_endpoints,
+            vec![ExternalEndpoint {
+                cidr: "10.1.0.0/16".to_string(),
+                ports: vec![],
+            }]
+        );
     }
 }
@@ -0,0 +1,220 @@
+use super::{
+    main_db_models::{
+        GetJobParams,
+        JobOptions,
+        ObjectIdSchema,
+    },
+    schedule_models::render_graph_json,
+};
+use anyhow::{
+    anyhow,
+    Result,
+};
+use mongodb::bson::oid::ObjectId;
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::HashMap;
+
+fn default_debounce() -> u64 {
+    60
+}
+
+/// TriggerCursor is the polling progress of a trigger
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct TriggerCursor {
+    /// head commit of branch which was handled last time
+    pub last_seen_commit: Option<String>,
+    /// new head commit waiting for the branch to settle down
+    pub pending_commit: Option<String>,
+    /// unix time pending commit was first seen
+    pub pending_since: Option<i64>,
+}
+
+/// length of commit hash in run name
+pub const SHORT_HASH_LEN: usize = 12;
+
+/// Trigger watch a branch of jiaozifs repository and create a job from graph_json for every new
+/// head commit. graph_json may contain handlebars placeholders which are filled with parameters,
+/// run_name, trigger_name and commit_hash
+#[derive(Default, Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct Trigger {
+    #[serde(rename = "_id")]
+    #[schemars(with = "ObjectIdSchema")]
+    pub id: ObjectId,
+    pub name: String,
+    pub owner: String,
+    pub repo: String,
+    pub branch: String,
+    /// only start a job if changes between two heads touch this path
+    pub path: Option<String>,
+    pub graph_json: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    /// seconds head must stay unchanged before a job is started, rapid pushes start one job
+    #[serde(default = "default_debounce")]
+    pub debounce: u64,
+    /// priority, labels and timeouts of runs
+    #[serde(default)]
+    pub job_options: JobOptions,
+    #[serde(default)]
+    pub cursor: TriggerCursor,
+    pub last_run_at: Option<i64>,
+    /// authenticated caller created this trigger, owner of jobs it create
+    #[serde(default)]
+    pub created_by: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Trigger {
+    /// observe compute the cursor after head commit of branch was seen at now, return the
+    /// commit to run once head stay unchanged for debounce seconds. first observation only
+    /// record head, commits before trigger created are not run
+    pub fn observe(&self, head: &str, now: i64) -> (TriggerCursor, Option<String>) {
+        let cursor = &self.cursor;
+        let settled = TriggerCursor {
+            last_seen_commit: Some(head.to_string()),
+            ..Default::default()
+        };
+        match cursor.last_seen_commit.as_deref() {
+            None => return (settled, None),
+            Some(last_seen) if last_seen == head => return (settled, None),
+            _ => {}
+        }
+
+        let pending_since = match (cursor.pending_commit.as_deref(), cursor.pending_since) {
+            (Some(pending), Some(since)) if pending == head => since,
+            _ => now,
+        };
+        if now - pending_since >= self.debounce as i64 {
+            return (settled, Some(head.to_string()));
+        }
+
+        let waiting = TriggerCursor {
+            last_seen_commit: cursor.last_seen_commit.clone(),
+            pending_commit: Some(head.to_string()),
+            pending_since: Some(pending_since),
+        };
+        (waiting, None)
+    }
+
+    /// run_name name a run by trigger name and the commit it runs at, so a commit is run once
+    pub fn run_name(&self, commit_hash: &str) -> String {
+        let short_hash: String = commit_hash
+            .chars()
+            .take(SHORT_HASH_LEN)
+            .collect::<String>()
+            .to_ascii_lowercase();
+        format!("{}-{short_hash}", self.name)
+    }
+
+    /// render_graph fill placeholders in graph_json, missing parameter is an error
+    pub fn render_graph(&self, run_name: &str, commit_hash: &str) -> Result<String> {
+        let mut data = self.parameters.clone();
+        data.insert("run_name".to_string(), run_name.to_string());
+        data.insert("trigger_name".to_string(), self.name.clone());
+        data.insert("commit_hash".to_string(), commit_hash.to_string());
+        render_graph_json(&self.graph_json, &data)
+            .map_err(|err| anyhow!("render graph of trigger {} {err}", self.name))
+    }
+}
+
+pub type GetTriggerParams = GetJobParams;
+
+pub trait TriggerRepo {
+    fn insert_trigger(
+        &self,
+        trigger: &Trigger,
+    ) -> impl std::future::Future<Output = Result<Trigger>> + Send;
+
+    fn get_trigger(
+        &self,
+        params: &GetTriggerParams,
+    ) -> impl std::future::Future<Output = Result<Option<Trigger>>> + Send;
+
+    fn list_triggers(&self) -> impl std::future::Future<Output = Result<Vec<Trigger>>> + Send;
+
+    /// update_trigger_cursor move cursor from expected to cursor, return false if trigger was
+    /// updated by others
+    fn update_trigger_cursor(
+        &self,
+        id: &ObjectId,
+        expected: &TriggerCursor,
+        cursor: &TriggerCursor,
+        last_run_at: Option<i64>,
+    ) -> impl std::future::Future<Output = Result<bool>> + Send;
+
+    fn delete_trigger(&self, id: &ObjectId)
+        -> impl std::future::Future<Output = Result<()>> + Send;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_observe() {
+        let mut trigger = Trigger {
+            name: "on-main".to_string(),
+            debounce: 30,
+            ..Default::default()
+        };
+
+        //first head is only recorded
+        let (cursor, run) = trigger.observe("a", 100);
+        assert_eq!(cursor.last_seen_commit.as_deref(), Some("a"));
+        assert!(run.is_none());
+        trigger.cursor = cursor;
+
+        //new head wait for debounce
+        let (cursor, run) = trigger.observe("b", 110);
+        assert_eq!(cursor.pending_commit.as_deref(), Some("b"));
+        assert_eq!(cursor.pending_since, Some(110));
+        assert!(run.is_none());
+        trigger.cursor = cursor;
+
+        //another push restart debounce
+        let (cursor, run) = trigger.observe("c", 130);
+        assert_eq!(cursor.last_seen_commit.as_deref(), Some("a"));
+        assert_eq!(cursor.pending_since, Some(130));
+        assert!(run.is_none());
+        trigger.cursor = cursor;
+
+        let (cursor, run) = trigger.observe("c", 160);
+        assert_eq!(
+            cursor,
+            TriggerCursor {
+                last_seen_commit: Some("c".to_string()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(run.as_deref(), Some("c"));
+        trigger.cursor = cursor;
+
+        let (_, run) = trigger.observe("c", 200);
+        assert!(run.is_none());
+
+        trigger.debounce = 0;
+        let (_, run) = trigger.observe("d", 200);
+        assert_eq!(run.as_deref(), Some("d"));
+    }
+
+    #[test]
+    fn test_render_graph() {
+        let trigger = Trigger {
+            name: "on-main".to_string(),
+            graph_json: r#"{"name": "{{run_name}}", "args": ["--ref={{commit_hash}}"]}"#
+                .to_string(),
+            ..Default::default()
+        };
+        let commit_hash = "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B";
+        let run_name = trigger.run_name(commit_hash);
+        assert_eq!(
+            trigger.render_graph(&run_name, commit_hash).unwrap(),
+            r#"{"name": "on-main-9f86d081884c", "args": ["--ref=9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B"]}"#
+        );
+    }
+}
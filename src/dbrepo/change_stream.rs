@@ -0,0 +1,251 @@
+use crate::core::{
+    db::{
+        DataState,
+        Direction,
+    },
+    Notification,
+    Notifier,
+};
+use anyhow::Result;
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{
+        doc,
+        Document,
+    },
+    change_stream::event::{
+        ChangeStreamEvent,
+        OperationType,
+    },
+    error::{
+        Error,
+        ErrorKind,
+    },
+    options::FullDocumentType,
+    Database,
+};
+use serde_variant::to_variant_name;
+use std::time::Duration;
+use tokio::{
+    select,
+    time::sleep,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{
+    info,
+    warn,
+};
+
+/// error code returned by standalone mongo which dont support change streams
+const CHANGE_STREAM_NOT_SUPPORTED: i32 = 40573;
+
+pub(crate) enum WatchTarget {
+    Database(Database),
+}
+
+fn is_change_stream_unsupported(err: &Error) -> bool {
+    matches!(*err.kind, ErrorKind::Command(ref command_error) if command_error.code == CHANGE_STREAM_NOT_SUPPORTED)
+}
+
+/// to_notification convert change event of node/data/job collection to notification
+pub(crate) fn to_notification(event: &ChangeStreamEvent<Document>) -> Option<Notification> {
+    let coll = event.ns.as_ref()?.coll.as_ref()?;
+    match coll.as_str() {
+        super::NODE_COL_NAME => {
+            let node_name = event.full_document.as_ref()?.get_str("node_name").ok()?;
+            Some(Notification::NodeStateChanged {
+                node_name: node_name.to_string(),
+            })
+        }
+        super::DATA_COL_NAME => {
+            let doc = event.full_document.as_ref()?;
+            let state = doc.get_str("state").ok()?;
+            if state != to_variant_name(&DataState::Received).ok()?
+                && state != to_variant_name(&DataState::PartialSent).ok()?
+            {
+                return None;
+            }
+            let direction = match doc.get_str("direction").ok()? {
+                val if val == to_variant_name(&Direction::In).ok()? => Direction::In,
+                _ => Direction::Out,
+            };
+            Some(Notification::DataReady {
+                node_name: doc.get_str("node_name").ok()?.to_string(),
+                direction,
+            })
+        }
+        super::JOB_COL_NAME => {
+            //backend rewrite other fields of jobs often, waking on them would loop passes
+            if let Some(update) = event.update_description.as_ref() {
+                if !update.updated_fields.contains_key("state") {
+                    return None;
+                }
+            }
+            let id = event
+                .document_key
+                .as_ref()
+                .and_then(|key| key.get_object_id("_id").ok());
+            Some(Notification::JobStateChanged { id })
+        }
+        _ => None,
+    }
+}
+
+/// watch_changes publish changes of collections to notifier until token was cancelled.
+/// return immediately if mongo dont support change streams, caller fallback to polling.
+pub(crate) async fn watch_changes(
+    target: WatchTarget,
+    collections: &[&str],
+    notifier: Notifier,
+    token: CancellationToken,
+) -> Result<()> {
+    let pipeline = vec![doc! {
+        "$match": {
+            "ns.coll": {"$in": collections},
+            "operationType": {"$in": ["insert", "update", "replace"]},
+        }
+    }];
+
+    loop {
+        let stream = match &target {
+            WatchTarget::Database(database) => {
+                database
+                    .watch()
+                    .pipeline(pipeline.clone())
+                    .full_document(FullDocumentType::UpdateLookup)
+                    .await
+            }
+        };
+
+        match stream {
+            Ok(mut stream) => {
+                info!("watch changes of {:?}", collections);
+                loop {
+                    select! {
+                        _ = token.cancelled() => {
+                            return Ok(());
+                        }
+                        event = stream.try_next() => {
+                            match event {
+                                Ok(Some(event)) => {
+                                    if matches!(event.operation_type, OperationType::Invalidate) {
+                                        break;
+                                    }
+                                    if let Some(notification) = to_notification(&event) {
+                                        notifier.notify(notification);
+                                    }
+                                }
+                                Ok(None) => break,
+                                Err(err) => {
+                                    warn!("change stream interrupted {err}");
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Err(err) if is_change_stream_unsupported(&err) => {
+                info!("change streams not supported, fallback to polling");
+                return Ok(());
+            }
+            Err(err) => warn!("open change stream {err}"),
+        }
+
+        select! {
+            _ = token.cancelled() => {
+                return Ok(());
+            }
+            _ = sleep(Duration::from_secs(5)) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{
+        from_document,
+        oid::ObjectId,
+    };
+
+    #[test]
+    fn test_to_notification() {
+        let event: ChangeStreamEvent<Document> = from_document(doc! {
+            "_id": {"_data": "token"},
+            "operationType": "insert",
+            "ns": {"db": "job-a", "coll": "data"},
+            "fullDocument": {"node_name": "copy", "direction": "In", "state": "Received"},
+        })
+        .unwrap();
+        assert_eq!(
+            to_notification(&event),
+            Some(Notification::DataReady {
+                node_name: "copy".to_string(),
+                direction: Direction::In,
+            })
+        );
+
+        let event: ChangeStreamEvent<Document> = from_document(doc! {
+            "_id": {"_data": "token"},
+            "operationType": "update",
+            "ns": {"db": "job-a", "coll": "data"},
+            "fullDocument": {"node_name": "copy", "direction": "In", "state": "Processed"},
+        })
+        .unwrap();
+        assert_eq!(to_notification(&event), None);
+
+        let event: ChangeStreamEvent<Document> = from_document(doc! {
+            "_id": {"_data": "token"},
+            "operationType": "update",
+            "ns": {"db": "job-a", "coll": "node"},
+            "fullDocument": {"node_name": "copy", "state": "Finish"},
+        })
+        .unwrap();
+        assert_eq!(
+            to_notification(&event),
+            Some(Notification::NodeStateChanged {
+                node_name: "copy".to_string(),
+            })
+        );
+
+        let id = ObjectId::new();
+        let event: ChangeStreamEvent<Document> = from_document(doc! {
+            "_id": {"_data": "token"},
+            "operationType": "update",
+            "ns": {"db": "jiaoziflow", "coll": "job"},
+            "documentKey": {"_id": id},
+        })
+        .unwrap();
+        assert_eq!(
+            to_notification(&event),
+            Some(Notification::JobStateChanged { id: Some(id) })
+        );
+
+        let event: ChangeStreamEvent<Document> = from_document(doc! {
+            "_id": {"_data": "token"},
+            "operationType": "update",
+            "ns": {"db": "jiaoziflow", "coll": "job"},
+            "documentKey": {"_id": id},
+            "updateDescription": {"updatedFields": {"reason": "stalled"}, "removedFields": []},
+        })
+        .unwrap();
+        assert_eq!(to_notification(&event), None);
+
+        let event: ChangeStreamEvent<Document> = from_document(doc! {
+            "_id": {"_data": "token"},
+            "operationType": "update",
+            "ns": {"db": "jiaoziflow", "coll": "job"},
+            "documentKey": {"_id": id},
+            "updateDescription": {
+                "updatedFields": {"state": "Running", "updated_at": 1},
+                "removedFields": [],
+            },
+        })
+        .unwrap();
+        assert_eq!(
+            to_notification(&event),
+            Some(Notification::JobStateChanged { id: Some(id) })
+        );
+    }
+}
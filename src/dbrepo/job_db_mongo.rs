@@ -1,14 +1,27 @@
+use super::change_stream::{
+    watch_changes,
+    WatchTarget,
+};
 use crate::{
-    core::db::{
-        DataRecord,
-        DataRepo,
-        DataState,
-        Direction,
-        Graph,
-        GraphRepo,
-        Node,
-        NodeRepo,
-        TrackerState,
+    core::{
+        db::{
+            DataList,
+            DataRecord,
+            DataRepo,
+            DataState,
+            DataStateCount,
+            Direction,
+            Graph,
+            GraphRepo,
+            ListDataParams,
+            Node,
+            NodeDataSummary,
+            NodeRepo,
+            PingRepo,
+            TrackerState,
+        },
+        Notification,
+        Notifier,
     },
     utils::StdIntoAnyhowResult,
 };
@@ -24,28 +37,36 @@ use futures::TryStreamExt;
 use mongodb::{
     bson::{
         doc,
+        from_document,
+        oid::ObjectId,
         Document,
     },
     error::ErrorKind,
     options::{
         ClientOptions,
+        FindOptions,
         IndexOptions,
     },
     Client,
     Collection,
+    Database,
     IndexModel,
 };
+use serde::Deserialize;
 use serde_variant::to_variant_name;
+use tokio_util::sync::CancellationToken;
 
 const GRAPH_COL_NAME: &str = "graph";
-const NODE_COL_NAME: &str = "node";
-const DATA_COL_NAME: &str = "data";
+pub(crate) const NODE_COL_NAME: &str = "node";
+pub(crate) const DATA_COL_NAME: &str = "data";
 
 #[derive(Clone)]
 pub struct MongoRunDbRepo {
+    database: Database,
     graph_col: Collection<Graph>,
     node_col: Collection<Node>,
     data_col: Collection<DataRecord>,
+    notifier: Notifier,
 }
 
 impl MongoRunDbRepo {
@@ -57,10 +78,7 @@ impl MongoRunDbRepo {
             .expect("set db name in url")
             .clone();
         let client = Client::with_options(options)?;
-        let database = client.database(database.as_str());
-        let graph_col: Collection<Graph> = database.collection(GRAPH_COL_NAME);
-        let node_col: Collection<Node> = database.collection(NODE_COL_NAME);
-        let data_col: Collection<DataRecord> = database.collection(DATA_COL_NAME);
+        let repo = Self::open(&client, &database);
 
         async fn create_index<T>(
             collection: &Collection<T>,
@@ -89,7 +107,7 @@ impl MongoRunDbRepo {
 
         // Create index for nodes
         create_index(
-            &node_col,
+            &repo.node_col,
             doc! { "node_name": 1 },
             "idx_node_name_unique",
             true,
@@ -97,10 +115,16 @@ impl MongoRunDbRepo {
         .await?;
 
         // Create index for data
-        create_index(&data_col, doc! { "created_at": 1 }, "idx_created_at", false).await?;
+        create_index(
+            &repo.data_col,
+            doc! { "created_at": 1 },
+            "idx_created_at",
+            false,
+        )
+        .await?;
 
         create_index(
-            &data_col,
+            &repo.data_col,
             doc! { "node_name": 1, "state": 1, "direction": 1 },
             "idx_node_name_state_direction",
             false,
@@ -108,7 +132,15 @@ impl MongoRunDbRepo {
         .await?;
 
         create_index(
-            &data_col,
+            &repo.data_col,
+            doc! { "node_name": 1, "updated_at": -1 },
+            "idx_node_name_updated_at",
+            false,
+        )
+        .await?;
+
+        create_index(
+            &repo.data_col,
             doc! { "node_name": 1, "id": 1, "direction": 1 },
             "idx_node_name_id_direction",
             false,
@@ -116,18 +148,48 @@ impl MongoRunDbRepo {
         .await?;
 
         create_index(
-            &data_col,
+            &repo.data_col,
             doc! { "node_name": 1, "id": 1, "direction": 1, "data.is_transparent_data": 1 },
             "idx_node_name_id_direction_transparent_data",
             false,
         )
         .await?;
 
-        Ok(MongoRunDbRepo {
-            graph_col,
-            node_col,
-            data_col,
-        })
+        //history of a batch across nodes
+        create_index(&repo.data_col, doc! { "id": 1 }, "idx_id", false).await?;
+
+        Ok(repo)
+    }
+
+    /// open use client to access a job database without creating indexes, so reading a dropped
+    /// database dont create it again
+    pub fn open(client: &Client, database: &str) -> Self {
+        let database = client.database(database);
+        MongoRunDbRepo {
+            graph_col: database.collection(GRAPH_COL_NAME),
+            node_col: database.collection(NODE_COL_NAME),
+            data_col: database.collection(DATA_COL_NAME),
+            database,
+            notifier: Notifier::new(),
+        }
+    }
+
+    /// notifier publish changes of node and data made by this repo, and changes made by others
+    /// while watch is running
+    pub fn notifier(&self) -> &Notifier {
+        &self.notifier
+    }
+
+    /// watch publish changes of node and data collections until token cancelled, return
+    /// immediately if mongo dont support change streams
+    pub async fn watch(&self, token: CancellationToken) -> Result<()> {
+        watch_changes(
+            WatchTarget::Database(self.database.clone()),
+            &[NODE_COL_NAME, DATA_COL_NAME],
+            self.notifier.clone(),
+            token,
+        )
+        .await
     }
 
     pub async fn drop(connectstring: &str) -> Result<()> {
@@ -142,6 +204,13 @@ impl MongoRunDbRepo {
     }
 }
 
+impl PingRepo for MongoRunDbRepo {
+    async fn ping(&self) -> Result<()> {
+        self.database.run_command(doc! {"ping": 1}).await?;
+        Ok(())
+    }
+}
+
 impl GraphRepo for MongoRunDbRepo {
     async fn insert_global_state(&self, graph: &Graph) -> Result<()> {
         self.graph_col.insert_one(graph).await.map(|_| ()).anyhow()
@@ -170,18 +239,33 @@ impl NodeRepo for MongoRunDbRepo {
     }
 
     async fn update_node_by_name(&self, name: &str, state: TrackerState) -> Result<()> {
-        let update = doc! {
-            "$set": {
-                "state": to_variant_name(&state)?,
-                "updated_at":Utc::now().timestamp(),
-            },
+        let now = Utc::now().timestamp();
+        let mut update_fields = doc! {
+            "state": to_variant_name(&state)?,
+            "updated_at": now,
         };
+        if state == TrackerState::Finish {
+            update_fields.insert("finished_at", now);
+        }
 
         self.node_col
-            .update_one(doc! {"node_name":name}, update)
+            .update_one(doc! {"node_name":name}, doc! {"$set": update_fields})
             .await
-            .map(|_| ())
-            .anyhow()
+            .anyhow()?;
+        //keep the first time node became ready
+        if state == TrackerState::Ready {
+            self.node_col
+                .update_one(
+                    doc! {"node_name":name, "ready_at": null},
+                    doc! {"$set": {"ready_at": now}},
+                )
+                .await
+                .anyhow()?;
+        }
+        self.notifier.notify(Notification::NodeStateChanged {
+            node_name: name.to_string(),
+        });
+        Ok(())
     }
 
     async fn mark_incoming_finish(&self, name: &str) -> Result<()> {
@@ -204,7 +288,28 @@ impl NodeRepo for MongoRunDbRepo {
                 update,
             )
             .await
-            .map(|_| ())
+            .anyhow()?;
+        self.notifier.notify(Notification::NodeStateChanged {
+            node_name: name.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn reset_unfinished_nodes(&self) -> Result<u64> {
+        let update = doc! {
+            "$set": {
+                "state": to_variant_name(&TrackerState::Init)?,
+                "updated_at":Utc::now().timestamp(),
+            },
+        };
+
+        self.node_col
+            .update_many(
+                doc! {"state": {"$ne": to_variant_name(&TrackerState::Finish)?}},
+                update,
+            )
+            .await
+            .map(|r| r.modified_count)
             .anyhow()
     }
 
@@ -272,6 +377,38 @@ impl DataRepo for MongoRunDbRepo {
             },
         };
 
+        let count = self
+            .data_col
+            .update_many(query, update)
+            .await
+            .map(|r| r.modified_count)
+            .anyhow()?;
+        if count > 0 {
+            self.notifier.notify(Notification::DataReady {
+                node_name: node_name.to_string(),
+                direction: direction.clone(),
+            });
+        }
+        Ok(count)
+    }
+
+    async fn revert_in_flight(&self) -> Result<u64> {
+        let update = doc! {
+            "$set": {
+                "state": to_variant_name(&DataState::Received)?,
+                "updated_at":Utc::now().timestamp(),
+            }
+        };
+
+        let query = doc! {
+            "state": {
+                "$in": [
+                    to_variant_name(&DataState::Assigned)?,
+                    to_variant_name(&DataState::SelectForSend)?,
+                ]
+            },
+        };
+
         self.data_col
             .update_many(query, update)
             .await
@@ -293,19 +430,148 @@ impl DataRepo for MongoRunDbRepo {
             .anyhow()
     }
 
+    async fn last_updated_at(&self, node_name: &str) -> Result<Option<i64>> {
+        self.data_col
+            .find_one(doc! {"node_name": node_name})
+            .sort(doc! {"updated_at": -1})
+            .await
+            .map(|record| record.map(|record| record.updated_at))
+            .anyhow()
+    }
+
+    async fn list_by_node_name(&self, node_name: &str) -> Result<Vec<DataRecord>> {
+        self.data_col
+            .find(doc! {"node_name":node_name})
+            .await?
+            .try_collect()
+            .await
+            .anyhow()
+    }
+
     async fn list_by_node_name_and_state(
         &self,
         node_name: &str,
-        state: &DataState,
-    ) -> Result<Vec<DataRecord>> {
+        params: &ListDataParams,
+    ) -> Result<DataList> {
+        let mut query = doc! {"node_name":node_name};
+        if let Some(direction) = params.direction.as_ref() {
+            query.insert("direction", to_variant_name(direction)?);
+        }
+        if let Some(state) = params.state.as_ref() {
+            query.insert("state", to_variant_name(state)?);
+        }
+        if let Some(machine) = params.machine.as_ref() {
+            query.insert("machine", machine);
+        }
+        if let Some(priority) = params.priority {
+            query.insert("priority", priority as i32);
+        }
+        for (field, after, before) in [
+            ("created_at", params.created_after, params.created_before),
+            ("updated_at", params.updated_after, params.updated_before),
+        ] {
+            let mut range = doc! {};
+            if let Some(after) = after {
+                range.insert("$gte", after);
+            }
+            if let Some(before) = before {
+                range.insert("$lt", before);
+            }
+            if !range.is_empty() {
+                query.insert(field, range);
+            }
+        }
+        let total = self.data_col.count_documents(query.clone()).await?;
+
+        //records have no id of their own, so pages are split by _id in insertion order
+        if let Some(cursor) = params.cursor.as_ref() {
+            let cursor =
+                ObjectId::parse_str(cursor).map_err(|err| anyhow!("invalid cursor {err}"))?;
+            query.insert("_id", doc! {"$gt": cursor});
+        }
+        let options = FindOptions::builder()
+            .sort(doc! {"_id": 1})
+            .limit(params.limit.map(|limit| limit as i64))
+            .build();
+        let documents: Vec<Document> = self
+            .data_col
+            .clone_with_type::<Document>()
+            .find(query)
+            .with_options(options)
+            .await?
+            .try_collect()
+            .await?;
+
+        let next_cursor = match (params.limit, documents.last()) {
+            (Some(limit), Some(last)) if documents.len() as u64 >= limit => {
+                Some(last.get_object_id("_id")?.to_hex())
+            }
+            _ => None,
+        };
+        let records = documents
+            .into_iter()
+            .map(from_document)
+            .collect::<Result<Vec<DataRecord>, _>>()?;
+        Ok(DataList {
+            records,
+            total,
+            next_cursor,
+        })
+    }
+
+    async fn list_by_id(&self, id: &str) -> Result<Vec<DataRecord>> {
         self.data_col
-            .find(doc! {"node_name":node_name, "state": to_variant_name(&state)?})
+            .find(doc! {"id": id})
+            .sort(doc! {"created_at": 1, "_id": 1})
             .await?
             .try_collect()
             .await
             .anyhow()
     }
 
+    async fn summarize_by_node(&self) -> Result<Vec<NodeDataSummary>> {
+        #[derive(Deserialize)]
+        struct Group {
+            node_name: String,
+            direction: Direction,
+            state: DataState,
+        }
+
+        #[derive(Deserialize)]
+        struct GroupCount {
+            _id: Group,
+            count: i64,
+        }
+
+        let pipeline = vec![
+            doc! {"$group": {
+                "_id": {"node_name": "$node_name", "direction": "$direction", "state": "$state"},
+                "count": {"$sum": 1},
+            }},
+            doc! {"$sort": {"_id.node_name": 1, "_id.direction": 1, "_id.state": 1}},
+        ];
+        let mut cursor = self.data_col.aggregate(pipeline).await?;
+        let mut summaries: Vec<NodeDataSummary> = vec![];
+        while let Some(document) = cursor.try_next().await? {
+            let group: GroupCount = from_document(document)?;
+            let count = DataStateCount {
+                direction: group._id.direction,
+                state: group._id.state,
+                count: group.count as u64,
+            };
+            match summaries.last_mut() {
+                Some(summary) if summary.node_name == group._id.node_name => {
+                    summary.counts.push(count)
+                }
+                _ => summaries.push(NodeDataSummary {
+                    node_name: group._id.node_name,
+                    counts: vec![count],
+                }),
+            }
+        }
+        Ok(summaries)
+    }
+
     async fn count(
         &self,
         node_name: &str,
@@ -356,6 +622,13 @@ impl DataRepo for MongoRunDbRepo {
     }
 
     async fn insert_new_path(&self, record: &DataRecord) -> Result<()> {
-        self.data_col.insert_one(record).await.map(|_| ()).anyhow()
+        self.data_col.insert_one(record).await.anyhow()?;
+        if record.state == DataState::Received {
+            self.notifier.notify(Notification::DataReady {
+                node_name: record.node_name.clone(),
+                direction: record.direction.clone(),
+            });
+        }
+        Ok(())
     }
 }
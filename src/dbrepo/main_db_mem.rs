@@ -0,0 +1,255 @@
+use crate::core::db::{
+    GetJobParams,
+    GetScheduleParams,
+    GetTriggerParams,
+    HookDelivery,
+    HookDeliveryUpdateInfo,
+    HookRepo,
+    InvalidTransition,
+    Job,
+    JobAttempt,
+    JobRepo,
+    JobState,
+    JobUpdateInfo,
+    LeaseRepo,
+    ListJobParams,
+    PingRepo,
+    RunSummary,
+    Schedule,
+    ScheduleRepo,
+    Trigger,
+    TriggerCursor,
+    TriggerRepo,
+};
+use anyhow::{
+    anyhow,
+    Result,
+};
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+/// MemMainDbRepo keep jobs in memory for api tests, other collections are not supported
+#[derive(Clone, Default)]
+pub struct MemMainDbRepo {
+    jobs: Arc<Mutex<Vec<Job>>>,
+}
+
+fn unsupported<T>() -> Result<T> {
+    Err(anyhow!("not supported by memory repo"))
+}
+
+impl MemMainDbRepo {
+    pub fn jobs(&self) -> Vec<Job> {
+        self.jobs.lock().unwrap().clone()
+    }
+}
+
+impl JobRepo for MemMainDbRepo {
+    async fn insert(&self, job: &Job) -> Result<Job> {
+        self.jobs.lock().unwrap().push(job.clone());
+        Ok(job.clone())
+    }
+
+    async fn get(&self, params: &GetJobParams) -> Result<Option<Job>> {
+        Ok(self
+            .jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|job| {
+                params.id.map_or(true, |id| job.id == id)
+                    && params.name.as_ref().map_or(true, |name| &job.name == name)
+            })
+            .cloned())
+    }
+
+    async fn delete(&self, id: &ObjectId) -> Result<()> {
+        self.jobs.lock().unwrap().retain(|job| &job.id != id);
+        Ok(())
+    }
+
+    async fn queue_created_jobs(&self) -> Result<u64> {
+        unsupported()
+    }
+
+    async fn select_job(&self, _id: &ObjectId, _holder: &str) -> Result<Option<Job>> {
+        unsupported()
+    }
+
+    async fn set_holder(&self, _id: &ObjectId, _holder: &str) -> Result<()> {
+        unsupported()
+    }
+
+    async fn update(&self, id: &ObjectId, info: &JobUpdateInfo) -> Result<()> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs
+            .iter_mut()
+            .find(|job| &job.id == id)
+            .ok_or_else(|| anyhow!("job {id} not found"))?;
+        if let Some(state) = info.state.as_ref() {
+            if !job.state.can_move_to(state) {
+                return Err(InvalidTransition::new(job.state.clone(), state.clone()).into());
+            }
+            job.state = state.clone();
+        }
+        if let Some(started_at) = info.started_at {
+            job.started_at = Some(started_at);
+        }
+        if let Some(active_secs) = info.active_secs {
+            job.active_secs = active_secs;
+        }
+        if let Some(reason) = info.reason.as_ref() {
+            job.reason = Some(reason.clone());
+        }
+        job.updated_at = Utc::now().timestamp();
+        Ok(())
+    }
+
+    async fn list_jobs(&self, params: &ListJobParams) -> Result<Vec<Job>> {
+        Ok(self
+            .jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|job| params.states.is_empty() || params.states.contains(&job.state))
+            .cloned()
+            .collect())
+    }
+
+    async fn count_jobs(&self) -> Result<Vec<(JobState, u64)>> {
+        unsupported()
+    }
+
+    async fn cancel(&self, _id: &ObjectId, _deadline: i64) -> Result<()> {
+        unsupported()
+    }
+
+    async fn mark_cancelled(&self, _id: &ObjectId, _completed_batches: u64) -> Result<()> {
+        unsupported()
+    }
+
+    async fn set_summary(&self, _id: &ObjectId, _summary: &RunSummary) -> Result<()> {
+        unsupported()
+    }
+
+    async fn retry(&self, _id: &ObjectId, _attempt: &JobAttempt) -> Result<()> {
+        unsupported()
+    }
+}
+
+impl ScheduleRepo for MemMainDbRepo {
+    async fn insert_schedule(&self, _schedule: &Schedule) -> Result<Schedule> {
+        unsupported()
+    }
+
+    async fn get_schedule(&self, _params: &GetScheduleParams) -> Result<Option<Schedule>> {
+        unsupported()
+    }
+
+    async fn list_schedules(&self) -> Result<Vec<Schedule>> {
+        unsupported()
+    }
+
+    async fn list_due_schedules(&self, _now: i64) -> Result<Vec<Schedule>> {
+        unsupported()
+    }
+
+    async fn advance_schedule(
+        &self,
+        _id: &ObjectId,
+        _expected: i64,
+        _next_run_at: i64,
+        _last_run_at: Option<i64>,
+    ) -> Result<bool> {
+        unsupported()
+    }
+
+    async fn set_schedule_paused(
+        &self,
+        _id: &ObjectId,
+        _paused: bool,
+        _next_run_at: i64,
+    ) -> Result<()> {
+        unsupported()
+    }
+
+    async fn delete_schedule(&self, _id: &ObjectId) -> Result<()> {
+        unsupported()
+    }
+}
+
+impl TriggerRepo for MemMainDbRepo {
+    async fn insert_trigger(&self, _trigger: &Trigger) -> Result<Trigger> {
+        unsupported()
+    }
+
+    async fn get_trigger(&self, _params: &GetTriggerParams) -> Result<Option<Trigger>> {
+        unsupported()
+    }
+
+    async fn list_triggers(&self) -> Result<Vec<Trigger>> {
+        unsupported()
+    }
+
+    async fn update_trigger_cursor(
+        &self,
+        _id: &ObjectId,
+        _expected: &TriggerCursor,
+        _cursor: &TriggerCursor,
+        _last_run_at: Option<i64>,
+    ) -> Result<bool> {
+        unsupported()
+    }
+
+    async fn delete_trigger(&self, _id: &ObjectId) -> Result<()> {
+        unsupported()
+    }
+}
+
+impl HookRepo for MemMainDbRepo {
+    async fn insert_delivery(&self, _delivery: &HookDelivery) -> Result<()> {
+        unsupported()
+    }
+
+    async fn has_event(&self, _job_id: &ObjectId, _event_key: &str) -> Result<bool> {
+        unsupported()
+    }
+
+    async fn list_due_deliveries(&self, _now: i64) -> Result<Vec<HookDelivery>> {
+        unsupported()
+    }
+
+    async fn list_deliveries(&self, _job_id: &ObjectId) -> Result<Vec<HookDelivery>> {
+        unsupported()
+    }
+
+    async fn update_delivery(&self, _id: &ObjectId, _info: &HookDeliveryUpdateInfo) -> Result<()> {
+        unsupported()
+    }
+}
+
+impl LeaseRepo for MemMainDbRepo {
+    async fn acquire_lease(
+        &self,
+        _name: &str,
+        _holder: &str,
+        _now: i64,
+        _expires_at: i64,
+    ) -> Result<bool> {
+        unsupported()
+    }
+
+    async fn release_lease(&self, _name: &str, _holder: &str) -> Result<()> {
+        unsupported()
+    }
+}
+
+impl PingRepo for MemMainDbRepo {
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,11 +1,36 @@
+use super::change_stream::{
+    watch_changes,
+    WatchTarget,
+};
 use crate::{
-    core::db::{
-        GetJobParams,
-        Job,
-        JobRepo,
-        JobState,
-        JobUpdateInfo,
-        ListJobParams,
+    core::{
+        db::{
+            DeliveryState,
+            GetJobParams,
+            HookDelivery,
+            HookDeliveryUpdateInfo,
+            HookRepo,
+            InvalidTransition,
+            Job,
+            JobAttempt,
+            JobCursor,
+            JobRepo,
+            JobState,
+            JobUpdateInfo,
+            Lease,
+            LeaseRepo,
+            ListJobParams,
+            PingRepo,
+            RunSummary,
+            Schedule,
+            ScheduleRepo,
+            SortOrder,
+            Trigger,
+            TriggerCursor,
+            TriggerRepo,
+        },
+        Notification,
+        Notifier,
     },
     utils::{
         IntoAnyhowResult,
@@ -24,11 +49,18 @@ use futures::TryStreamExt;
 use mongodb::{
     bson::{
         doc,
+        from_document,
         oid::ObjectId,
+        to_document,
+        Document,
+    },
+    error::{
+        ErrorKind,
+        WriteFailure,
     },
-    error::ErrorKind,
     options::{
         ClientOptions,
+        FindOptions,
         IndexOptions,
     },
     Client,
@@ -36,13 +68,36 @@ use mongodb::{
     IndexModel,
 };
 
+use serde::Deserialize;
 use serde_variant::to_variant_name;
+use tokio_util::sync::CancellationToken;
 
-const JOB_COL_NAME: &str = "job";
+pub(crate) const JOB_COL_NAME: &str = "job";
+const SCHEDULE_COL_NAME: &str = "schedule";
+const TRIGGER_COL_NAME: &str = "trigger";
+const HOOK_DELIVERY_COL_NAME: &str = "hook_delivery";
+const LEASE_COL_NAME: &str = "lease";
+
+/// is_duplicate_key return true if err was caused by violating an unique index
+pub fn is_duplicate_key(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<mongodb::error::Error>()
+        .is_some_and(|err| {
+            matches!(
+                *err.kind,
+                ErrorKind::Write(WriteFailure::WriteError(ref write_error)) if write_error.code == 11000
+            )
+        })
+}
 
 #[derive(Clone)]
 pub struct MongoMainDbRepo {
+    client: Client,
     job_col: Collection<Job>,
+    schedule_col: Collection<Schedule>,
+    trigger_col: Collection<Trigger>,
+    delivery_col: Collection<HookDelivery>,
+    lease_col: Collection<Lease>,
+    notifier: Notifier,
 }
 
 impl MongoMainDbRepo {
@@ -54,8 +109,19 @@ impl MongoMainDbRepo {
             .expect("set db name in url")
             .clone();
         let client = Client::with_options(options)?;
-        let database = client.database(database.as_str());
-        let job_col: Collection<Job> = database.collection(JOB_COL_NAME);
+        let job_col: Collection<Job> = client.database(database.as_str()).collection(JOB_COL_NAME);
+        let schedule_col: Collection<Schedule> = client
+            .database(database.as_str())
+            .collection(SCHEDULE_COL_NAME);
+        let trigger_col: Collection<Trigger> = client
+            .database(database.as_str())
+            .collection(TRIGGER_COL_NAME);
+        let delivery_col: Collection<HookDelivery> = client
+            .database(database.as_str())
+            .collection(HOOK_DELIVERY_COL_NAME);
+        let lease_col: Collection<Lease> = client
+            .database(database.as_str())
+            .collection(LEASE_COL_NAME);
 
         {
             //create index for jobs
@@ -98,13 +164,161 @@ impl MongoMainDbRepo {
                 }
             }
         }
-        Ok(MongoMainDbRepo { job_col })
+
+        {
+            //create indexes for listing jobs
+            for (keys, name) in [
+                (doc! { "created_at": 1, "_id": 1 }, "idx_created_at"),
+                (doc! { "updated_at": 1, "_id": 1 }, "idx_updated_at"),
+                (doc! { "owner": 1, "created_at": 1 }, "idx_owner_created_at"),
+                (doc! { "labels.$**": 1 }, "idx_labels"),
+            ] {
+                let idx_opts: IndexOptions = IndexOptions::builder().name(name.to_owned()).build();
+                let index = IndexModel::builder().keys(keys).options(idx_opts).build();
+
+                if let Err(err) = job_col.create_index(index).await {
+                    match *err.kind {
+                        ErrorKind::Command(ref command_error) if command_error.code == 85 => {}
+                        err => {
+                            return Err(anyhow!("create job {name} index error {err}"));
+                        }
+                    }
+                }
+            }
+        }
+
+        {
+            //create index for schedules
+            let idx_opts: IndexOptions = IndexOptions::builder()
+                .unique(true)
+                .name("idx_name".to_owned())
+                .build();
+
+            let index = IndexModel::builder()
+                .keys(doc! { "name": 1 })
+                .options(idx_opts)
+                .build();
+
+            if let Err(err) = schedule_col.create_index(index).await {
+                match *err.kind {
+                    ErrorKind::Command(ref command_error) if command_error.code == 85 => {}
+                    err => {
+                        return Err(anyhow!("create schedule name index error {err}"));
+                    }
+                }
+            }
+        }
+
+        {
+            //create index for triggers
+            let idx_opts: IndexOptions = IndexOptions::builder()
+                .unique(true)
+                .name("idx_name".to_owned())
+                .build();
+
+            let index = IndexModel::builder()
+                .keys(doc! { "name": 1 })
+                .options(idx_opts)
+                .build();
+
+            if let Err(err) = trigger_col.create_index(index).await {
+                match *err.kind {
+                    ErrorKind::Command(ref command_error) if command_error.code == 85 => {}
+                    err => {
+                        return Err(anyhow!("create trigger name index error {err}"));
+                    }
+                }
+            }
+        }
+
+        {
+            //create indexes for hook deliveries
+            for (keys, name) in [
+                (doc! { "job_id": 1, "event_key": 1 }, "idx_job_id_event_key"),
+                (
+                    doc! { "state": 1, "next_attempt_at": 1 },
+                    "idx_state_next_attempt_at",
+                ),
+            ] {
+                let idx_opts: IndexOptions = IndexOptions::builder().name(name.to_owned()).build();
+                let index = IndexModel::builder().keys(keys).options(idx_opts).build();
+
+                if let Err(err) = delivery_col.create_index(index).await {
+                    match *err.kind {
+                        ErrorKind::Command(ref command_error) if command_error.code == 85 => {}
+                        err => {
+                            return Err(anyhow!("create hook delivery index error {err}"));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(MongoMainDbRepo {
+            client,
+            job_col,
+            schedule_col,
+            trigger_col,
+            delivery_col,
+            lease_col,
+            notifier: Notifier::new(),
+        })
+    }
+
+    /// notifier publish job state changes made by this repo, and state changes made by others
+    /// while watch is running
+    pub fn notifier(&self) -> &Notifier {
+        &self.notifier
+    }
+
+    /// watch publish state changes of jobs in main database until token cancelled, return
+    /// immediately if mongo dont support change streams
+    pub async fn watch(&self, token: CancellationToken) -> Result<()> {
+        watch_changes(
+            WatchTarget::Database(self.client.database(&self.job_col.namespace().db)),
+            &[JOB_COL_NAME],
+            self.notifier.clone(),
+            token,
+        )
+        .await
+    }
+
+    /// transition_error explain why a conditional state update matched no job
+    async fn transition_error(&self, id: &ObjectId, next: Option<&JobState>) -> anyhow::Error {
+        match (self.job_col.find_one(doc! {"_id": id}).await, next) {
+            (Ok(Some(job)), Some(next)) => InvalidTransition::new(job.state, next.clone()).into(),
+            (Ok(_), _) => anyhow!("job {id} not found"),
+            (Err(err), _) => err.into(),
+        }
     }
 }
 
+/// escape_regex quote characters having special meaning in mongo regex
+fn escape_regex(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if "\\.^$|?*+()[]{}/".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// from_states match jobs whose state can move to next
+fn from_states(next: &JobState) -> Result<Document> {
+    let states = JobState::prev_states(next)
+        .iter()
+        .map(to_variant_name)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(doc! {"$in": states})
+}
+
 impl JobRepo for MongoMainDbRepo {
     async fn insert(&self, job: &Job) -> Result<Job> {
         let inserted_id = self.job_col.insert_one(job).await?.inserted_id;
+        self.notifier.notify(Notification::JobStateChanged {
+            id: inserted_id.as_object_id(),
+        });
 
         self.job_col
             .find_one(doc! {"_id": inserted_id})
@@ -113,20 +327,72 @@ impl JobRepo for MongoMainDbRepo {
             .and_then(|r| r.anyhow("insert job not found"))
     }
 
-    async fn get_job_for_running(&self) -> Result<Option<Job>> {
+    async fn queue_created_jobs(&self) -> Result<u64> {
+        let update = doc! {
+            "$set": {
+                "state": to_variant_name(&JobState::Queued)?,
+                "updated_at": Utc::now().timestamp(),
+            },
+        };
+
+        let result = self
+            .job_col
+            .update_many(doc! {"state": from_states(&JobState::Queued)?}, update)
+            .await?;
+        if result.modified_count > 0 {
+            self.notifier
+                .notify(Notification::JobStateChanged { id: None });
+        }
+        Ok(result.modified_count)
+    }
+
+    async fn select_job(&self, id: &ObjectId, holder: &str) -> Result<Option<Job>> {
         let update = doc! {
             "$set": {
                 "state": to_variant_name(&JobState::Selected)?,
+                "holder": holder,
                 "updated_at":Utc::now().timestamp(),
             },
         };
+        let query = doc! {
+            "_id": id,
+            "state": from_states(&JobState::Selected)?,
+        };
 
         self.job_col
-            .find_one_and_update(doc! {"state": to_variant_name(&JobState::Created)?}, update)
+            .find_one_and_update(query, update)
             .await
             .anyhow()
     }
 
+    async fn set_holder(&self, id: &ObjectId, holder: &str) -> Result<()> {
+        let update = doc! {
+            "$set": {
+                "holder": holder,
+                "updated_at": Utc::now().timestamp(),
+            },
+        };
+        self.job_col
+            .update_one(doc! {"_id": id}, update)
+            .await
+            .anyhow()?;
+        Ok(())
+    }
+
+    async fn set_summary(&self, id: &ObjectId, summary: &RunSummary) -> Result<()> {
+        let update = doc! {
+            "$set": {
+                "summary": to_document(summary)?,
+                "updated_at": Utc::now().timestamp(),
+            },
+        };
+        self.job_col
+            .update_one(doc! {"_id": id}, update)
+            .await
+            .anyhow()?;
+        Ok(())
+    }
+
     async fn update(&self, id: &ObjectId, info: &JobUpdateInfo) -> Result<()> {
         let mut update_fields = doc! {
             "updated_at":Utc::now().timestamp()
@@ -134,26 +400,209 @@ impl JobRepo for MongoMainDbRepo {
         if let Some(state) = info.state.as_ref() {
             update_fields.insert("state", to_variant_name(state)?);
         }
+        if let Some(started_at) = info.started_at {
+            update_fields.insert("started_at", started_at);
+        }
+        if let Some(active_secs) = info.active_secs {
+            update_fields.insert("active_secs", active_secs as i64);
+        }
+        if let Some(reason) = info.reason.as_ref() {
+            update_fields.insert("reason", reason);
+        }
 
         let update = doc! {"$set": update_fields};
-        let query = doc! {
+        let mut query = doc! {
             "_id":  id,
         };
+        if let Some(state) = info.state.as_ref() {
+            query.insert("state", from_states(state)?);
+        }
 
-        self.job_col
-            .update_one(query, update)
-            .await
-            .map(|_| ())
-            .anyhow()
+        let result = self.job_col.update_one(query, update).await.anyhow()?;
+        if result.matched_count == 0 {
+            return Err(self.transition_error(id, info.state.as_ref()).await);
+        }
+        if info.state.is_some() {
+            self.notifier
+                .notify(Notification::JobStateChanged { id: Some(*id) });
+        }
+        Ok(())
+    }
+
+    async fn count_jobs(&self) -> Result<Vec<(JobState, u64)>> {
+        #[derive(Deserialize)]
+        struct StateCount {
+            #[serde(rename = "_id")]
+            state: JobState,
+            count: u64,
+        }
+
+        let pipeline = vec![doc! {"$group": {"_id": "$state", "count": {"$sum": 1}}}];
+        let mut cursor = self.job_col.aggregate(pipeline).await?;
+        let mut counts = vec![];
+        while let Some(document) = cursor.try_next().await? {
+            let StateCount { state, count } = from_document(document)?;
+            counts.push((state, count));
+        }
+        Ok(counts)
+    }
+
+    async fn cancel(&self, id: &ObjectId, deadline: i64) -> Result<()> {
+        let update = doc! {
+            "$set": {
+                "state": to_variant_name(&JobState::Cancelling)?,
+                "cancel_deadline": deadline,
+                "updated_at": Utc::now().timestamp(),
+            },
+        };
+        let query = doc! {
+            "_id": id,
+            "state": from_states(&JobState::Cancelling)?,
+        };
+
+        let result = self.job_col.update_one(query, update).await.anyhow()?;
+        if result.matched_count == 0 {
+            return Err(self.transition_error(id, Some(&JobState::Cancelling)).await);
+        }
+        self.notifier
+            .notify(Notification::JobStateChanged { id: Some(*id) });
+        Ok(())
+    }
+
+    async fn mark_cancelled(&self, id: &ObjectId, completed_batches: u64) -> Result<()> {
+        let update = doc! {
+            "$set": {
+                "state": to_variant_name(&JobState::Cancelled)?,
+                "completed_batches": completed_batches as i64,
+                "updated_at": Utc::now().timestamp(),
+            },
+        };
+        let query = doc! {
+            "_id": id,
+            "state": from_states(&JobState::Cancelled)?,
+        };
+
+        self.job_col.update_one(query, update).await.anyhow()?;
+        self.notifier
+            .notify(Notification::JobStateChanged { id: Some(*id) });
+        Ok(())
+    }
+
+    async fn retry(&self, id: &ObjectId, attempt: &JobAttempt) -> Result<()> {
+        let update = doc! {
+            "$set": {
+                "state": to_variant_name(&JobState::Created)?,
+                "active_secs": 0i64,
+                "updated_at": Utc::now().timestamp(),
+            },
+            "$push": {
+                "attempts": to_document(attempt)?,
+            },
+            "$unset": {
+                "started_at": "",
+                "reason": "",
+            },
+        };
+        let query = doc! {
+            "_id": id,
+            "state": from_states(&JobState::Created)?,
+        };
+
+        let result = self.job_col.update_one(query, update).await.anyhow()?;
+        if result.matched_count == 0 {
+            return Err(self.transition_error(id, Some(&JobState::Created)).await);
+        }
+        self.notifier
+            .notify(Notification::JobStateChanged { id: Some(*id) });
+        Ok(())
     }
 
     async fn list_jobs(&self, list_job_params: &ListJobParams) -> Result<Vec<Job>> {
         let mut query = doc! {};
-        if let Some(state) = list_job_params.state.as_ref() {
-            query.insert("state", to_variant_name(state)?);
+        let mut conditions = vec![];
+        if !list_job_params.states.is_empty() {
+            let states = list_job_params
+                .states
+                .iter()
+                .map(to_variant_name)
+                .collect::<Result<Vec<_>, _>>()?;
+            query.insert("state", doc! {"$in": states});
+        }
+        if let Some(prefix) = list_job_params.name_prefix.as_ref() {
+            conditions.push(doc! {"name": {"$regex": format!("^{}", escape_regex(prefix))}});
+        }
+        if let Some(regex) = list_job_params.name_regex.as_ref() {
+            conditions.push(doc! {"name": {"$regex": regex}});
+        }
+        for label in list_job_params.labels.iter() {
+            let (key, value) = label
+                .split_once('=')
+                .anyhow(format!("label {label} must be key=value"))?;
+            query.insert(format!("labels.{key}"), value);
+        }
+        if let Some(owner) = list_job_params.owner.as_ref() {
+            query.insert("owner", owner);
+        }
+        if let Some(schedule_id) = list_job_params.schedule_id.as_ref() {
+            query.insert("schedule_id", schedule_id);
+        }
+        if let Some(trigger_id) = list_job_params.trigger_id.as_ref() {
+            query.insert("trigger_id", trigger_id);
+        }
+        for (field, after, before) in [
+            (
+                "created_at",
+                list_job_params.created_after,
+                list_job_params.created_before,
+            ),
+            (
+                "updated_at",
+                list_job_params.updated_after,
+                list_job_params.updated_before,
+            ),
+        ] {
+            let mut range = doc! {};
+            if let Some(after) = after {
+                range.insert("$gte", after);
+            }
+            if let Some(before) = before {
+                range.insert("$lt", before);
+            }
+            if !range.is_empty() {
+                query.insert(field, range);
+            }
         }
 
-        self.job_col.find(query).await?.try_collect().await.anyhow()
+        //ties of sort field are ordered by id so pages never overlap
+        let field = list_job_params.sort.field_name();
+        let (direction, compare) = match list_job_params.order {
+            SortOrder::Asc => (1, "$gt"),
+            SortOrder::Desc => (-1, "$lt"),
+        };
+        if let Some(cursor) = list_job_params.cursor.as_ref() {
+            let cursor = JobCursor::decode(cursor)?;
+            conditions.push(doc! {
+                "$or": [
+                    { field: { compare: cursor.value.clone() } },
+                    { field: cursor.value, "_id": { compare: cursor.id } },
+                ]
+            });
+        }
+        if !conditions.is_empty() {
+            query.insert("$and", conditions);
+        }
+
+        let options = FindOptions::builder()
+            .sort(doc! {field: direction, "_id": direction})
+            .limit(list_job_params.limit.map(|limit| limit as i64))
+            .build();
+        self.job_col
+            .find(query)
+            .with_options(options)
+            .await?
+            .try_collect()
+            .await
+            .anyhow()
     }
 
     async fn get(&self, get_params: &GetJobParams) -> Result<Option<Job>> {
@@ -180,3 +629,295 @@ impl JobRepo for MongoMainDbRepo {
             .anyhow()
     }
 }
+
+impl ScheduleRepo for MongoMainDbRepo {
+    async fn insert_schedule(&self, schedule: &Schedule) -> Result<Schedule> {
+        let inserted_id = self.schedule_col.insert_one(schedule).await?.inserted_id;
+        self.schedule_col
+            .find_one(doc! {"_id": inserted_id})
+            .await
+            .anyhow()
+            .and_then(|r| r.anyhow("insert schedule not found"))
+    }
+
+    async fn get_schedule(&self, get_params: &GetJobParams) -> Result<Option<Schedule>> {
+        let mut query = doc! {};
+        if let Some(id) = get_params.id.as_ref() {
+            query.insert("_id", id);
+        }
+        if let Some(name) = get_params.name.as_ref() {
+            query.insert("name", name);
+        }
+
+        if query.is_empty() {
+            return Ok(None);
+        }
+
+        self.schedule_col.find_one(query).await.anyhow()
+    }
+
+    async fn list_schedules(&self) -> Result<Vec<Schedule>> {
+        self.schedule_col
+            .find(doc! {})
+            .await?
+            .try_collect()
+            .await
+            .anyhow()
+    }
+
+    async fn list_due_schedules(&self, now: i64) -> Result<Vec<Schedule>> {
+        let query = doc! {
+            "paused": false,
+            "next_run_at": {"$lte": now},
+        };
+        self.schedule_col
+            .find(query)
+            .await?
+            .try_collect()
+            .await
+            .anyhow()
+    }
+
+    async fn advance_schedule(
+        &self,
+        id: &ObjectId,
+        expected: i64,
+        next_run_at: i64,
+        last_run_at: Option<i64>,
+    ) -> Result<bool> {
+        let mut update_fields = doc! {
+            "next_run_at": next_run_at,
+            "updated_at": Utc::now().timestamp(),
+        };
+        if let Some(last_run_at) = last_run_at {
+            update_fields.insert("last_run_at", last_run_at);
+        }
+
+        let query = doc! {
+            "_id": id,
+            "next_run_at": expected,
+        };
+        self.schedule_col
+            .update_one(query, doc! {"$set": update_fields})
+            .await
+            .map(|r| r.modified_count > 0)
+            .anyhow()
+    }
+
+    async fn set_schedule_paused(
+        &self,
+        id: &ObjectId,
+        paused: bool,
+        next_run_at: i64,
+    ) -> Result<()> {
+        let update = doc! {
+            "$set": {
+                "paused": paused,
+                "next_run_at": next_run_at,
+                "updated_at": Utc::now().timestamp(),
+            },
+        };
+        self.schedule_col
+            .update_one(doc! {"_id": id}, update)
+            .await
+            .map(|_| ())
+            .anyhow()
+    }
+
+    async fn delete_schedule(&self, id: &ObjectId) -> Result<()> {
+        self.schedule_col
+            .delete_one(doc! {"_id": id})
+            .await
+            .map(|_| ())
+            .anyhow()
+    }
+}
+
+impl TriggerRepo for MongoMainDbRepo {
+    async fn insert_trigger(&self, trigger: &Trigger) -> Result<Trigger> {
+        let inserted_id = self.trigger_col.insert_one(trigger).await?.inserted_id;
+        self.trigger_col
+            .find_one(doc! {"_id": inserted_id})
+            .await
+            .anyhow()
+            .and_then(|r| r.anyhow("insert trigger not found"))
+    }
+
+    async fn get_trigger(&self, get_params: &GetJobParams) -> Result<Option<Trigger>> {
+        let mut query = doc! {};
+        if let Some(id) = get_params.id.as_ref() {
+            query.insert("_id", id);
+        }
+        if let Some(name) = get_params.name.as_ref() {
+            query.insert("name", name);
+        }
+
+        if query.is_empty() {
+            return Ok(None);
+        }
+
+        self.trigger_col.find_one(query).await.anyhow()
+    }
+
+    async fn list_triggers(&self) -> Result<Vec<Trigger>> {
+        self.trigger_col
+            .find(doc! {})
+            .await?
+            .try_collect()
+            .await
+            .anyhow()
+    }
+
+    async fn update_trigger_cursor(
+        &self,
+        id: &ObjectId,
+        expected: &TriggerCursor,
+        cursor: &TriggerCursor,
+        last_run_at: Option<i64>,
+    ) -> Result<bool> {
+        let mut update_fields = doc! {
+            "cursor": to_document(cursor)?,
+            "updated_at": Utc::now().timestamp(),
+        };
+        if let Some(last_run_at) = last_run_at {
+            update_fields.insert("last_run_at", last_run_at);
+        }
+
+        let query = doc! {
+            "_id": id,
+            "cursor": to_document(expected)?,
+        };
+        self.trigger_col
+            .update_one(query, doc! {"$set": update_fields})
+            .await
+            .map(|r| r.matched_count > 0)
+            .anyhow()
+    }
+
+    async fn delete_trigger(&self, id: &ObjectId) -> Result<()> {
+        self.trigger_col
+            .delete_one(doc! {"_id": id})
+            .await
+            .map(|_| ())
+            .anyhow()
+    }
+}
+
+impl HookRepo for MongoMainDbRepo {
+    async fn insert_delivery(&self, delivery: &HookDelivery) -> Result<()> {
+        self.delivery_col
+            .insert_one(delivery)
+            .await
+            .map(|_| ())
+            .anyhow()
+    }
+
+    async fn has_event(&self, job_id: &ObjectId, event_key: &str) -> Result<bool> {
+        self.delivery_col
+            .count_documents(doc! {"job_id": job_id, "event_key": event_key})
+            .await
+            .map(|count| count > 0)
+            .anyhow()
+    }
+
+    async fn list_due_deliveries(&self, now: i64) -> Result<Vec<HookDelivery>> {
+        let query = doc! {
+            "state": to_variant_name(&DeliveryState::Pending)?,
+            "next_attempt_at": {"$lte": now},
+        };
+        self.delivery_col
+            .find(query)
+            .await?
+            .try_collect()
+            .await
+            .anyhow()
+    }
+
+    async fn list_deliveries(&self, job_id: &ObjectId) -> Result<Vec<HookDelivery>> {
+        self.delivery_col
+            .find(doc! {"job_id": job_id})
+            .sort(doc! {"created_at": 1})
+            .await?
+            .try_collect()
+            .await
+            .anyhow()
+    }
+
+    async fn update_delivery(&self, id: &ObjectId, info: &HookDeliveryUpdateInfo) -> Result<()> {
+        let update = doc! {
+            "$set": {
+                "state": to_variant_name(&info.state)?,
+                "attempts": info.attempts,
+                "last_error": info.last_error.as_ref(),
+                "next_attempt_at": info.next_attempt_at,
+                "updated_at": Utc::now().timestamp(),
+            },
+        };
+        self.delivery_col
+            .update_one(doc! {"_id": id}, update)
+            .await
+            .map(|_| ())
+            .anyhow()
+    }
+}
+
+impl PingRepo for MongoMainDbRepo {
+    async fn ping(&self) -> Result<()> {
+        self.client
+            .database("admin")
+            .run_command(doc! {"ping": 1})
+            .await?;
+        Ok(())
+    }
+}
+
+impl LeaseRepo for MongoMainDbRepo {
+    async fn acquire_lease(
+        &self,
+        name: &str,
+        holder: &str,
+        now: i64,
+        expires_at: i64,
+    ) -> Result<bool> {
+        let query = doc! {
+            "_id": name,
+            "$or": [
+                {"holder": holder},
+                {"expires_at": {"$lte": now}},
+            ],
+        };
+        let update = doc! {
+            "$set": {
+                "holder": holder,
+                "expires_at": expires_at,
+                "renewed_at": now,
+            },
+        };
+
+        //lease held by others fail the upsert with duplicate key
+        match self.lease_col.update_one(query, update).upsert(true).await {
+            Ok(_) => Ok(true),
+            Err(err) => match *err.kind {
+                ErrorKind::Write(WriteFailure::WriteError(ref write_error))
+                    if write_error.code == 11000 =>
+                {
+                    Ok(false)
+                }
+                _ => Err(err.into()),
+            },
+        }
+    }
+
+    async fn release_lease(&self, name: &str, holder: &str) -> Result<()> {
+        let update = doc! {
+            "$set": {
+                "expires_at": 0,
+            },
+        };
+        self.lease_col
+            .update_one(doc! {"_id": name, "holder": holder}, update)
+            .await
+            .anyhow()?;
+        Ok(())
+    }
+}
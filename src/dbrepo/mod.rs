@@ -1,5 +1,10 @@
+mod change_stream;
 mod job_db_mongo;
+#[cfg(test)]
+mod main_db_mem;
 mod main_db_mongo;
 
 pub use job_db_mongo::*;
+#[cfg(test)]
+pub use main_db_mem::*;
 pub use main_db_mongo::*;
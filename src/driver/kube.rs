@@ -2,6 +2,7 @@ use super::{
     Driver,
     NodeStatus,
     PipelineController,
+    Plan,
     PodStauts,
     UnitHandler,
 };
@@ -17,6 +18,10 @@ use crate::{
         },
         AccessMode,
         ComputeUnit,
+        ExternalEndpoint,
+        MachineSpec,
+        PullPolicy,
+        RunMode,
         StorageOptions,
     },
     dag::Dag,
@@ -37,6 +42,7 @@ use handlebars::{
     Context,
     Handlebars,
     Helper,
+    JsonValue,
     Output,
     RenderContext,
     RenderError,
@@ -44,12 +50,14 @@ use handlebars::{
 use k8s_metrics::v1beta1 as metricsv1;
 use k8s_openapi::api::{
     apps::v1::StatefulSet,
+    batch::v1::Job,
     core::v1::{
         Namespace,
         PersistentVolumeClaim,
         Pod,
         Service,
     },
+    networking::v1::NetworkPolicy,
 };
 use kube::{
     api::{
@@ -61,11 +69,22 @@ use kube::{
     Api,
     Client,
 };
-use serde::Serialize;
+use mongodb::options::{
+    ConnectionString,
+    HostInfo,
+    ServerAddress,
+};
+use serde::{
+    de::DeserializeOwned,
+    Serialize,
+};
 use std::{
     collections::HashMap,
     default::Default,
+    fmt::Debug,
     marker::PhantomData,
+    net::IpAddr,
+    path::Path,
 };
 use tokio_retry::{
     strategy::ExponentialBackoff,
@@ -74,9 +93,17 @@ use tokio_retry::{
 use tracing::{
     debug,
     error,
+    info,
     warn,
 };
 
+/// Workload is the kubernetes resource which run pods of a node
+#[derive(Debug, Clone)]
+pub(crate) enum Workload {
+    StatefulSet(String),
+    Job(String),
+}
+
 pub struct KubeHandler<R>
 where
     R: JobDbRepo,
@@ -84,7 +111,7 @@ where
     pub(crate) client: Client,
     pub(crate) node_name: String,
     pub(crate) namespace: String,
-    pub(crate) stateset_name: String,
+    pub(crate) workload: Workload,
     pub(crate) claim_name: String,
     pub(crate) _service_name: String,
     pub(crate) db_repo: R,
@@ -101,19 +128,32 @@ where
     async fn status(&self) -> Result<NodeStatus> {
         let statefulset_api: Api<StatefulSet> =
             Api::namespaced(self.client.clone(), &self.namespace);
+        let job_api: Api<Job> = Api::namespaced(self.client.clone(), &self.namespace);
         let claim_api: Api<PersistentVolumeClaim> =
             Api::namespaced(self.client.clone(), &self.namespace);
         let pods_api: Api<Pod> = Api::namespaced(self.client.clone(), &self.namespace);
         let metrics_api: Api<metricsv1::PodMetrics> =
             Api::<metricsv1::PodMetrics>::namespaced(self.client.clone(), &self.namespace);
 
-        let statefulset = statefulset_api.get(&self.stateset_name).await.anyhow()?;
-        let selector = statefulset
-            .spec
-            .as_ref()
-            .unwrap()
-            .selector
-            .match_labels
+        let (match_labels, replicas) = match &self.workload {
+            Workload::StatefulSet(name) => {
+                let statefulset = statefulset_api.get(name).await.anyhow()?;
+                let spec = statefulset.spec.expect("set in template");
+                (
+                    spec.selector.match_labels,
+                    spec.replicas.unwrap_or_default() as u32,
+                )
+            }
+            Workload::Job(name) => {
+                let job = job_api.get(name).await.anyhow()?;
+                let spec = job.spec.expect("set in template");
+                (
+                    spec.selector.and_then(|selector| selector.match_labels),
+                    spec.parallelism.unwrap_or_default() as u32,
+                )
+            }
+        };
+        let selector = match_labels
             .as_ref()
             .expect("set in template")
             .iter()
@@ -143,11 +183,7 @@ where
             name: self.node_name.clone(),
             state: db_node.state,
             data_count,
-            replicas: statefulset
-                .spec
-                .as_ref()
-                .and_then(|spec| spec.replicas)
-                .unwrap_or_default() as u32,
+            replicas,
             storage: cap,
             pods: HashMap::new(),
         };
@@ -197,13 +233,29 @@ where
     }
 
     async fn start(&self) -> Result<()> {
+        //finished node keep its state when job is resumed
+        let node = self.db_repo.get_node_by_name(&self.node_name).await?;
+        if node.state == TrackerState::Finish {
+            return Ok(());
+        }
         self.db_repo
             .update_node_by_name(&self.node_name, TrackerState::Ready)
             .await
     }
 
+    async fn workload_state(&self) -> Result<Option<TrackerState>> {
+        let Workload::Job(job_name) = &self.workload else {
+            return Ok(None);
+        };
+
+        let job_api: Api<Job> = Api::namespaced(self.client.clone(), &self.namespace);
+        let job = job_api.get(job_name).await.anyhow()?;
+        Ok(job_tracker_state(&job))
+    }
+
     async fn pause(&mut self) -> Result<()> {
-        todo!()
+        //runner idle when node is Stop, pods and data are kept for inspection
+        self.stop().await
     }
 
     async fn restart(&mut self) -> Result<()> {
@@ -211,10 +263,31 @@ where
     }
 
     async fn stop(&mut self) -> Result<()> {
-        todo!()
+        //runner stop accepting new data when node is Stop, finished node keep its state
+        let node = self.db_repo.get_node_by_name(&self.node_name).await?;
+        if node.state.is_end_state() {
+            return Ok(());
+        }
+        self.db_repo
+            .update_node_by_name(&self.node_name, TrackerState::Stop)
+            .await
     }
 }
 
+/// job_tracker_state map the conditions of a kubernetes job to node state, return None if the job
+/// is still running
+fn job_tracker_state(job: &Job) -> Option<TrackerState> {
+    let conditions = job.status.as_ref()?.conditions.as_ref()?;
+    conditions
+        .iter()
+        .filter(|condition| condition.status == "True")
+        .find_map(|condition| match condition.type_.as_str() {
+            "Complete" => Some(TrackerState::Finish),
+            "Failed" => Some(TrackerState::Error),
+            _ => None,
+        })
+}
+
 pub struct KubePipelineController<R>
 where
     R: JobDbRepo,
@@ -278,10 +351,7 @@ fn join_array(
         None => Ok(()),
         Some(args) => {
             let args = args.value().as_array().unwrap();
-            let args_str: Vec<String> = args
-                .iter()
-                .map(|v| "\"".to_owned() + v.as_str().unwrap() + "\"")
-                .collect();
+            let args_str: Vec<String> = args.iter().map(|v| v.to_string()).collect();
             let rendered = args_str.join(",").to_string();
             out.write(rendered.as_ref())?;
             Ok(())
@@ -289,10 +359,58 @@ fn join_array(
     }
 }
 
+/// json_escape write a string escaped for use inside a json string literal, so that quotes and
+/// backslashes in options dont break rendered manifests
+fn json_escape(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> Result<(), RenderError> {
+    let value = h
+        .param(0)
+        .map(|param| param.value())
+        .unwrap_or(&JsonValue::Null);
+    let content = match value {
+        JsonValue::String(content) => content.clone(),
+        other => other.to_string(),
+    };
+    let quoted = JsonValue::String(content).to_string();
+    out.write(&quoted[1..quoted.len() - 1])?;
+    Ok(())
+}
+
+/// RunnerOptions control how the compute_unit_runner sidecar is deployed in every node pod.
+/// pull policy and image pull secrets apply to all containers in the pod.
+#[derive(Clone, Debug, Serialize)]
+pub struct RunnerOptions {
+    pub image: String,
+    pub pull_policy: PullPolicy,
+    pub image_pull_secrets: Vec<String>,
+    pub log_level: String,
+    pub args: Vec<String>,
+}
+
+impl Default for RunnerOptions {
+    fn default() -> Self {
+        Self {
+            image: "gitdatateam/compute_unit_runner:latest".to_string(),
+            pull_policy: PullPolicy::default(),
+            image_pull_secrets: vec![],
+            log_level: "debug".to_string(),
+            args: vec![],
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct KubeOptions {
     db_url: String,
     storage: StorageOptions,
+    runner: RunnerOptions,
+    template_dir: Option<String>,
+    network_policy: bool,
 }
 
 impl Default for KubeOptions {
@@ -304,6 +422,9 @@ impl Default for KubeOptions {
                 capacity: Some("1Gi".to_string()),
                 access_mode: Some(AccessMode::ReadWriteMany),
             },
+            runner: RunnerOptions::default(),
+            template_dir: None,
+            network_policy: false,
         }
     }
 }
@@ -346,6 +467,192 @@ impl KubeOptions {
         self.storage.access_mode = Some(mode);
         self
     }
+
+    pub fn set_runner_image(mut self, image: &str) -> Self {
+        self.runner.image = image.to_string();
+        self
+    }
+
+    pub fn set_pull_policy(mut self, pull_policy: PullPolicy) -> Self {
+        self.runner.pull_policy = pull_policy;
+        self
+    }
+
+    pub fn set_image_pull_secrets(mut self, secrets: Vec<String>) -> Self {
+        self.runner.image_pull_secrets = secrets;
+        self
+    }
+
+    pub fn set_runner_log_level(mut self, log_level: &str) -> Self {
+        self.runner.log_level = log_level.to_string();
+        self
+    }
+
+    pub fn set_runner_args(mut self, args: Vec<String>) -> Self {
+        self.runner.args = args;
+        self
+    }
+
+    /// set_template_dir load claim.tpl, statefulset.tpl, job.tpl, service.tpl and
+    /// networkpolicy.tpl from this directory, missing files fall back to the built-in templates
+    pub fn set_template_dir(mut self, dir: &str) -> Self {
+        self.template_dir = Some(dir.to_string());
+        self
+    }
+
+    /// set_network_policy isolate node pods of a job, node only accept data from its upstream
+    /// nodes and only access downstream nodes, database and declared external endpoints
+    pub fn set_network_policy(mut self, enable: bool) -> Self {
+        self.network_policy = enable;
+        self
+    }
+}
+
+const CLAIM_TPL: &str = "claim";
+const STATEFULSET_TPL: &str = "statefulset";
+const JOB_TPL: &str = "job";
+const SERVICE_TPL: &str = "service";
+const NETWORK_POLICY_TPL: &str = "networkpolicy";
+
+/// label selector of resources deleted when resume a job, claims dont have this label so cached
+/// data is kept
+const WORKLOAD_SELECTOR: &str = "exec-type=compute-unit";
+
+/// new_registry create handlebars registry with built-in templates, templates in template_dir
+/// override the built-in one with the same name
+pub(crate) fn new_registry(template_dir: Option<&str>) -> Result<Handlebars<'static>> {
+    let mut reg = Handlebars::new();
+    reg.set_strict_mode(true);
+    reg.register_helper("join_array", Box::new(join_array));
+    reg.register_helper("json_escape", Box::new(json_escape));
+
+    let builtin = [
+        (CLAIM_TPL, include_str!("kubetpl/claim.tpl")),
+        (STATEFULSET_TPL, include_str!("kubetpl/statefulset.tpl")),
+        (JOB_TPL, include_str!("kubetpl/job.tpl")),
+        (SERVICE_TPL, include_str!("kubetpl/service.tpl")),
+        (
+            NETWORK_POLICY_TPL,
+            include_str!("kubetpl/networkpolicy.tpl"),
+        ),
+    ];
+    for (name, content) in builtin {
+        let custom_path = template_dir.map(|dir| Path::new(dir).join(format!("{name}.tpl")));
+        match custom_path {
+            Some(path) if path.exists() => {
+                info!("load {name} template from {}", path.display());
+                reg.register_template_file(name, &path)
+                    .map_err(|err| anyhow!("load template {} {err}", path.display()))?;
+            }
+            _ => reg.register_template_string(name, content)?,
+        }
+    }
+    Ok(reg)
+}
+
+/// validate_templates render a sample node with all templates and make sure the output can be
+/// parsed as kubernetes resources
+pub(crate) fn validate_templates(reg: &Handlebars, options: &KubeOptions) -> Result<()> {
+    let node = ComputeUnit {
+        name: "sample".to_string(),
+        spec: MachineSpec {
+            image: "sample:latest".to_string(),
+            command: "/sample".to_string(),
+            args: vec!["--log-level=debug".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let claim_string = reg.render(
+        CLAIM_TPL,
+        &ClaimRenderParams {
+            storage: merge_storage_options(&options.storage, &node.spec.storage),
+            name: node.name.clone() + "-node-claim",
+        },
+    )?;
+    serde_json::from_str::<PersistentVolumeClaim>(&claim_string)
+        .map_err(|err| anyhow!("claim template render invalid resource {err}"))?;
+
+    let node_params = NodeRenderParams {
+        node: &node,
+        db_url: "mongodb://127.0.0.1:27017/sample",
+        run_id: "sample",
+        runner: &options.runner,
+    };
+    let statefulset_string = reg.render(STATEFULSET_TPL, &node_params)?;
+    serde_json::from_str::<StatefulSet>(&statefulset_string)
+        .map_err(|err| anyhow!("statefulset template render invalid resource {err}"))?;
+
+    let job_string = reg.render(JOB_TPL, &node_params)?;
+    serde_json::from_str::<Job>(&job_string)
+        .map_err(|err| anyhow!("job template render invalid resource {err}"))?;
+
+    let service_string = reg.render(SERVICE_TPL, &node)?;
+    serde_json::from_str::<Service>(&service_string)
+        .map_err(|err| anyhow!("service template render invalid resource {err}"))?;
+
+    let network_policy_string = reg.render(
+        NETWORK_POLICY_TPL,
+        &NetworkPolicyRenderParams {
+            node: &node,
+            up_nodes: vec!["sample-up"],
+            down_nodes: vec!["sample-down"],
+            egress: vec![EgressRule {
+                cidr: Some("127.0.0.1/32".to_string()),
+                ports: vec![27017],
+            }],
+        },
+    )?;
+    serde_json::from_str::<NetworkPolicy>(&network_policy_string)
+        .map_err(|err| anyhow!("network policy template render invalid resource {err}"))?;
+    Ok(())
+}
+
+/// EgressRule allow pods access ports of cidr, any destination if cidr is None
+#[derive(Serialize, Debug, PartialEq)]
+struct EgressRule {
+    cidr: Option<String>,
+    ports: Vec<u16>,
+}
+
+impl From<&ExternalEndpoint> for EgressRule {
+    fn from(endpoint: &ExternalEndpoint) -> Self {
+        EgressRule {
+            cidr: Some(endpoint.cidr.clone()),
+            ports: endpoint.ports.clone(),
+        }
+    }
+}
+
+/// db_egress_rules allow access database in db_url. network policy cant select hostname, so only
+/// ip host is restricted by address, other hosts are restricted by port
+fn db_egress_rules(db_url: &str) -> Result<Vec<EgressRule>> {
+    let connection_string =
+        ConnectionString::parse(db_url).map_err(|err| anyhow!("parse db url {err}"))?;
+    let rules = match connection_string.host_info {
+        HostInfo::HostIdentifiers(hosts) => hosts
+            .into_iter()
+            .filter_map(|host| match host {
+                ServerAddress::Tcp { host, port } => {
+                    let cidr = host.parse::<IpAddr>().ok().map(|ip| match ip {
+                        IpAddr::V4(_) => format!("{ip}/32"),
+                        IpAddr::V6(_) => format!("{ip}/128"),
+                    });
+                    Some(EgressRule {
+                        cidr,
+                        ports: vec![port.unwrap_or(27017)],
+                    })
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => vec![EgressRule {
+            cidr: None,
+            ports: vec![27017],
+        }],
+    };
+    Ok(rules)
 }
 
 #[derive(Clone)]
@@ -365,12 +672,8 @@ where
     R: JobDbRepo,
 {
     pub async fn new(client: Client, options: KubeOptions) -> Result<KubeDriver<R>> {
-        let mut reg = Handlebars::new();
-        reg.register_template_string("claim", include_str!("kubetpl/claim.tpl"))?;
-
-        reg.register_template_string("statefulset", include_str!("kubetpl/statefulset.tpl"))?;
-        reg.register_template_string("service", include_str!("kubetpl/service.tpl"))?;
-        reg.register_helper("join_array", Box::new(join_array));
+        let reg = new_registry(options.template_dir.as_deref())?;
+        validate_templates(&reg, &options)?;
         Ok(KubeDriver {
             reg,
             client,
@@ -417,6 +720,58 @@ where
             .map(|_| ())
             .map_err(|e| anyhow!("{}", e.to_string()))
     }
+
+    /// ensure_namespace_exit_and_clean_workloads delete workloads, services and network policies in
+    /// namespace, but keep namespace and claims so data cached on disk is still there when
+    /// resume
+    async fn ensure_namespace_exit_and_clean_workloads(client: &Client, ns: &str) -> Result<()> {
+        let namespaces: Api<Namespace> = Api::all(client.clone());
+        if namespaces.get_opt(ns).await?.is_none() {
+            let namespace = Namespace {
+                metadata: kube::api::ObjectMeta {
+                    name: Some(ns.to_string()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            namespaces
+                .create(&PostParams::default(), &namespace)
+                .await
+                .map_err(|e| anyhow!("{}", e.to_string()))?;
+            return Ok(());
+        }
+
+        delete_workloads::<StatefulSet>(client, ns).await?;
+        delete_workloads::<Job>(client, ns).await?;
+        delete_workloads::<Service>(client, ns).await?;
+        delete_workloads::<NetworkPolicy>(client, ns).await
+    }
+}
+
+/// delete_workloads delete resources matching WORKLOAD_SELECTOR and wait until they are gone,
+/// deploy create them with the same names
+async fn delete_workloads<K>(client: &Client, ns: &str) -> Result<()>
+where
+    K: kube::Resource<Scope = kube::core::NamespaceResourceScope>
+        + Clone
+        + DeserializeOwned
+        + Debug,
+    <K as kube::Resource>::DynamicType: Default,
+{
+    let api: Api<K> = Api::namespaced(client.clone(), ns);
+    let list_params = ListParams::default().labels(WORKLOAD_SELECTOR);
+    api.delete_collection(&DeleteParams::foreground(), &list_params)
+        .await?;
+
+    let retry_strategy = ExponentialBackoff::from_millis(1000).take(20);
+    Retry::spawn(retry_strategy, || async {
+        if api.list(&list_params).await?.items.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("expect deleted"))
+        }
+    })
+    .await
 }
 
 #[derive(Serialize)]
@@ -428,9 +783,176 @@ struct ClaimRenderParams {
 #[derive(Serialize)]
 struct NodeRenderParams<'a> {
     node: &'a ComputeUnit,
-    log_level: &'a str,
     db_url: &'a str,
     run_id: &'a str,
+    runner: &'a RunnerOptions,
+}
+
+#[derive(Serialize)]
+struct NetworkPolicyRenderParams<'a> {
+    node: &'a ComputeUnit,
+    up_nodes: Vec<&'a str>,
+    down_nodes: Vec<&'a str>,
+    egress: Vec<EgressRule>,
+}
+
+/// WorkloadResource is the workload running user container of a node
+enum WorkloadResource {
+    StatefulSet(StatefulSet),
+    Job(Job),
+}
+
+/// NodeResources is everything deploy create for a node
+struct NodeResources {
+    claim: PersistentVolumeClaim,
+    workload: WorkloadResource,
+    service: Service,
+    network_policy: Option<NetworkPolicy>,
+    node: Node,
+}
+
+/// render_node render resources and record of a node without calling the api server
+fn render_node(
+    reg: &Handlebars,
+    options: &KubeOptions,
+    run_id: &str,
+    graph: &Dag,
+    node: &ComputeUnit,
+    cur_tm: i64,
+) -> Result<NodeResources> {
+    if node.spec.command.is_empty() {
+        return Err(anyhow!("{} dont have command", &node.name));
+    }
+
+    let db_url = options.db_url.clone() + "/" + run_id;
+    let data_unit_render_args = NodeRenderParams {
+        node,
+        db_url: db_url.as_str(),
+        run_id,
+        runner: &options.runner,
+    };
+    let up_nodes = graph.get_incomming_nodes(&node.name);
+    let down_nodes = graph.get_outgoing_nodes(&node.name);
+
+    let claim_string = reg.render(
+        CLAIM_TPL,
+        &ClaimRenderParams {
+            storage: merge_storage_options(&options.storage, &node.spec.storage),
+            name: node.name.clone() + "-node-claim",
+        },
+    )?;
+    debug!("rendered clam string {}", claim_string);
+    let claim: PersistentVolumeClaim = serde_json::from_str(&claim_string)
+        .map_err(|err| anyhow!("render claim of {} {err}", node.name))?;
+
+    let workload = match node.spec.run_mode {
+        RunMode::Stream => {
+            let statefulset_string = reg.render(STATEFULSET_TPL, &data_unit_render_args)?;
+            debug!("rendered unit string {}", statefulset_string);
+            WorkloadResource::StatefulSet(
+                serde_json::from_str(&statefulset_string)
+                    .map_err(|err| anyhow!("render statefulset of {} {err}", node.name))?,
+            )
+        }
+        RunMode::Finite => {
+            let job_string = reg.render(JOB_TPL, &data_unit_render_args)?;
+            debug!("rendered unit job string {}", job_string);
+            WorkloadResource::Job(
+                serde_json::from_str(&job_string)
+                    .map_err(|err| anyhow!("render job of {} {err}", node.name))?,
+            )
+        }
+    };
+
+    // compute unit only receive data from channel
+    let outgoing_node_streams = down_nodes
+        .iter()
+        .map(|node_name| {
+            format!(
+                "http://{}-service.{}.svc.cluster.local:80",
+                node_name, run_id
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let node_record = Node {
+        node_name: node.name.clone(),
+        state: TrackerState::Init,
+        node_type: NodeType::CoputeUnit,
+        up_nodes: up_nodes.iter().map(|v| v.to_string()).collect(),
+        outgoing_streams: outgoing_node_streams,
+        ready_at: None,
+        finished_at: None,
+        created_at: cur_tm,
+        updated_at: cur_tm,
+    };
+
+    let service_string = reg.render(SERVICE_TPL, node)?;
+    debug!("rendered unit service config {}", service_string);
+    let service: Service = serde_json::from_str(service_string.as_str())
+        .map_err(|err| anyhow!("render service of {} {err}", node.name))?;
+
+    let network_policy = if options.network_policy {
+        let mut egress = db_egress_rules(&options.db_url)?;
+        egress.extend(node.spec.external_endpoints.iter().map(EgressRule::from));
+        let network_policy_string = reg.render(
+            NETWORK_POLICY_TPL,
+            &NetworkPolicyRenderParams {
+                node,
+                up_nodes,
+                down_nodes,
+                egress,
+            },
+        )?;
+        debug!("rendered network policy {}", network_policy_string);
+        Some(
+            serde_json::from_str(&network_policy_string)
+                .map_err(|err| anyhow!("render network policy of {} {err}", node.name))?,
+        )
+    } else {
+        None
+    };
+
+    Ok(NodeResources {
+        claim,
+        workload,
+        service,
+        network_policy,
+        node: node_record,
+    })
+}
+
+/// render_plan render all resources deploy would create for graph in order
+fn render_plan(reg: &Handlebars, options: &KubeOptions, run_id: &str, graph: &Dag) -> Result<Plan> {
+    let namespace = Namespace {
+        metadata: kube::api::ObjectMeta {
+            name: Some(run_id.to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut plan = Plan {
+        namespace: run_id.to_string(),
+        resources: vec![serde_json::to_value(&namespace)?],
+        nodes: vec![],
+    };
+
+    let cur_tm = Utc::now().timestamp();
+    for node in graph.iter() {
+        let resources = render_node(reg, options, run_id, graph, node, cur_tm)?;
+        plan.resources.push(serde_json::to_value(&resources.claim)?);
+        plan.resources.push(match &resources.workload {
+            WorkloadResource::StatefulSet(statefulset) => serde_json::to_value(statefulset)?,
+            WorkloadResource::Job(job) => serde_json::to_value(job)?,
+        });
+        plan.resources
+            .push(serde_json::to_value(&resources.service)?);
+        if let Some(network_policy) = resources.network_policy.as_ref() {
+            plan.resources.push(serde_json::to_value(network_policy)?);
+        }
+        plan.nodes.push(resources.node);
+    }
+    Ok(plan)
 }
 
 impl<R> Driver for KubeDriver<R>
@@ -443,100 +965,99 @@ where
         run_id: &str,
         graph: &Dag,
     ) -> Result<KubePipelineController<MongoRunDbRepo>> {
-        Self::ensure_namespace_exit_and_clean(&self.client, run_id).await?;
-
         let db_url = self.options.db_url.clone() + "/" + run_id;
         let repo = MongoRunDbRepo::new(db_url.as_str())
             .await
             .map_err(|err| anyhow!("create database fail {err}"))?;
+
+        // resume from persisted state if job was deployed before, keep claims for cached data
+        let is_resume = repo.get_global_state().await.is_ok();
+        if is_resume {
+            Self::ensure_namespace_exit_and_clean_workloads(&self.client, run_id).await?;
+        } else {
+            Self::ensure_namespace_exit_and_clean(&self.client, run_id).await?;
+        }
+
         let statefulset_api: Api<StatefulSet> = Api::namespaced(self.client.clone(), run_id);
+        let job_api: Api<Job> = Api::namespaced(self.client.clone(), run_id);
         let claim_api: Api<PersistentVolumeClaim> = Api::namespaced(self.client.clone(), run_id);
         let service_api: Api<Service> = Api::namespaced(self.client.clone(), run_id);
+        let network_policy_api: Api<NetworkPolicy> = Api::namespaced(self.client.clone(), run_id);
 
         // insert global record
         let cur_tm = Utc::now().timestamp();
-        let graph_record = Graph {
-            graph_json: graph.raw.clone(),
-            created_at: cur_tm,
-            updated_at: cur_tm,
-        };
-        repo.insert_global_state(&graph_record).await?;
+        if is_resume {
+            let node_count = repo.reset_unfinished_nodes().await?;
+            let data_count = repo.revert_in_flight().await?;
+            info!("resume job {run_id}, reset {node_count} nodes and {data_count} data");
+        } else {
+            let graph_record = Graph {
+                graph_json: graph.raw.clone(),
+                created_at: cur_tm,
+                updated_at: cur_tm,
+            };
+            repo.insert_global_state(&graph_record).await?;
+        }
         let topo_sort_nodes = graph.topo_sort_nodes();
         let mut pipeline_ctl =
             KubePipelineController::new(repo.clone(), self.client.clone(), topo_sort_nodes);
         for node in graph.iter() {
-            if node.spec.command.is_empty() {
-                return Err(anyhow!("{} dont have command", &node.name));
-            }
+            let resources = render_node(&self.reg, &self.options, run_id, graph, node, cur_tm)?;
 
-            let data_unit_render_args = NodeRenderParams {
-                node,
-                db_url: db_url.as_str(),
-                log_level: "debug",
-                run_id,
+            // apply nodes, reuse claim created by last deploy when resume
+            let claim_name = resources.claim.name().expect("set name in template");
+            let existing_claim = if is_resume {
+                claim_api.get_opt(&claim_name).await?
+            } else {
+                None
+            };
+            let claim_deployment = match existing_claim {
+                Some(claim) => claim,
+                None => {
+                    claim_api
+                        .create(&PostParams::default(), &resources.claim)
+                        .await?
+                }
             };
-            let up_nodes = graph.get_incomming_nodes(&node.name);
-            let down_nodes = graph.get_outgoing_nodes(&node.name);
-
-            // apply nodes
-            let claim_string = self.reg.render(
-                "claim",
-                &ClaimRenderParams {
-                    storage: merge_storage_options(&self.options.storage, &node.spec.storage),
-                    name: node.name.clone() + "-node-claim",
-                },
-            )?;
-            debug!("rendered clam string {}", claim_string);
-            let claim: PersistentVolumeClaim = serde_json::from_str(&claim_string)?;
-            let claim_deployment = claim_api.create(&PostParams::default(), &claim).await?;
-
-            let statefulset_string = self.reg.render("statefulset", &data_unit_render_args)?;
-            debug!("rendered unit string {}", statefulset_string);
-
-            let unit_statefulset: StatefulSet = serde_json::from_str(&statefulset_string)?;
-            let unit_statefulset = statefulset_api
-                .create(&PostParams::default(), &unit_statefulset)
-                .await?;
 
-            // compute unit only receive data from channel
-            let outgoing_node_streams = down_nodes
-                .iter()
-                .map(|node_name| {
-                    format!(
-                        "http://{}-service.{}.svc.cluster.local:80",
-                        node_name, run_id
+            let workload = match resources.workload {
+                WorkloadResource::StatefulSet(unit_statefulset) => {
+                    let unit_statefulset = statefulset_api
+                        .create(&PostParams::default(), &unit_statefulset)
+                        .await?;
+                    Workload::StatefulSet(
+                        unit_statefulset
+                            .name()
+                            .expect("set name in template")
+                            .to_string(),
                     )
-                })
-                .collect::<Vec<_>>();
-
-            let node_record = Node {
-                node_name: node.name.clone(),
-                state: TrackerState::Init,
-                node_type: NodeType::CoputeUnit,
-                up_nodes: up_nodes.iter().map(|v| v.to_string()).collect(),
-                outgoing_streams: outgoing_node_streams,
-                created_at: cur_tm,
-                updated_at: cur_tm,
+                }
+                WorkloadResource::Job(unit_job) => {
+                    let unit_job = job_api.create(&PostParams::default(), &unit_job).await?;
+                    Workload::Job(unit_job.name().expect("set name in template").to_string())
+                }
             };
 
-            repo.insert_node(&node_record).await?;
-
-            let service_string = self.reg.render("service", node)?;
-            debug!("rendered unit service config {}", service_string);
+            //node may not be recorded if last deploy failed halfway
+            if !is_resume || repo.get_node_by_name(&node.name).await.is_err() {
+                repo.insert_node(&resources.node).await?;
+            }
 
-            let unit_service: Service = serde_json::from_str(service_string.as_str())?;
             let unit_service = service_api
-                .create(&PostParams::default(), &unit_service)
+                .create(&PostParams::default(), &resources.service)
                 .await?;
 
+            if let Some(network_policy) = resources.network_policy.as_ref() {
+                network_policy_api
+                    .create(&PostParams::default(), network_policy)
+                    .await?;
+            }
+
             let handler = KubeHandler {
                 node_name: node.name.clone(),
                 client: self.client.clone(),
                 namespace: run_id.to_string().clone(),
-                stateset_name: unit_statefulset
-                    .name()
-                    .expect("set name in template")
-                    .to_string(),
+                workload,
                 claim_name: claim_deployment
                     .name()
                     .expect("set name in template")
@@ -553,6 +1074,10 @@ where
         Ok(pipeline_ctl)
     }
 
+    fn plan(&self, run_id: &str, graph: &Dag) -> Result<Plan> {
+        render_plan(&self.reg, &self.options, run_id, graph)
+    }
+
     #[allow(refining_impl_trait)]
     async fn attach(
         &self,
@@ -564,6 +1089,7 @@ where
             .await
             .map_err(|err| anyhow!("create database fail {err}"))?;
         let statefulset_api: Api<StatefulSet> = Api::namespaced(self.client.clone(), run_id);
+        let job_api: Api<Job> = Api::namespaced(self.client.clone(), run_id);
         let claim_api: Api<PersistentVolumeClaim> = Api::namespaced(self.client.clone(), run_id);
         let service_api: Api<Service> = Api::namespaced(self.client.clone(), run_id);
 
@@ -575,9 +1101,23 @@ where
             let claim_deployment = claim_api
                 .get((node.name.clone() + "-node-claim").as_str())
                 .await?;
-            let unit_statefulset = statefulset_api
-                .get((node.name.clone() + "-statefulset").as_str())
-                .await?;
+            let workload = match node.spec.run_mode {
+                RunMode::Stream => {
+                    let unit_statefulset = statefulset_api
+                        .get((node.name.clone() + "-statefulset").as_str())
+                        .await?;
+                    Workload::StatefulSet(
+                        unit_statefulset
+                            .name()
+                            .expect("set name in template")
+                            .to_string(),
+                    )
+                }
+                RunMode::Finite => {
+                    let unit_job = job_api.get((node.name.clone() + "-job").as_str()).await?;
+                    Workload::Job(unit_job.name().expect("set name in template").to_string())
+                }
+            };
 
             let unit_service = service_api
                 .get((node.name.clone() + "-service").as_str())
@@ -587,10 +1127,7 @@ where
                 node_name: node.name.clone(),
                 client: self.client.clone(),
                 namespace: run_id.to_string().clone(),
-                stateset_name: unit_statefulset
-                    .name()
-                    .expect("set name in template")
-                    .to_string(),
+                workload,
                 claim_name: claim_deployment
                     .name()
                     .expect("set name in template")
@@ -625,9 +1162,16 @@ where
 mod tests {
     use super::*;
     use crate::dbrepo::MongoRunDbRepo;
+    use k8s_openapi::{
+        api::core::v1::Probe,
+        apimachinery::pkg::util::intstr::IntOrString,
+    };
     use local_ip_address::local_ip;
     use mongodb::Client as MongoClient;
-    use std::env;
+    use std::{
+        env,
+        str::FromStr,
+    };
     use tracing_subscriber;
 
     #[tokio::test]
@@ -692,4 +1236,405 @@ mod tests {
         kube_driver.deploy("ntest", &dag).await.unwrap();
         //    kube_driver.clean("ntest").await.unwrap();
     }
+
+    #[test]
+    fn test_render_runner_options() {
+        let options = KubeOptions::default()
+            .set_runner_image("registry.local/compute_unit_runner:v0.1.0")
+            .set_pull_policy(PullPolicy::Always)
+            .set_image_pull_secrets(vec!["regcred".to_string(), "backup".to_string()])
+            .set_runner_log_level("info")
+            .set_runner_args(vec![
+                "--buf-size=10".to_string(),
+                r#"--label="a\b""#.to_string(),
+            ]);
+        let reg = new_registry(None).unwrap();
+        validate_templates(&reg, &options).unwrap();
+
+        let node = ComputeUnit {
+            name: "node-a".to_string(),
+            spec: MachineSpec {
+                image: "user:latest".to_string(),
+                command: "/user".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let rendered = reg
+            .render(
+                STATEFULSET_TPL,
+                &NodeRenderParams {
+                    node: &node,
+                    db_url: "mongodb://localhost:27017/run",
+                    run_id: "run",
+                    runner: &options.runner,
+                },
+            )
+            .unwrap();
+        let statefulset: StatefulSet = serde_json::from_str(&rendered).unwrap();
+        let pod_spec = statefulset.spec.unwrap().template.spec.unwrap();
+
+        let secrets: Vec<_> = pod_spec
+            .image_pull_secrets
+            .unwrap()
+            .into_iter()
+            .filter_map(|secret| secret.name)
+            .collect();
+        assert_eq!(secrets, vec!["regcred", "backup"]);
+
+        let runner = &pod_spec.containers[0];
+        assert_eq!(
+            runner.image.as_deref(),
+            Some("registry.local/compute_unit_runner:v0.1.0")
+        );
+        let probe_path = |probe: &Option<Probe>| {
+            let http_get = probe.as_ref().unwrap().http_get.as_ref().unwrap();
+            assert_eq!(http_get.port, IntOrString::String("metrics".to_string()));
+            http_get.path.clone()
+        };
+        assert_eq!(
+            probe_path(&runner.liveness_probe).as_deref(),
+            Some("/healthz")
+        );
+        assert_eq!(
+            probe_path(&runner.readiness_probe).as_deref(),
+            Some("/readyz")
+        );
+        assert_eq!(runner.image_pull_policy.as_deref(), Some("Always"));
+        let args = runner.args.as_ref().unwrap();
+        assert!(args.contains(&"--log-level=info".to_string()));
+        assert_eq!(
+            &args[args.len() - 2..],
+            &["--buf-size=10".to_string(), r#"--label="a\b""#.to_string()]
+        );
+        assert_eq!(
+            pod_spec.containers[1].image_pull_policy.as_deref(),
+            Some("Always")
+        );
+    }
+
+    #[test]
+    fn test_parse_pull_policy() {
+        assert_eq!(
+            PullPolicy::from_str("IfNotPresent").unwrap(),
+            PullPolicy::IfNotPresent
+        );
+        assert!(PullPolicy::from_str("always").is_err());
+        assert!(PullPolicy::from_str("Always\", \"x").is_err());
+    }
+
+    #[test]
+    fn test_template_dir_override() {
+        let dir = env::temp_dir().join(format!("jz-flow-tpl-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("service.tpl"),
+            include_str!("kubetpl/service.tpl").replace("\"port\": 80", "\"port\": 8080"),
+        )
+        .unwrap();
+
+        let reg = new_registry(dir.to_str()).unwrap();
+        validate_templates(&reg, &KubeOptions::default()).unwrap();
+        let node = ComputeUnit {
+            name: "node-a".to_string(),
+            ..Default::default()
+        };
+        let service: Service =
+            serde_json::from_str(&reg.render(SERVICE_TPL, &node).unwrap()).unwrap();
+        assert_eq!(service.spec.unwrap().ports.unwrap()[0].port, 8080);
+
+        std::fs::write(dir.join("claim.tpl"), "{ \"kind\": ").unwrap();
+        let reg = new_registry(dir.to_str()).unwrap();
+        assert!(validate_templates(&reg, &KubeOptions::default()).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_job_tracker_state() {
+        let job: Job = serde_json::from_str(
+            r#"{"status": {"conditions": [{"type": "Complete", "status": "True"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(job_tracker_state(&job), Some(TrackerState::Finish));
+
+        let job: Job = serde_json::from_str(
+            r#"{"status": {"conditions": [{"type": "Failed", "status": "True"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(job_tracker_state(&job), Some(TrackerState::Error));
+
+        let job: Job = serde_json::from_str(r#"{"status": {"active": 1}}"#).unwrap();
+        assert_eq!(job_tracker_state(&job), None);
+    }
+
+    #[test]
+    fn test_render_finite_node() {
+        let options = KubeOptions::default();
+        let reg = new_registry(None).unwrap();
+        let node = ComputeUnit {
+            name: "source".to_string(),
+            spec: MachineSpec {
+                image: "source:latest".to_string(),
+                command: "/source".to_string(),
+                replicas: 2,
+                run_mode: RunMode::Finite,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let rendered = reg
+            .render(
+                JOB_TPL,
+                &NodeRenderParams {
+                    node: &node,
+                    db_url: "mongodb://localhost:27017/run",
+                    run_id: "run",
+                    runner: &options.runner,
+                },
+            )
+            .unwrap();
+        let job: Job = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(job.metadata.name.as_deref(), Some("source-job"));
+
+        let spec = job.spec.unwrap();
+        assert_eq!(spec.completions, Some(2));
+        let pod_spec = spec.template.spec.unwrap();
+        assert_eq!(pod_spec.restart_policy.as_deref(), Some("OnFailure"));
+        let runner = &pod_spec.containers[0];
+        for (probe, path) in [
+            (&runner.liveness_probe, "/healthz"),
+            (&runner.readiness_probe, "/readyz"),
+        ] {
+            let http_get = probe.as_ref().unwrap().http_get.as_ref().unwrap();
+            assert_eq!(http_get.path.as_deref(), Some(path));
+            assert_eq!(http_get.port, IntOrString::String("metrics".to_string()));
+        }
+        for container in pod_spec.containers {
+            let run_mode = container
+                .env
+                .unwrap()
+                .into_iter()
+                .find(|env| env.name == "RUN_MODE")
+                .and_then(|env| env.value);
+            assert_eq!(run_mode.as_deref(), Some("finite"));
+        }
+    }
+    #[test]
+    fn test_render_plan() {
+        let json_str = r#"
+        {
+          "name": "example",
+          "dag": [
+            {
+              "name": "source",
+              "spec": {"image": "source:latest", "command": "/source", "run_mode": "finite"}
+            },
+            {
+              "name": "sink",
+              "dependency": ["source"],
+              "spec": {"image": "sink:latest", "command": "/sink", "storage": {"capacity": "10Gi"}}
+            }
+          ]
+        }"#;
+        let dag = Dag::from_json(json_str).unwrap();
+        let reg = new_registry(None).unwrap();
+        let options = KubeOptions::default()
+            .set_db_url("mongodb://10.0.0.5:27017")
+            .set_network_policy(true);
+
+        let plan = render_plan(&reg, &options, "run", &dag).unwrap();
+        let kinds: Vec<_> = plan
+            .resources
+            .iter()
+            .map(|resource| resource["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "Namespace",
+                "PersistentVolumeClaim",
+                "Job",
+                "Service",
+                "NetworkPolicy",
+                "PersistentVolumeClaim",
+                "StatefulSet",
+                "Service",
+                "NetworkPolicy",
+            ]
+        );
+        assert_eq!(
+            plan.resources[5]["spec"]["resources"]["requests"]["storage"],
+            "10Gi"
+        );
+        assert_eq!(plan.nodes[1].up_nodes, vec!["source"]);
+        assert_eq!(
+            plan.nodes[0].outgoing_streams,
+            vec!["http://sink-service.run.svc.cluster.local:80"]
+        );
+
+        //rendering error surface without a cluster
+        let dag = Dag::from_json(
+            r#"{"name": "example", "dag": [{"name": "source", "spec": {"image": "source:latest"}}]}"#,
+        )
+        .unwrap();
+        assert!(render_plan(&reg, &options, "run", &dag).is_err());
+    }
+
+    #[test]
+    fn test_db_egress_rules() {
+        assert_eq!(
+            db_egress_rules("mongodb://10.0.0.5:27018,mongo.default.svc").unwrap(),
+            vec![
+                EgressRule {
+                    cidr: Some("10.0.0.5/32".to_string()),
+                    ports: vec![27018],
+                },
+                EgressRule {
+                    cidr: None,
+                    ports: vec![27017],
+                }
+            ]
+        );
+        assert!(db_egress_rules("localhost:27017").is_err());
+    }
+
+    #[test]
+    fn test_render_network_policy() {
+        let json_str = r#"
+        {
+          "name": "example",
+          "dag": [
+            {
+              "name": "source",
+              "spec": {"image": "source:latest", "command": "/source"}
+            },
+            {
+              "name": "transform",
+              "dependency": ["source"],
+              "spec": {
+                "image": "transform:latest",
+                "command": "/transform",
+                "external_endpoints": [{"cidr": "192.168.1.0/24", "ports": [8000]}]
+              }
+            },
+            {
+              "name": "sink",
+              "dependency": ["transform"],
+              "spec": {"image": "sink:latest", "command": "/sink"}
+            }
+          ]
+        }"#;
+        let dag = Dag::from_json(json_str).unwrap();
+        let reg = new_registry(None).unwrap();
+        let render = |name: &str| -> NetworkPolicy {
+            let node = dag.get_node(name).unwrap();
+            let mut egress = db_egress_rules("mongodb://10.0.0.5:27017").unwrap();
+            egress.extend(node.spec.external_endpoints.iter().map(EgressRule::from));
+            let rendered = reg
+                .render(
+                    NETWORK_POLICY_TPL,
+                    &NetworkPolicyRenderParams {
+                        node,
+                        up_nodes: dag.get_incomming_nodes(name),
+                        down_nodes: dag.get_outgoing_nodes(name),
+                        egress,
+                    },
+                )
+                .unwrap();
+            serde_json::from_str(&rendered).unwrap()
+        };
+        let pod_app = |peer: &k8s_openapi::api::networking::v1::NetworkPolicyPeer| {
+            peer.pod_selector
+                .as_ref()
+                .unwrap()
+                .match_labels
+                .as_ref()
+                .unwrap()["app"]
+                .clone()
+        };
+
+        let spec = render("transform").spec.unwrap();
+        assert_eq!(
+            spec.pod_selector.match_labels.unwrap()["app"],
+            "transform-pod"
+        );
+        let ingress = spec.ingress.unwrap();
+        //upstream, metrics scrape
+        assert_eq!(ingress.len(), 2);
+        let from: Vec<_> = ingress[0]
+            .from
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(pod_app)
+            .collect();
+        assert_eq!(from, vec!["source-pod"]);
+        assert!(ingress[1].from.is_none());
+        assert_eq!(
+            ingress[1].ports.as_ref().unwrap()[0].port,
+            Some(IntOrString::Int(9090))
+        );
+
+        let egress = spec.egress.unwrap();
+        //dns, downstream, database, external endpoint
+        assert_eq!(egress.len(), 4);
+        let to: Vec<_> = egress[1].to.as_ref().unwrap().iter().map(pod_app).collect();
+        assert_eq!(to, vec!["sink-pod"]);
+        let ip_block = |index: usize| {
+            egress[index].to.as_ref().unwrap()[0]
+                .ip_block
+                .as_ref()
+                .unwrap()
+                .cidr
+                .clone()
+        };
+        assert_eq!(ip_block(2), "10.0.0.5/32");
+        assert_eq!(ip_block(3), "192.168.1.0/24");
+        let port = |index: usize| egress[index].ports.as_ref().unwrap()[0].port.clone();
+        assert_eq!(port(3), Some(IntOrString::Int(8000)));
+
+        //source node accept only metrics scrape and sink node send to nobody
+        let spec = render("source").spec.unwrap();
+        assert_eq!(spec.ingress.unwrap().len(), 1);
+        let spec = render("sink").spec.unwrap();
+        assert_eq!(spec.egress.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_resume_keeps_claims() {
+        let options = KubeOptions::default()
+            .set_db_url("mongodb://10.0.0.5:27017")
+            .set_network_policy(true);
+        let reg = new_registry(None).unwrap();
+        let dag = Dag::from_json(
+            r#"{"name": "example", "dag": [
+                {"name": "source", "spec": {"image": "source:latest", "command": "/source"}},
+                {"name": "sink", "dependency": ["source"],
+                 "spec": {"image": "sink:latest", "command": "/sink", "run_mode": "finite"}}
+            ]}"#,
+        )
+        .unwrap();
+        let (key, value) = WORKLOAD_SELECTOR.split_once('=').unwrap();
+        let selected = |labels: Option<&std::collections::BTreeMap<String, String>>| {
+            labels
+                .and_then(|labels| labels.get(key))
+                .map(String::as_str)
+                == Some(value)
+        };
+
+        for node in dag.iter() {
+            let resources = render_node(&reg, &options, "run", &dag, node, 0).unwrap();
+            //resume delete everything else of node but claim
+            assert!(!selected(resources.claim.metadata.labels.as_ref()));
+            let workload_labels = match &resources.workload {
+                WorkloadResource::StatefulSet(statefulset) => statefulset.metadata.labels.as_ref(),
+                WorkloadResource::Job(job) => job.metadata.labels.as_ref(),
+            };
+            assert!(selected(workload_labels));
+            assert!(selected(resources.service.metadata.labels.as_ref()));
+            assert!(selected(
+                resources.network_policy.unwrap().metadata.labels.as_ref()
+            ));
+        }
+    }
 }
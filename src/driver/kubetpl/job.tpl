@@ -0,0 +1,134 @@
+{
+  "apiVersion": "batch/v1",
+  "kind": "Job",
+  "metadata": {
+    "name": "{{{node.name}}}-job",
+    "labels": {
+      "exec-type": "compute-unit"
+    }
+  },
+  "spec": {
+    "parallelism": {{{node.spec.replicas}}},
+    "completions": {{{node.spec.replicas}}},
+    "backoffLimit": 6,
+    "template": {
+      "metadata": {
+        "labels": {
+          "app": "{{{node.name}}}-pod"
+        }
+      },
+      "spec": {
+        "restartPolicy": "OnFailure",
+        "imagePullSecrets": [
+          {{#each runner.image_pull_secrets}}{{#unless @first}},{{/unless}}{"name": "{{{json_escape this}}}"}{{/each}}
+        ],
+        "containers": [
+          {
+            "name": "compute-data-unit",
+            "image": "{{{json_escape runner.image}}}",
+            "command": [
+              "/compute_unit_runner"
+            ],
+            "args": [
+              "--node-name={{{node.name}}}",
+              "--log-level={{{json_escape runner.log_level}}}",
+              "--mongo-url={{{json_escape db_url}}}"
+               {{#if (eq node.spec.cache_type "Disk") }},"--tmp-path=/app/tmp"{{/if}}
+               {{#each runner.args}},"{{{json_escape this}}}"{{/each}}
+            ],
+            "imagePullPolicy": "{{{runner.pull_policy}}}",
+            "ports": [
+              {
+                "name": "data",
+                "containerPort": 80
+              },
+              {
+                "name": "metrics",
+                "containerPort": 9090
+              }
+            ],
+            "livenessProbe": {
+              "httpGet": {
+                "path": "/healthz",
+                "port": "metrics"
+              },
+              "initialDelaySeconds": 10,
+              "periodSeconds": 10,
+              "timeoutSeconds": 5,
+              "failureThreshold": 3
+            },
+            "readinessProbe": {
+              "httpGet": {
+                "path": "/readyz",
+                "port": "metrics"
+              },
+              "periodSeconds": 5,
+              "timeoutSeconds": 5
+            },
+            "env": [
+              {
+                "name": "MACHINE_NAME",
+                "valueFrom": {
+                  "fieldRef": {
+                    "fieldPath": "metadata.name"
+                  }
+                }
+              },
+              {
+                "name": "RUN_MODE",
+                "value": "finite"
+              }
+            ],
+            "volumeMounts": [
+              {
+                "mountPath": "/unix_socket",
+                "name": "unix-socket"
+              },
+              {
+                "mountPath": "/app/tmp",
+                "name": "tmpstore"
+              }
+            ]
+          },
+          {
+            "name": "compute-user-unit",
+            "image": "{{{json_escape node.spec.image}}}",
+            "command": [
+              "{{{json_escape node.spec.command}}}"
+            ],
+            "imagePullPolicy": "{{{runner.pull_policy}}}",
+            "args": [{{{join_array node.spec.args}}}],
+            "env": [
+              {
+                "name": "RUN_MODE",
+                "value": "finite"
+              }
+            ],
+            "volumeMounts": [
+              {
+                "mountPath": "/unix_socket",
+                "name": "unix-socket"
+              },
+              {
+                "mountPath": "/app/tmp",
+                "name": "tmpstore"
+              }
+            ]
+          }
+        ],
+        "volumes": [
+          {
+            "name": "unix-socket",
+            "emptyDir": {}
+          },
+          {
+            "name": "tmpstore",
+            "persistentVolumeClaim": {
+              "claimName": "{{{node.name}}}-node-claim"
+            }
+          }
+        ]
+      }
+    }
+  }
+}
@@ -0,0 +1,79 @@
+{
+  "apiVersion": "networking.k8s.io/v1",
+  "kind": "NetworkPolicy",
+  "metadata": {
+    "name": "{{{node.name}}}-network-policy",
+    "labels": {
+      "exec-type": "compute-unit"
+    }
+  },
+  "spec": {
+    "podSelector": {
+      "matchLabels": {
+        "app": "{{{node.name}}}-pod"
+      }
+    },
+    "policyTypes": [
+      "Ingress",
+      "Egress"
+    ],
+    "ingress": [
+      {{#if up_nodes}}
+      {
+        "from": [
+          {{#each up_nodes}}{{#unless @first}},{{/unless}}{"podSelector": {"matchLabels": {"app": "{{{this}}}-pod"}}}{{/each}}
+        ],
+        "ports": [
+          {
+            "protocol": "TCP",
+            "port": 80
+          }
+        ]
+      },
+      {{/if}}
+      {
+        "ports": [
+          {
+            "protocol": "TCP",
+            "port": 9090
+          }
+        ]
+      }
+    ],
+    "egress": [
+      {
+        "ports": [
+          {
+            "protocol": "UDP",
+            "port": 53
+          },
+          {
+            "protocol": "TCP",
+            "port": 53
+          }
+        ]
+      }
+      {{#if down_nodes}}
+      ,{
+        "to": [
+          {{#each down_nodes}}{{#unless @first}},{{/unless}}{"podSelector": {"matchLabels": {"app": "{{{this}}}-pod"}}}{{/each}}
+        ],
+        "ports": [
+          {
+            "protocol": "TCP",
+            "port": 80
+          }
+        ]
+      }
+      {{/if}}
+      {{#each egress}}
+      ,{
+        {{#if cidr}}"to": [{"ipBlock": {"cidr": "{{{cidr}}}"}}],{{/if}}
+        "ports": [
+          {{#each ports}}{{#unless @first}},{{/unless}}{"protocol": "TCP", "port": {{this}}}{{/each}}
+        ]
+      }
+      {{/each}}
+    ]
+  }
+}
@@ -3,7 +3,9 @@
   "kind": "Service",
   "metadata": {
     "name": "{{{name}}}-service",
-    "exec-type": "compute-unit"
+    "labels": {
+      "exec-type": "compute-unit"
+    }
   },
   "spec": {
     "selector": {
@@ -11,8 +13,14 @@
     },
     "ports": [
       {
+        "name": "data",
         "port": 80,
         "targetPort": 80
+      },
+      {
+        "name": "metrics",
+        "port": 9090,
+        "targetPort": 9090
       }
     ]
   }
@@ -9,6 +9,7 @@
   },
   "spec": {
     "serviceName": "{{{node.name}}}-service",
+    "podManagementPolicy": "Parallel",
     "replicas": {{{node.spec.replicas}}},
     "selector": {
       "matchLabels": {
@@ -22,25 +23,52 @@
         }
       },
       "spec": {
+        "imagePullSecrets": [
+          {{#each runner.image_pull_secrets}}{{#unless @first}},{{/unless}}{"name": "{{{json_escape this}}}"}{{/each}}
+        ],
         "containers": [
           {
             "name": "compute-data-unit",
-            "image": "gitdatateam/compute_unit_runner:latest",
+            "image": "{{{json_escape runner.image}}}",
             "command": [
               "/compute_unit_runner"
             ],
             "args": [
               "--node-name={{{node.name}}}",
-              "--log-level={{{log_level}}}",
-              "--mongo-url={{{db_url}}}"
+              "--log-level={{{json_escape runner.log_level}}}",
+              "--mongo-url={{{json_escape db_url}}}"
                {{#if (eq node.spec.cache_type "Disk") }},"--tmp-path=/app/tmp"{{/if}}
+               {{#each runner.args}},"{{{json_escape this}}}"{{/each}}
             ],
-            "imagePullPolicy": "IfNotPresent",
+            "imagePullPolicy": "{{{runner.pull_policy}}}",
             "ports": [
               {
+                "name": "data",
                 "containerPort": 80
+              },
+              {
+                "name": "metrics",
+                "containerPort": 9090
               }
             ],
+            "livenessProbe": {
+              "httpGet": {
+                "path": "/healthz",
+                "port": "metrics"
+              },
+              "initialDelaySeconds": 10,
+              "periodSeconds": 10,
+              "timeoutSeconds": 5,
+              "failureThreshold": 3
+            },
+            "readinessProbe": {
+              "httpGet": {
+                "path": "/readyz",
+                "port": "metrics"
+              },
+              "periodSeconds": 5,
+              "timeoutSeconds": 5
+            },
             "env": [
               {
                 "name": "MACHINE_NAME",
@@ -64,11 +92,11 @@
           },
           {
             "name": "compute-user-unit",
-            "image": "{{{node.spec.image}}}",
+            "image": "{{{json_escape node.spec.image}}}",
             "command": [
-              "{{{node.spec.command}}}"
+              "{{{json_escape node.spec.command}}}"
             ],
-            "imagePullPolicy": "IfNotPresent",
+            "imagePullPolicy": "{{{runner.pull_policy}}}",
             "args": [{{{join_array node.spec.args}}}],
             "volumeMounts": [
               {
@@ -1,10 +1,14 @@
 pub mod kube;
 
 use crate::{
-    core::db::TrackerState,
+    core::db::{
+        Node,
+        TrackerState,
+    },
     dag::Dag,
 };
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::{
     Deserialize,
     Serialize,
@@ -14,14 +18,14 @@ use std::{
     future::Future,
 };
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct PodStauts {
     pub state: String,
     pub cpu_usage: f64,
     pub memory_usage: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct NodeStatus {
     pub name: String,
     pub state: TrackerState,
@@ -31,10 +35,24 @@ pub struct NodeStatus {
     pub pods: HashMap<String, PodStauts>,
 }
 
-pub trait UnitHandler: Send {
+/// Plan is what deploy would create for a graph, rendered without touching the cluster
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Plan {
+    pub namespace: String,
+    /// cluster objects in the order they are created
+    pub resources: Vec<serde_json::Value>,
+    /// node records inserted into job database
+    pub nodes: Vec<Node>,
+}
+
+pub trait UnitHandler: Send + Sync {
     fn name(&self) -> &str;
 
     fn start(&self) -> impl std::future::Future<Output = Result<()>> + Send;
+
+    //state reported by cluster for finite node, None if the node is still running
+    fn workload_state(&self) -> impl Future<Output = Result<Option<TrackerState>>> + Send;
+
     //pause graph running for now
     fn status(&self) -> impl Future<Output = Result<NodeStatus>> + Send;
 
@@ -71,6 +89,9 @@ pub trait Driver: 'static + Clone + Send + Sync {
         graph: &Dag,
     ) -> impl Future<Output = Result<impl PipelineController>> + Send;
 
+    //render what deploy would create for graph without applying it
+    fn plan(&self, namespace: &str, graph: &Dag) -> Result<Plan>;
+
     //attach cluster in cloud with graph
     fn attach(
         &self,
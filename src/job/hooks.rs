@@ -0,0 +1,455 @@
+use crate::{
+    core::db::{
+        DeliveryState,
+        Hook,
+        HookDelivery,
+        HookDeliveryUpdateInfo,
+        HookEvent,
+        MainDbRepo,
+    },
+    job::{
+        leader::{
+            Leadership,
+            DEFAULT_INSTANCE,
+        },
+        metrics::DaemonMetrics,
+    },
+    utils::StdIntoAnyhowResult,
+};
+use anyhow::{
+    anyhow,
+    Result,
+};
+use chrono::Utc;
+use hmac::{
+    Hmac,
+    Mac,
+};
+use mongodb::bson::oid::ObjectId;
+use reqwest::{
+    Client,
+    Url,
+};
+use sha2::Sha256;
+use std::{
+    collections::{
+        hash_map::Entry,
+        HashMap,
+    },
+    hash::Hash,
+    process::Stdio,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use tokio::{
+    io::AsyncWriteExt,
+    process::Command,
+    select,
+    task::JoinSet,
+    time::{
+        sleep,
+        timeout,
+    },
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{
+    error,
+    info,
+    warn,
+};
+
+/// header carry hmac-sha256 signature of webhook body
+pub const SIGNATURE_HEADER: &str = "X-Jiaoziflow-Signature";
+
+/// delivery is marked failed after this number of attempts
+const MAX_ATTEMPTS: u32 = 8;
+
+/// time limit of a webhook request or a command hook
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// max number of jobs whose deliveries are sent at the same time
+const MAX_CONCURRENT_JOBS: usize = 16;
+
+/// WebhookPolicy restrict urls of webhooks set on jobs, otherwise submitters could make daemon
+/// post to any address reachable from it
+#[derive(Debug, Clone, Default)]
+pub struct WebhookPolicy {
+    allowed_hosts: Vec<String>,
+}
+
+impl WebhookPolicy {
+    /// add_allowed_host allow webhooks to host, eg. hooks.example.com
+    pub fn add_allowed_host(mut self, host: &str) -> Self {
+        self.allowed_hosts.push(host.to_ascii_lowercase());
+        self
+    }
+
+    /// check fail unless url is http or https, and its host is allowed or trusted is true
+    pub fn check(&self, url: &str, trusted: bool) -> Result<()> {
+        let parsed = Url::parse(url).map_err(|err| anyhow!("webhook {url} {err}"))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(anyhow!("webhook {url} must be http or https"));
+        }
+        let host = parsed.host_str().unwrap_or_default();
+        if !trusted && !self.allowed_hosts.iter().any(|allowed| allowed == host) {
+            return Err(anyhow!("webhook host {host} is not allowed by daemon"));
+        }
+        Ok(())
+    }
+}
+
+/// sign return hex encoded hmac-sha256 of body
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accept any key size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// backoff return seconds to wait before next attempt, doubled after every failed attempt
+fn backoff(attempts: u32) -> i64 {
+    5 * 2i64.pow(attempts.saturating_sub(1).min(10))
+}
+
+/// deliver send event to hook once
+pub async fn deliver(client: &Client, hook: &Hook, event: &HookEvent) -> Result<()> {
+    let body = serde_json::to_vec(event)?;
+    match hook {
+        Hook::Webhook { url, secret } => {
+            let mut request = client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .timeout(DELIVERY_TIMEOUT);
+            if let Some(secret) = secret.as_ref() {
+                request =
+                    request.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, &body)));
+            }
+            let resp = request.body(body).send().await.anyhow()?;
+            if !resp.status().is_success() {
+                return Err(anyhow!("webhook {url} response {}", resp.status()));
+            }
+            Ok(())
+        }
+        Hook::Command { program, args } => {
+            let mut child = Command::new(program)
+                .args(args)
+                .env("JIAOZIFLOW_EVENT", String::from_utf8_lossy(&body).as_ref())
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .kill_on_drop(true)
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(&body).await?;
+            }
+            let status = timeout(DELIVERY_TIMEOUT, child.wait())
+                .await
+                .map_err(|_| anyhow!("command {program} timeout"))??;
+            if !status.success() {
+                return Err(anyhow!("command {program} exit with {status}"));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// group_by split items by key, keeping their order in every group
+fn group_by<T, K>(items: Vec<T>, key: impl Fn(&T) -> K) -> Vec<Vec<T>>
+where
+    K: Eq + Hash,
+{
+    let mut groups: Vec<Vec<T>> = vec![];
+    let mut index: HashMap<K, usize> = HashMap::new();
+    for item in items {
+        match index.entry(key(&item)) {
+            Entry::Occupied(entry) => groups[*entry.get()].push(item),
+            Entry::Vacant(entry) => {
+                entry.insert(groups.len());
+                groups.push(vec![item]);
+            }
+        }
+    }
+    groups
+}
+
+/// enqueue record deliveries of event to every hook, an event_key is only recorded once for job
+pub async fn enqueue<MAINR>(
+    db: &MAINR,
+    hooks: &[&Hook],
+    job_id: &ObjectId,
+    event_key: &str,
+    event: &HookEvent,
+) -> Result<()>
+where
+    MAINR: MainDbRepo,
+{
+    if hooks.is_empty() || db.has_event(job_id, event_key).await? {
+        return Ok(());
+    }
+
+    let now = Utc::now().timestamp();
+    for hook in hooks {
+        let delivery = HookDelivery {
+            id: ObjectId::new(),
+            job_id: *job_id,
+            event_key: event_key.to_string(),
+            hook: (*hook).clone(),
+            event: event.clone(),
+            state: DeliveryState::Pending,
+            attempts: 0,
+            last_error: None,
+            next_attempt_at: now,
+            created_at: now,
+            updated_at: now,
+        };
+        db.insert_delivery(&delivery).await?;
+    }
+    Ok(())
+}
+
+/// HookDispatcher deliver pending events and retry failed ones with backoff
+pub struct HookDispatcher<MAINR>
+where
+    MAINR: MainDbRepo,
+{
+    db: MAINR,
+    client: Client,
+    leadership: Leadership,
+    metrics: DaemonMetrics,
+}
+
+impl<MAINR> HookDispatcher<MAINR>
+where
+    MAINR: MainDbRepo,
+{
+    pub fn new(db: MAINR) -> Self {
+        HookDispatcher {
+            db,
+            client: Client::new(),
+            leadership: Leadership::standalone(DEFAULT_INSTANCE),
+            metrics: DaemonMetrics::new(),
+        }
+    }
+
+    /// set_leadership only deliver events while this instance is leader
+    pub fn set_leadership(mut self, leadership: Leadership) -> Self {
+        self.leadership = leadership;
+        self
+    }
+
+    pub fn set_metrics(mut self, metrics: DaemonMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn run_backend(
+        &self,
+        join_set: &mut JoinSet<Result<()>>,
+        token: CancellationToken,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        let client = self.client.clone();
+        let leadership = self.leadership.clone();
+        let metrics = self.metrics.clone();
+
+        join_set.spawn(async move {
+            info!("hook dispatcher is running");
+            loop {
+                let loop_start = Instant::now();
+                //followers leave deliveries to the leader
+                let deliveries = if leadership.is_leader() {
+                    db.list_due_deliveries(Utc::now().timestamp()).await
+                } else {
+                    Ok(vec![])
+                };
+                match deliveries {
+                    Ok(deliveries) => {
+                        //jobs are delivered concurrently so a dead endpoint only delay its own
+                        //job, events of a job are kept in order
+                        let mut tasks = JoinSet::new();
+                        for deliveries in group_by(deliveries, |delivery| delivery.job_id) {
+                            if tasks.len() >= MAX_CONCURRENT_JOBS {
+                                tasks.join_next().await;
+                            }
+                            let db = db.clone();
+                            let client = client.clone();
+                            let metrics = metrics.clone();
+                            tasks.spawn(async move {
+                                for delivery in deliveries {
+                                    if let Err(err) = Self::attempt(&db, &client, &delivery).await {
+                                        error!("update hook delivery {} {err}", delivery.id);
+                                        metrics.loop_errors.inc(&["hook"]);
+                                    }
+                                }
+                            });
+                        }
+                        while tasks.join_next().await.is_some() {}
+                    }
+                    Err(err) => {
+                        error!("list hook deliveries {err}");
+                        metrics.loop_errors.inc(&["hook"]);
+                    }
+                }
+                metrics
+                    .loop_duration
+                    .observe(&["hook"], loop_start.elapsed().as_secs_f64());
+
+                select! {
+                    _ = token.cancelled() => {
+                        return Ok(());
+                    }
+                    _ = sleep(Duration::from_secs(5)) => {}
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn attempt(db: &MAINR, client: &Client, delivery: &HookDelivery) -> Result<()> {
+        let attempts = delivery.attempts + 1;
+        let info = match deliver(client, &delivery.hook, &delivery.event).await {
+            Ok(_) => HookDeliveryUpdateInfo {
+                state: DeliveryState::Delivered,
+                attempts,
+                last_error: None,
+                next_attempt_at: delivery.next_attempt_at,
+            },
+            Err(err) => {
+                warn!(
+                    "deliver {} of job {} attempt {attempts} {err}",
+                    delivery.event_key, delivery.job_id
+                );
+                HookDeliveryUpdateInfo {
+                    state: if attempts >= MAX_ATTEMPTS {
+                        DeliveryState::Failed
+                    } else {
+                        DeliveryState::Pending
+                    },
+                    attempts,
+                    last_error: Some(err.to_string()),
+                    next_attempt_at: Utc::now().timestamp() + backoff(attempts),
+                }
+            }
+        };
+        db.update_delivery(&delivery.id, &info).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::JobState;
+    use actix_web::{
+        web,
+        App,
+        HttpRequest,
+        HttpResponse,
+        HttpServer,
+    };
+
+    #[test]
+    fn test_sign() {
+        //rfc 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(backoff(1), 5);
+        assert_eq!(backoff(3), 20);
+        assert_eq!(backoff(100), 5 * 1024);
+    }
+
+    #[test]
+    fn test_webhook_policy() {
+        let policy = WebhookPolicy::default().add_allowed_host("Hooks.example.com");
+        assert!(policy.check("https://hooks.example.com/a", false).is_ok());
+        assert!(policy
+            .check("http://hooks.example.com:8080/a", false)
+            .is_ok());
+        for url in [
+            "http://169.254.169.254/latest/meta-data",
+            "http://hooks.example.com.evil.io/a",
+            "http://kubernetes.default.svc",
+        ] {
+            assert!(policy.check(url, false).is_err(), "{url}");
+        }
+        assert!(policy.check("http://10.0.0.1/a", true).is_ok());
+        assert!(policy.check("file:///etc/passwd", true).is_err());
+        assert!(policy.check("not a url", true).is_err());
+    }
+
+    #[test]
+    fn test_group_by() {
+        let groups = group_by(
+            vec![(1, "a"), (2, "b"), (1, "c"), (3, "d"), (2, "e")],
+            |item| item.0,
+        );
+        assert_eq!(
+            groups,
+            vec![
+                vec![(1, "a"), (1, "c")],
+                vec![(2, "b"), (2, "e")],
+                vec![(3, "d")]
+            ]
+        );
+    }
+
+    async fn receive(req: HttpRequest, body: web::Bytes) -> HttpResponse {
+        let signature = req
+            .headers()
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if signature != format!("sha256={}", sign("secret", &body)) {
+            return HttpResponse::Unauthorized().finish();
+        }
+        match serde_json::from_slice::<HookEvent>(&body) {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(_) => HttpResponse::BadRequest().finish(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deliver() {
+        let server = HttpServer::new(|| App::new().route("/hook", web::post().to(receive)))
+            .workers(1)
+            .bind("127.0.0.1:0")
+            .unwrap();
+        let addr = server.addrs()[0];
+        tokio::spawn(server.run());
+
+        let client = Client::new();
+        let event = HookEvent::Job {
+            job_id: ObjectId::new(),
+            job_name: "mnist".to_string(),
+            state: JobState::Finish,
+            reason: None,
+            time: 100,
+        };
+
+        let mut hook = Hook::Webhook {
+            url: format!("http://{addr}/hook"),
+            secret: Some("secret".to_string()),
+        };
+        deliver(&client, &hook, &event).await.unwrap();
+        if let Hook::Webhook { secret, .. } = &mut hook {
+            *secret = Some("wrong".to_string());
+        }
+        assert!(deliver(&client, &hook, &event).await.is_err());
+
+        let hook = Hook::Command {
+            program: "sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                r#"test "$(cat)" = "$JIAOZIFLOW_EVENT""#.to_string(),
+            ],
+        };
+        deliver(&client, &hook, &event).await.unwrap();
+        let hook = Hook::Command {
+            program: "false".to_string(),
+            args: vec![],
+        };
+        assert!(deliver(&client, &hook, &event).await.is_err());
+    }
+}
@@ -1,21 +1,65 @@
 use crate::{
-    core::db::{
-        GetJobParams,
-        Job,
-        JobDbRepo,
-        JobState,
-        JobUpdateInfo,
-        ListJobParams,
-        MainDbRepo,
+    core::{
+        db::{
+            ConcurrencyPolicy,
+            DataList,
+            DataRecord,
+            DataRepo,
+            DataState,
+            Direction,
+            GetJobParams,
+            Hook,
+            HookEvent,
+            InvalidSpec,
+            InvalidTransition,
+            Job,
+            JobDbRepo,
+            JobState,
+            JobUpdateInfo,
+            ListDataParams,
+            ListJobParams,
+            MainDbRepo,
+            NoJobDb,
+            NodeDataSummary,
+            RunSummary,
+            Schedule,
+            TimeoutAction,
+            TrackerState,
+            Trigger,
+            TriggerCursor,
+            SHORT_HASH_LEN,
+        },
+        Notification,
+        Notifier,
+        RunMode,
     },
     dag::Dag,
-    dbrepo::MongoRunDbRepo,
+    dbrepo::{
+        is_duplicate_key,
+        MongoRunDbRepo,
+    },
     driver::{
         Driver,
         NodeStatus,
         PipelineController,
+        Plan,
         UnitHandler,
     },
+    job::{
+        hooks::enqueue,
+        leader::{
+            Leadership,
+            DEFAULT_INSTANCE,
+        },
+        metrics::DaemonMetrics,
+        queue::QueueLimits,
+        summary::summarize_node,
+        watchdog::{
+            expired_reason,
+            has_deadline,
+            NodeProgress,
+        },
+    },
     utils::{
         IntoAnyhowResult,
         StdIntoAnyhowResult,
@@ -25,17 +69,31 @@ use anyhow::{
     anyhow,
     Result,
 };
+use chrono::Utc;
 use futures::future::try_join_all;
 use kube::Client;
+use mongodb::bson::oid::ObjectId;
+use schemars::JsonSchema;
 use serde::{
     Deserialize,
     Serialize,
 };
+use serde_variant::to_variant_name;
 use std::{
+    collections::HashMap,
     marker::PhantomData,
-    time::Duration,
+    sync::{
+        Arc,
+        Mutex,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
 };
 use tokio::{
+    select,
+    sync::watch,
     task::JoinSet,
     time::sleep,
 };
@@ -43,14 +101,82 @@ use tokio_util::sync::CancellationToken;
 use tracing::{
     error,
     info,
+    warn,
 };
 
-#[derive(Serialize, Deserialize)]
+/// grace period used when a job is cancelled without an explicit one
+pub const DEFAULT_CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// interval job details are recomputed for watchers when nothing was notified
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// is_drained return true if no data is processing or the deadline to drain was reached
+fn is_drained(in_flight: usize, deadline: Option<i64>, now: i64) -> bool {
+    in_flight == 0 || deadline.map_or(true, |deadline| now >= deadline)
+}
+
+/// skip_invalid_transition log and ignore a state change rejected because job was moved by
+/// others meanwhile, eg. cancelled by user, so it dont abort the pass of other jobs
+fn skip_invalid_transition(job: &Job, result: Result<()>) -> Result<()> {
+    match result {
+        Err(err) if err.downcast_ref::<InvalidTransition>().is_some() => {
+            warn!("skip job {}, {err}", job.name);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// lost_leadership return true if lease of this instance expired during the pass, the pass must
+/// stop before deploying or cleaning jobs the next leader may handle
+fn lost_leadership(leadership: &Leadership) -> bool {
+    if leadership.is_leader() {
+        return false;
+    }
+    warn!(
+        "instance {} lost leadership, stop the pass",
+        leadership.instance()
+    );
+    true
+}
+
+/// check_run_name make sure runs named by schedule or trigger are valid kubernetes namespaces
+/// (rfc 1123 labels), name is limited by the length of suffix kind append to it
+fn check_run_name(kind: &str, name: &str, run_name: &str) -> Result<()> {
+    let max_len = 63usize.saturating_sub(run_name.len().saturating_sub(name.len()));
+    let is_alphanumeric = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+    if name.is_empty()
+        || run_name.len() > 63
+        || !run_name.chars().all(|c| is_alphanumeric(c) || c == '-')
+        || !name.starts_with(is_alphanumeric)
+        || !name.ends_with(is_alphanumeric)
+    {
+        return Err(anyhow!(
+            "{kind} name must be lowercase alphanumeric or '-', start and end with alphanumeric and at most {max_len} characters"
+        ));
+    }
+    Ok(())
+}
+
+/// job_webhooks return hooks of job to notify, commands only come from daemon flags as they run on
+/// daemon host
+fn job_webhooks(job: &Job) -> impl Iterator<Item = &Hook> {
+    job.hooks.iter().filter(|hook| hook.is_webhook())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct JobDetails {
     pub job: Job,
     pub node_status: Option<Vec<NodeStatus>>,
 }
 
+impl JobDetails {
+    pub fn redacted(mut self) -> JobDetails {
+        self.job = self.job.redacted();
+        self
+    }
+}
+
 #[derive(Clone)]
 pub struct JobManager<D, MAINR, JOBR>
 where
@@ -61,6 +187,15 @@ where
     driver: D,
     db: MAINR,
     connection_string: String,
+    /// shared by job databases read by api
+    job_db_client: mongodb::Client,
+    notifier: Notifier,
+    queue_limits: QueueLimits,
+    hooks: Vec<Hook>,
+    leadership: Leadership,
+    metrics: DaemonMetrics,
+    /// details of watched jobs, computed once and shared by all watchers of a job
+    watchers: Arc<Mutex<HashMap<ObjectId, watch::Sender<Option<JobDetails>>>>>,
     _phantom_data: PhantomData<JOBR>,
 }
 
@@ -75,14 +210,46 @@ where
         connection_string: &str,
         driver: D,
         db: MAINR,
+        notifier: Notifier,
     ) -> Result<Self> {
+        let job_db_client = mongodb::Client::with_uri_str(connection_string).await?;
         Ok(JobManager {
             db,
             driver,
             connection_string: connection_string.to_string(),
+            job_db_client,
+            notifier,
+            queue_limits: QueueLimits::default(),
+            hooks: vec![],
+            leadership: Leadership::standalone(DEFAULT_INSTANCE),
+            metrics: DaemonMetrics::new(),
+            watchers: Arc::new(Mutex::new(HashMap::new())),
             _phantom_data: PhantomData,
         })
     }
+
+    /// set_leadership only run backend while this instance is leader
+    pub fn set_leadership(mut self, leadership: Leadership) -> Self {
+        self.leadership = leadership;
+        self
+    }
+
+    /// set_hooks set hooks notified for every job
+    pub fn set_hooks(mut self, hooks: Vec<Hook>) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    pub fn set_queue_limits(mut self, queue_limits: QueueLimits) -> Self {
+        self.queue_limits = queue_limits;
+        self
+    }
+
+    /// set_metrics record deploys and backend loops into metrics
+    pub fn set_metrics(mut self, metrics: DaemonMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
 }
 
 impl<D, MAINR, JOBR> JobManager<D, MAINR, JOBR>
@@ -99,41 +266,112 @@ where
         let db = self.db.clone();
         let driver = self.driver.clone();
         let connect_string = self.connection_string.clone();
+        let hooks = self.hooks.clone();
+        let queue_limits = self.queue_limits.clone();
+        let leadership = self.leadership.clone();
+        let metrics = self.metrics.clone();
+        let mut subscription = self.notifier.subscribe();
 
         join_set.spawn(async move {
             info!("backend thead is running");
+            let mut is_leader = false;
             loop {
                 if token.is_cancelled() {
                     return Ok(());
                 }
 
-                if let Err(err) = {
-                    //fetch data to deploy
+                //followers only serve api, jobs are left to the leader
+                if !leadership.is_leader() {
+                    is_leader = false;
+                    select! {
+                        _ = token.cancelled() => {}
+                        _ = sleep(Duration::from_secs(5)) => {}
+                    }
+                    continue;
+                }
+                if !is_leader {
+                    match Self::take_over(&driver, &db, &hooks, leadership.instance()).await {
+                        Ok(_) => is_leader = true,
+                        Err(err) => error!("take over jobs {err}"),
+                    }
+                }
+
+                let loop_start = Instant::now();
+                if let Err(err) = async {
+                    //create jobs from due schedules
                     {
-                        while let Some(job) = db.get_job_for_running().await? {
+                        let now = Utc::now().timestamp();
+                        for schedule in db.list_due_schedules(now).await? {
+                            if let Err(err) = Self::run_schedule(&driver, &db, &schedule, now).await
+                            {
+                                error!("run schedule {} {err}", schedule.name);
+                            }
+                        }
+                    }
+
+                    //deploy queued jobs in priority order as capacity frees up
+                    {
+                        db.queue_created_jobs().await?;
+                        let queued_jobs_params = &ListJobParams {
+                            states: vec![JobState::Queued],
+                            ..Default::default()
+                        };
+                        let queued = db.list_jobs(queued_jobs_params).await?;
+                        let active_jobs_params = &ListJobParams {
+                            states: vec![
+                                JobState::Selected,
+                                JobState::Deployed,
+                                JobState::Running,
+                                JobState::Paused,
+                                JobState::Cancelling,
+                            ],
+                            ..Default::default()
+                        };
+                        let active = db.list_jobs(active_jobs_params).await?;
+
+                        for job in queue_limits.admit(&queued, &active) {
+                            if lost_leadership(&leadership) {
+                                return Ok(());
+                            }
+                            let Some(job) = db.select_job(&job.id, leadership.instance()).await?
+                            else {
+                                continue;
+                            };
                             let dag = Dag::from_json(job.graph_json.as_str())?;
+                            if lost_leadership(&leadership) {
+                                return Ok(());
+                            }
+                            let deploy_start = Instant::now();
                             match driver.deploy(job.name.as_str(), &dag).await {
                                 Ok(controller) => {
-                                    if let Err(err) = db
-                                        .update(
-                                            &job.id,
-                                            &JobUpdateInfo {
-                                                state: Some(JobState::Deployed),
-                                            },
-                                        )
-                                        .await
+                                    metrics
+                                        .deploy_duration
+                                        .observe(&[], deploy_start.elapsed().as_secs_f64());
+                                    if let Err(err) = Self::update_state(
+                                        &db,
+                                        &hooks,
+                                        &job,
+                                        &JobUpdateInfo {
+                                            state: Some(JobState::Deployed),
+                                            ..Default::default()
+                                        },
+                                    )
+                                    .await
                                     {
                                         error!("set job to deploy state {err}");
                                     }
                                     if !job.manual_run && controller.start().await.is_ok() {
-                                        if let Err(err) = db
-                                            .update(
-                                                &job.id,
-                                                &JobUpdateInfo {
-                                                    state: Some(JobState::Running),
-                                                },
-                                            )
-                                            .await
+                                        if let Err(err) = Self::update_state(
+                                            &db,
+                                            &hooks,
+                                            &job,
+                                            &JobUpdateInfo {
+                                                state: Some(JobState::Running),
+                                                started_at: Some(Utc::now().timestamp()),
+                                                ..Default::default()
+                                            },
+                                        )
+                                        .await
                                         {
                                             error!("start job {err}");
                                         }
@@ -141,17 +379,20 @@ where
                                 }
                                 Err(err) => {
                                     error!("run job {} {err}, start cleaning", job.name);
+                                    metrics.deploy_failures.inc(&[]);
                                     if let Err(err) = driver.clean(job.name.as_str()).await {
                                         error!("clean job resource {err}");
                                     }
-                                    if let Err(err) = db
-                                        .update(
-                                            &job.id,
-                                            &JobUpdateInfo {
-                                                state: Some(JobState::Error),
-                                            },
-                                        )
-                                        .await
+                                    if let Err(err) = Self::update_state(
+                                        &db,
+                                        &hooks,
+                                        &job,
+                                        &JobUpdateInfo {
+                                            state: Some(JobState::Error),
+                                            ..Default::default()
+                                        },
+                                    )
+                                    .await
                                     {
                                         error!("set job to error state {err}");
                                     }
@@ -162,62 +403,467 @@ where
                     //mark finish if all nodes was finish
                     {
                         let running_jobs_params = &ListJobParams {
-                            state: Some(JobState::Running),
+                            states: vec![JobState::Running],
+                            ..Default::default()
                         };
                         for job in db.list_jobs(running_jobs_params).await? {
-                            let namespace = job.name;
+                            let namespace = job.name.clone();
                             let db_url = connect_string.clone() + "/" + &namespace;
                             let job_db = MongoRunDbRepo::new(&db_url).await?;
+
+                            let dag = Dag::from_json(job.graph_json.as_str())?;
+                            //a job whose workloads are gone must not block the pass of others
+                            let has_failed_node =
+                                match Self::sync_workload_state(&driver, &job_db, &namespace, &dag)
+                                    .await
+                                {
+                                    Ok(has_failed_node) => has_failed_node,
+                                    Err(err) => {
+                                        error!("sync workload state of job {namespace} {err}");
+                                        continue;
+                                    }
+                                };
+                            if let Err(err) =
+                                Self::notify_node_states(&db, &hooks, &job, &job_db, &dag).await
+                            {
+                                error!("notify node states of job {namespace} {err}");
+                            }
+                            if has_failed_node {
+                                error!("node of job {namespace} failed in cluster");
+                                let result = Self::update_state(
+                                    &db,
+                                    &hooks,
+                                    &job,
+                                    &JobUpdateInfo {
+                                        state: Some(JobState::Error),
+                                        reason: Some("node failed in cluster".to_string()),
+                                        ..Default::default()
+                                    },
+                                )
+                                .await;
+                                skip_invalid_transition(&job, result)?;
+                                continue;
+                            }
+
                             let is_job_finish = job_db.is_all_node_finish().await?;
 
                             if is_job_finish {
-                                db.update(
-                                    &job.id,
+                                let result = Self::update_state(
+                                    &db,
+                                    &hooks,
+                                    &job,
                                     &JobUpdateInfo {
                                         state: Some(JobState::Finish),
+                                        ..Default::default()
                                     },
                                 )
-                                .await?;
+                                .await;
+                                skip_invalid_transition(&job, result)?;
+                                continue;
+                            }
+
+                            if let Some(reason) = Self::check_deadlines(&job_db, &job, &dag).await?
+                            {
+                                let result =
+                                    Self::expire(&driver, &db, &hooks, &job, &dag, reason).await;
+                                skip_invalid_transition(&job, result)?;
+                            }
+                        }
+                    }
+
+                    //clean cancelled job after in-flight data drained or grace period elapsed
+                    {
+                        let cancelling_jobs_params = &ListJobParams {
+                            states: vec![JobState::Cancelling],
+                            ..Default::default()
+                        };
+                        for job in db.list_jobs(cancelling_jobs_params).await? {
+                            let namespace = job.name.clone();
+                            let db_url = connect_string.clone() + "/" + &namespace;
+                            let job_db = MongoRunDbRepo::new(&db_url).await?;
+                            let dag = Dag::from_json(job.graph_json.as_str())?;
+
+                            let (in_flight, completed) = Self::count_batches(&job_db, &dag).await?;
+                            if !is_drained(in_flight, job.cancel_deadline, Utc::now().timestamp()) {
+                                info!("job {namespace} has {in_flight} batches in processing");
+                                continue;
                             }
+
+                            if lost_leadership(&leadership) {
+                                return Ok(());
+                            }
+                            driver.clean(&namespace).await?;
+                            Self::summarize(&db, &job, &job_db, &dag).await;
+                            MongoRunDbRepo::drop(&db_url).await?;
+                            db.mark_cancelled(&job.id, completed).await?;
+                            info!("job {namespace} cancelled, {completed} batches completed");
                         }
                     }
 
                     //clean data
                     {
                         let finish_jobs_params = &ListJobParams {
-                            state: Some(JobState::Finish),
+                            states: vec![JobState::Finish],
+                            ..Default::default()
                         };
 
                         for job in db.list_jobs(finish_jobs_params).await? {
-                            let namespace = job.name;
+                            if lost_leadership(&leadership) {
+                                return Ok(());
+                            }
+                            let namespace = job.name.clone();
                             driver.clean(&namespace).await?;
                             let db_url = connect_string.clone() + "/" + &namespace;
+                            let job_db = MongoRunDbRepo::new(&db_url).await?;
+                            let dag = Dag::from_json(job.graph_json.as_str())?;
+                            Self::summarize(&db, &job, &job_db, &dag).await;
                             MongoRunDbRepo::drop(&db_url).await?;
-                            db.update(
-                                &job.id,
+                            let result = Self::update_state(
+                                &db,
+                                &hooks,
+                                &job,
                                 &JobUpdateInfo {
                                     state: Some(JobState::Clean),
+                                    ..Default::default()
                                 },
                             )
-                            .await?;
+                            .await;
+                            skip_invalid_transition(&job, result)?;
                         }
                     }
 
                     anyhow::Ok(())
-                } {
+                }
+                .await
+                {
                     error!("error in job backend {err}");
+                    metrics.loop_errors.inc(&["job"]);
                 }
+                metrics
+                    .loop_duration
+                    .observe(&["job"], loop_start.elapsed().as_secs_f64());
 
-                sleep(Duration::from_secs(5)).await;
+                //polling as fallback when notification not available
+                select! {
+                    _ = token.cancelled() => {}
+                    _ = sleep(Duration::from_secs(5)) => {}
+                    _ = subscription.wait(|notification| matches!(notification, Notification::JobStateChanged { .. })) => {}
+                }
             }
         });
 
         Ok(())
     }
 
+    /// sync_workload_state write the state kubernetes reported for finite nodes to job
+    /// database, return true if any node failed
+    async fn sync_workload_state(
+        driver: &D,
+        job_db: &MongoRunDbRepo,
+        namespace: &str,
+        dag: &Dag,
+    ) -> Result<bool> {
+        if !dag.iter().any(|node| node.spec.run_mode == RunMode::Finite) {
+            return Ok(false);
+        }
+
+        let controller = driver.attach(namespace, dag).await?;
+        let mut has_error = false;
+        for node_name in controller.nodes_in_order()? {
+            let handler = controller.get_node(&node_name).await?;
+            let Some(workload_state) = handler.workload_state().await? else {
+                continue;
+            };
+
+            let node = job_db.get_node_by_name(&node_name).await?;
+            has_error |= workload_state == TrackerState::Error;
+            if node.state == workload_state || node.state == TrackerState::Finish {
+                continue;
+            }
+            info!(
+                "node {node_name} state {:?} -> {:?} by cluster",
+                node.state, workload_state
+            );
+            job_db
+                .update_node_by_name(&node_name, workload_state)
+                .await?;
+        }
+        Ok(has_error)
+    }
+
+    /// take_over adopt active jobs held by other instances once this instance become leader.
+    /// deployed jobs are attached instead of redeployed, jobs interrupted while deploying are
+    /// cleaned and failed. takeover is retried if any job can not be attached
+    async fn take_over(driver: &D, db: &MAINR, hooks: &[Hook], instance: &str) -> Result<()> {
+        let list_job_params = &ListJobParams {
+            states: vec![
+                JobState::Selected,
+                JobState::Deployed,
+                JobState::Running,
+                JobState::Paused,
+                JobState::Cancelling,
+            ],
+            ..Default::default()
+        };
+        for job in db.list_jobs(list_job_params).await? {
+            let holder = job.holder.clone().unwrap_or_default();
+            if holder == instance {
+                continue;
+            }
+
+            if matches!(job.state, JobState::Deployed | JobState::Running) {
+                let dag = Dag::from_json(job.graph_json.as_str())?;
+                driver
+                    .attach(&job.name, &dag)
+                    .await
+                    .map_err(|err| anyhow!("attach job {} {err}", job.name))?;
+            }
+
+            db.set_holder(&job.id, instance).await?;
+            info!("take over job {} from {holder}", job.name);
+            if job.state == JobState::Selected {
+                let reason = format!("daemon {holder} stopped while deploying");
+                error!("job {} failed, {reason}", job.name);
+                driver.clean(&job.name).await?;
+                Self::update_state(
+                    db,
+                    hooks,
+                    &job,
+                    &JobUpdateInfo {
+                        state: Some(JobState::Error),
+                        reason: Some(reason),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// update_state update job, hooks of job and global hooks are notified of the new state
+    async fn update_state(
+        db: &MAINR,
+        hooks: &[Hook],
+        job: &Job,
+        info: &JobUpdateInfo,
+    ) -> Result<()> {
+        db.update(&job.id, info).await?;
+        let Some(state) = info.state.as_ref() else {
+            return Ok(());
+        };
+        if !matches!(
+            state,
+            JobState::Deployed
+                | JobState::Running
+                | JobState::Finish
+                | JobState::Error
+                | JobState::Clean
+        ) {
+            return Ok(());
+        }
+
+        let event = HookEvent::Job {
+            job_id: job.id,
+            job_name: job.name.clone(),
+            state: state.clone(),
+            reason: info.reason.clone(),
+            time: Utc::now().timestamp(),
+        };
+        let event_key = format!("{}/job/{}", job.attempts.len(), to_variant_name(state)?);
+        let hooks: Vec<_> = hooks.iter().chain(job_webhooks(job)).collect();
+        if let Err(err) = enqueue(db, &hooks, &job.id, &event_key, &event).await {
+            error!("notify {event_key} of job {} {err}", job.name);
+        }
+        Ok(())
+    }
+
+    /// notify_node_states notify hooks of finished and failed nodes once
+    async fn notify_node_states(
+        db: &MAINR,
+        hooks: &[Hook],
+        job: &Job,
+        job_db: &MongoRunDbRepo,
+        dag: &Dag,
+    ) -> Result<()> {
+        let hooks: Vec<_> = hooks.iter().chain(job_webhooks(job)).collect();
+        if hooks.is_empty() {
+            return Ok(());
+        }
+
+        for node in dag.iter() {
+            let state = job_db.get_node_by_name(&node.name).await?.state;
+            if !matches!(state, TrackerState::Finish | TrackerState::Error) {
+                continue;
+            }
+            let event_key = format!(
+                "{}/node/{}/{}",
+                job.attempts.len(),
+                node.name,
+                to_variant_name(&state)?
+            );
+            let event = HookEvent::Node {
+                job_id: job.id,
+                job_name: job.name.clone(),
+                node_name: node.name.clone(),
+                state,
+                time: Utc::now().timestamp(),
+            };
+            enqueue(db, &hooks, &job.id, &event_key, &event).await?;
+        }
+        Ok(())
+    }
+
+    /// check_deadlines return why job should be expired, None if job has no deadline or is
+    /// within all of them
+    async fn check_deadlines(
+        job_db: &MongoRunDbRepo,
+        job: &Job,
+        dag: &Dag,
+    ) -> Result<Option<String>> {
+        if !has_deadline(job, dag) {
+            return Ok(None);
+        }
+
+        let mut nodes = vec![];
+        for node in dag.iter() {
+            let state = job_db.get_node_by_name(&node.name).await?;
+            let pending = job_db
+                .count(
+                    &node.name,
+                    &[&DataState::Received, &DataState::Assigned],
+                    Some(&Direction::In),
+                )
+                .await?;
+            let last_data_update = job_db.last_updated_at(&node.name).await?;
+            nodes.push(NodeProgress {
+                node_name: node.name.clone(),
+                timeout: node.spec.timeout,
+                stall_timeout: node.spec.stall_timeout.or(job.stall_timeout),
+                finished: state.state.is_end_state(),
+                has_work: node.dependency.is_empty() || pending > 0,
+                last_progress: state.updated_at.max(last_data_update.unwrap_or_default()),
+            });
+        }
+
+        let started_at = job.started_at.unwrap_or(job.updated_at);
+        Ok(expired_reason(
+            job.timeout,
+            started_at,
+            job.active_secs,
+            &nodes,
+            Utc::now().timestamp(),
+        ))
+    }
+
+    /// expire apply the timeout action of job
+    async fn expire(
+        driver: &D,
+        db: &MAINR,
+        hooks: &[Hook],
+        job: &Job,
+        dag: &Dag,
+        reason: String,
+    ) -> Result<()> {
+        let reason_only = JobUpdateInfo {
+            reason: Some(reason.clone()),
+            ..Default::default()
+        };
+        match job.timeout_action {
+            TimeoutAction::Fail => {
+                error!("job {} failed, {reason}", job.name);
+                Self::update_state(
+                    db,
+                    hooks,
+                    job,
+                    &JobUpdateInfo {
+                        state: Some(JobState::Error),
+                        reason: Some(reason),
+                        ..Default::default()
+                    },
+                )
+                .await
+            }
+            TimeoutAction::Cancel => {
+                warn!("job {} cancelled, {reason}", job.name);
+                db.update(&job.id, &reason_only).await?;
+                Self::cancel(driver, db, job, DEFAULT_CANCEL_GRACE_PERIOD).await
+            }
+            TimeoutAction::Pause => {
+                warn!("job {} paused, {reason}", job.name);
+                let mut controller = driver.attach(&job.name, dag).await?;
+                for node_name in controller.nodes_in_order()? {
+                    controller.get_node_mut(&node_name).await?.pause().await?;
+                }
+                //time paused is not counted in timeout, keep what job ran so far
+                let now = Utc::now().timestamp();
+                let started_at = job.started_at.unwrap_or(job.updated_at);
+                Self::update_state(
+                    db,
+                    hooks,
+                    job,
+                    &JobUpdateInfo {
+                        state: Some(JobState::Paused),
+                        active_secs: Some(job.active_secs + (now - started_at).max(0) as u64),
+                        reason: Some(reason),
+                        ..Default::default()
+                    },
+                )
+                .await
+            }
+            TimeoutAction::Notify => {
+                if job.reason.as_ref() == Some(&reason) {
+                    return Ok(());
+                }
+                warn!("job {} {reason}", job.name);
+                db.update(&job.id, &reason_only).await
+            }
+        }
+    }
+
+    /// summarize store run metrics of job before its job database is dropped, job never
+    /// deployed has nothing to summarize
+    async fn summarize(db: &MAINR, job: &Job, job_db: &MongoRunDbRepo, dag: &Dag) {
+        let result = async {
+            let mut nodes = vec![];
+            for node in dag.iter() {
+                let node_record = job_db.get_node_by_name(&node.name).await?;
+                let records = job_db.list_by_node_name(&node.name).await?;
+                nodes.push(summarize_node(&node_record, &records));
+            }
+            let summary = RunSummary {
+                nodes,
+                created_at: Utc::now().timestamp(),
+            };
+            db.set_summary(&job.id, &summary).await
+        }
+        .await;
+        if let Err(err) = result {
+            warn!("summarize job {} {err}", job.name);
+        }
+    }
+
+    /// count_batches return the number of batches assigned to user containers and processed
+    async fn count_batches(job_db: &MongoRunDbRepo, dag: &Dag) -> Result<(usize, u64)> {
+        let mut in_flight = 0;
+        let mut completed = 0;
+        for node in dag.iter() {
+            in_flight += job_db
+                .count(&node.name, &[&DataState::Assigned], Some(&Direction::In))
+                .await?;
+            completed += job_db
+                .count(&node.name, &[&DataState::Processed], Some(&Direction::In))
+                .await? as u64;
+        }
+        Ok((in_flight, completed))
+    }
+
     pub async fn get_job_details(&self, params: &GetJobParams) -> Result<JobDetails> {
         let job = self.db.get(params).await?.anyhow("job not found")?;
+        self.job_details(job).await
+    }
 
+    async fn job_details(&self, job: Job) -> Result<JobDetails> {
         let node_status = if job.state == JobState::Running {
             let dag = Dag::from_json(job.graph_json.as_str())?;
             let controller = self.driver.attach(&job.name, &dag).await?;
@@ -238,52 +884,356 @@ where
         Ok(JobDetails { job, node_status })
     }
 
+    /// job_db open database of job, it has no data before job deployed or after job cleaned
+    fn job_db(&self, job: &Job) -> Result<MongoRunDbRepo> {
+        if !job.state.has_job_db() {
+            return Err(NoJobDb {
+                name: job.name.clone(),
+                state: job.state.clone(),
+            }
+            .into());
+        }
+        Ok(MongoRunDbRepo::open(&self.job_db_client, &job.name))
+    }
+
+    /// list_data list a page of data records of a node of job
+    pub async fn list_data(
+        &self,
+        job: &Job,
+        node_name: &str,
+        params: &ListDataParams,
+    ) -> Result<DataList> {
+        self.job_db(job)?
+            .list_by_node_name_and_state(node_name, params)
+            .await
+    }
+
+    /// batch_history list records of a data batch in all nodes of job
+    pub async fn batch_history(&self, job: &Job, id: &str) -> Result<Vec<DataRecord>> {
+        self.job_db(job)?.list_by_id(id).await
+    }
+
+    /// data_summary count data records of every node of job by direction and state
+    pub async fn data_summary(&self, job: &Job) -> Result<Vec<NodeDataSummary>> {
+        self.job_db(job)?.summarize_by_node().await
+    }
+
+    /// watch_job subscribe details of job, value is None until details are computed first time
+    /// and channel is closed when job was deleted
+    pub fn watch_job(&self, id: ObjectId) -> watch::Receiver<Option<JobDetails>> {
+        let mut watchers = self.watchers.lock().unwrap();
+        if let Some(tx) = watchers.get(&id).filter(|tx| !tx.is_closed()) {
+            return tx.subscribe();
+        }
+
+        let (tx, rx) = watch::channel(None);
+        watchers.insert(id, tx.clone());
+        let job_manager = self.clone();
+        tokio::spawn(async move { job_manager.run_watcher(id, tx).await });
+        rx
+    }
+
+    /// run_watcher recompute details of job on changes until no one is watching it
+    async fn run_watcher(&self, id: ObjectId, tx: watch::Sender<Option<JobDetails>>) {
+        let mut subscription = self.notifier.subscribe();
+        let params = GetJobParams::new().set_id(id);
+        loop {
+            let details = match self.db.get(&params).await {
+                Ok(Some(job)) => self.job_details(job).await,
+                Ok(None) => break,
+                Err(err) => Err(err),
+            };
+            match details {
+                Ok(details) => {
+                    tx.send_if_modified(|cur| {
+                        if cur.as_ref() == Some(&details) {
+                            return false;
+                        }
+                        *cur = Some(details);
+                        true
+                    });
+                }
+                Err(err) => warn!("compute details of job {id} {err}"),
+            }
+
+            select! {
+                _ = tx.closed() => break,
+                _ = subscription.wait(|notification| matches!(
+                    notification,
+                    Notification::JobStateChanged { id: Some(changed) } if *changed == id
+                ) || matches!(notification, Notification::NodeStateChanged { .. })) => {
+                    //coalesce bursts of changes
+                    sleep(Duration::from_millis(500)).await;
+                }
+                _ = sleep(WATCH_INTERVAL) => {}
+            }
+        }
+
+        let mut watchers = self.watchers.lock().unwrap();
+        if watchers.get(&id).is_some_and(|tx| tx.is_closed()) {
+            watchers.remove(&id);
+        }
+    }
+
+    /// plan_job render what deploying job would create without touching the cluster
+    pub fn plan_job(&self, job: &Job) -> Result<Plan> {
+        let dag = Dag::from_json(job.graph_json.as_str())?;
+        self.driver.plan(&job.name, &dag)
+    }
+
+    /// start_job start a deployed job, or resume a paused job. started_at is reset so stall
+    /// timeouts count from now, timeouts keep counting from active_secs recorded at pause
     pub async fn start_job(&self, params: &GetJobParams) -> Result<()> {
         let job = self.db.get(params).await?.anyhow("job not found")?;
-        if job.state != JobState::Deployed {
-            return Err(anyhow!("only can run a deployed job"));
+        if !job.state.can_move_to(&JobState::Running) {
+            return Err(InvalidTransition::new(job.state, JobState::Running).into());
         }
 
         let dag = Dag::from_json(job.graph_json.as_str())?;
         let controller = self.driver.attach(&job.name, &dag).await?;
         controller.start().await?;
+        Self::update_state(
+            &self.db,
+            &self.hooks,
+            &job,
+            &JobUpdateInfo {
+                state: Some(JobState::Running),
+                started_at: Some(Utc::now().timestamp()),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(|err| {
+            error!("set job to deploy state {err}");
+            err
+        })
+    }
+
+    /// retry_job redeploy a failed job onto its existing job database, processed data are kept
+    /// and the run continue from where it stopped
+    pub async fn retry_job(&self, params: &GetJobParams) -> Result<()> {
+        let job = self.db.get(params).await?.anyhow("job not found")?;
+        if !job.state.can_move_to(&JobState::Created) {
+            return Err(InvalidTransition::new(job.state, JobState::Created).into());
+        }
+
+        let attempt = job.current_attempt(Utc::now().timestamp());
+        self.db.retry(&job.id, &attempt).await
+    }
+
+    /// cancel_job stop nodes accepting new data, user containers have grace_period to finish
+    /// assigned data, then backend clean the resources of job
+    pub async fn cancel_job(&self, params: &GetJobParams, grace_period: Duration) -> Result<()> {
+        let job = self.db.get(params).await?.anyhow("job not found")?;
+        Self::cancel(&self.driver, &self.db, &job, grace_period).await
+    }
+
+    async fn cancel(driver: &D, db: &MAINR, job: &Job, grace_period: Duration) -> Result<()> {
+        if matches!(job.state, JobState::Deployed | JobState::Running) {
+            let dag = Dag::from_json(job.graph_json.as_str())?;
+            let mut controller = driver.attach(&job.name, &dag).await?;
+            for node_name in controller.nodes_in_order()? {
+                controller.get_node_mut(&node_name).await?.stop().await?;
+            }
+        }
+
+        let deadline = Utc::now().timestamp() + grace_period.as_secs() as i64;
+        db.cancel(&job.id, deadline).await
+    }
+
+    /// create_schedule validate schedule and compute its first run time
+    pub async fn create_schedule(&self, mut schedule: Schedule) -> Result<Schedule> {
+        let now = Utc::now().timestamp();
+        let validate = || {
+            let run_name = schedule.run_name(now);
+            check_run_name("schedule", &schedule.name, &run_name)?;
+            let _ = Dag::from_json(&schedule.render_graph(&run_name)?)?;
+            schedule.next_run_after(now)
+        };
+        schedule.next_run_at = validate().map_err(|err| InvalidSpec(err.to_string()))?;
+        schedule.created_at = now;
+        schedule.updated_at = now;
+        self.db.insert_schedule(&schedule).await
+    }
+
+    /// set_schedule_paused pause or resume a schedule, runs missed while paused are skipped
+    pub async fn set_schedule_paused(&self, params: &GetJobParams, paused: bool) -> Result<()> {
+        let schedule = self
+            .db
+            .get_schedule(params)
+            .await?
+            .anyhow("schedule not found")?;
+        let next_run_at = schedule.next_run_after(Utc::now().timestamp())?;
         self.db
-            .update(
-                &job.id,
-                &JobUpdateInfo {
-                    state: Some(JobState::Running),
-                },
-            )
+            .set_schedule_paused(&schedule.id, paused, next_run_at)
+            .await
+    }
+
+    /// create_trigger validate trigger, its cursor is initialized by the first poll
+    pub async fn create_trigger(&self, mut trigger: Trigger) -> Result<Trigger> {
+        let now = Utc::now().timestamp();
+        let validate = || {
+            let run_name = trigger.run_name(&"0".repeat(SHORT_HASH_LEN));
+            check_run_name("trigger", &trigger.name, &run_name)?;
+            if trigger.owner.is_empty() || trigger.repo.is_empty() || trigger.branch.is_empty() {
+                return Err(anyhow!("trigger must set owner, repo and branch"));
+            }
+            Dag::from_json(&trigger.render_graph(&run_name, "")?).map(|_| ())
+        };
+        validate().map_err(|err| InvalidSpec(err.to_string()))?;
+        trigger.cursor = TriggerCursor::default();
+        trigger.created_at = now;
+        trigger.updated_at = now;
+        self.db.insert_trigger(&trigger).await
+    }
+
+    /// run_schedule create a job from due schedule according to its concurrency policy
+    async fn run_schedule(driver: &D, db: &MAINR, schedule: &Schedule, now: i64) -> Result<()> {
+        let list_params = ListJobParams {
+            schedule_id: Some(schedule.id),
+            ..Default::default()
+        };
+        //run of this slot may be created by a retried pass, it is not a concurrent run
+        let run_name = schedule.run_name(schedule.next_run_at);
+        let active_jobs: Vec<_> = db
+            .list_jobs(&list_params)
+            .await?
+            .into_iter()
+            .filter(|job| job.state.is_active() && job.name != run_name)
+            .collect();
+
+        let next_run_at = schedule.next_run_after(now)?;
+        match schedule.concurrency_policy {
+            ConcurrencyPolicy::Forbid if !active_jobs.is_empty() => {
+                info!("schedule {} skip run, last run is active", schedule.name);
+                db.advance_schedule(&schedule.id, schedule.next_run_at, next_run_at, None)
+                    .await?;
+                return Ok(());
+            }
+            ConcurrencyPolicy::Replace => {
+                //job in deploying cant be cancelled, wait for next loop
+                if active_jobs
+                    .iter()
+                    .any(|job| job.state == JobState::Selected)
+                {
+                    return Ok(());
+                }
+                for job in active_jobs
+                    .iter()
+                    .filter(|job| job.state != JobState::Cancelling)
+                {
+                    info!("schedule {} replace run {}", schedule.name, job.name);
+                    Self::cancel(driver, db, job, DEFAULT_CANCEL_GRACE_PERIOD).await?;
+                }
+            }
+            _ => {}
+        }
+
+        //create run before advancing schedule so a failed insert is retried by next pass,
+        //run is named by the time it was due, unique job name keep it from being created twice
+        let job = Job {
+            name: run_name.clone(),
+            graph_json: schedule.render_graph(&run_name)?,
+            schedule_id: Some(schedule.id),
+            owner: schedule.created_by.clone(),
+            created_at: now,
+            updated_at: now,
+            ..Default::default()
+        }
+        .set_options(&schedule.job_options);
+        match db.insert(&job).await {
+            Ok(_) => info!("schedule {} create run {run_name}", schedule.name),
+            Err(err) if is_duplicate_key(&err) => {
+                info!("schedule {} run {run_name} already created", schedule.name)
+            }
+            Err(err) => return Err(err),
+        }
+
+        db.advance_schedule(&schedule.id, schedule.next_run_at, next_run_at, Some(now))
             .await
-            .map_err(|err| {
-                error!("set job to deploy state {err}");
-                err
-            })
+            .map(|_| ())
     }
 
     pub async fn clean_job(&self, params: &GetJobParams) -> Result<()> {
         let job = self.db.get(params).await?.anyhow("job not found")?;
+        if !job.state.can_move_to(&JobState::Clean) {
+            return Err(InvalidTransition::new(job.state, JobState::Clean).into());
+        }
         //clean k8s
-        self.db
-            .update(
-                &job.id,
-                &JobUpdateInfo {
-                    state: Some(JobState::Finish),
-                },
-            )
-            .await?;
         self.driver.clean(&job.name).await?;
         //drop database
         let db_url = self.connection_string.clone() + "/" + &job.name;
+        let job_db = MongoRunDbRepo::new(&db_url).await?;
+        let dag = Dag::from_json(job.graph_json.as_str())?;
+        Self::summarize(&self.db, &job, &job_db, &dag).await;
         MongoRunDbRepo::drop(&db_url).await?;
-        self.db
-            .update(
-                &job.id,
-                &JobUpdateInfo {
-                    state: Some(JobState::Clean),
-                },
-            )
-            .await?;
+        Self::update_state(
+            &self.db,
+            &self.hooks,
+            &job,
+            &JobUpdateInfo {
+                state: Some(JobState::Clean),
+                ..Default::default()
+            },
+        )
+        .await?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_drained() {
+        assert!(is_drained(0, Some(200), 100));
+        assert!(!is_drained(3, Some(200), 100));
+        assert!(is_drained(3, Some(200), 200));
+        assert!(is_drained(3, None, 100));
+    }
+
+    #[test]
+    fn test_skip_invalid_transition() {
+        let job = Job::default();
+        let moved = InvalidTransition::new(JobState::Cancelling, JobState::Finish);
+        assert!(skip_invalid_transition(&job, Err(moved.into())).is_ok());
+        assert!(skip_invalid_transition(&job, Err(anyhow!("db down"))).is_err());
+        assert!(skip_invalid_transition(&job, Ok(())).is_ok());
+    }
+
+    #[test]
+    fn test_check_run_name() {
+        let check_schedule = |name: &str| {
+            let schedule = Schedule {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            check_run_name("schedule", name, &schedule.run_name(1_700_000_000))
+        };
+        assert!(check_schedule(&"a".repeat(48)).is_ok());
+        let err = check_schedule(&"a".repeat(49)).unwrap_err();
+        assert!(err.to_string().ends_with("at most 48 characters"), "{err}");
+        for name in ["", "-daily", "daily-", "Daily", "daily.run"] {
+            assert!(check_schedule(name).is_err(), "{name}");
+        }
+
+        let check_trigger = |name: &str| {
+            let trigger = Trigger {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            check_run_name(
+                "trigger",
+                name,
+                &trigger.run_name(&"0".repeat(SHORT_HASH_LEN)),
+            )
+        };
+        assert!(check_trigger(&"a".repeat(50)).is_ok());
+        let err = check_trigger(&"a".repeat(51)).unwrap_err();
+        assert!(err.to_string().ends_with("at most 50 characters"), "{err}");
+        for name in ["", "-on-main", "on-main-", "On-main"] {
+            assert!(check_trigger(name).is_err(), "{name}");
+        }
+    }
+}
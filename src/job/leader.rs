@@ -0,0 +1,207 @@
+use crate::core::db::LeaseRepo;
+use anyhow::Result;
+use chrono::Utc;
+use std::{
+    sync::{
+        atomic::{
+            AtomicI64,
+            Ordering,
+        },
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    select,
+    task::JoinSet,
+    time::sleep,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{
+    error,
+    info,
+    warn,
+};
+
+/// instance name used when daemon run without leader election
+pub const DEFAULT_INSTANCE: &str = "jiaoziflow";
+
+/// name of the lease guarding the job backend
+pub const BACKEND_LEASE: &str = "jiaoziflow-backend";
+
+/// Leadership tell whether this daemon instance may run the backend loops
+#[derive(Debug, Clone)]
+pub struct Leadership {
+    instance: String,
+    /// unix time the lease held by this instance is valid until
+    valid_until: Arc<AtomicI64>,
+}
+
+impl Leadership {
+    /// standalone is always leader, used when only one daemon is running
+    pub fn standalone(instance: &str) -> Self {
+        Leadership {
+            instance: instance.to_string(),
+            valid_until: Arc::new(AtomicI64::new(i64::MAX)),
+        }
+    }
+
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    /// is_leader return true until the lease this instance renewed last time expires
+    pub fn is_leader(&self) -> bool {
+        Utc::now().timestamp() < self.valid_until.load(Ordering::SeqCst)
+    }
+}
+
+/// LeaderElector keep renewing the backend lease, only one instance hold it at a time
+pub struct LeaderElector<R>
+where
+    R: LeaseRepo,
+{
+    db: R,
+    lease_duration: Duration,
+    leadership: Leadership,
+}
+
+impl<R> LeaderElector<R>
+where
+    R: LeaseRepo + Clone + Send + Sync + 'static,
+{
+    pub fn new(db: R, instance: &str, lease_duration: Duration) -> Self {
+        LeaderElector {
+            db,
+            lease_duration,
+            leadership: Leadership {
+                instance: instance.to_string(),
+                valid_until: Arc::new(AtomicI64::new(0)),
+            },
+        }
+    }
+
+    pub fn leadership(&self) -> Leadership {
+        self.leadership.clone()
+    }
+
+    /// renew try to take or renew the lease at now, return true if this instance is leader
+    async fn renew(db: &R, leadership: &Leadership, lease_duration: Duration, now: i64) -> bool {
+        let expires_at = now + lease_duration.as_secs() as i64;
+        let acquired = db
+            .acquire_lease(BACKEND_LEASE, &leadership.instance, now, expires_at)
+            .await
+            .unwrap_or_else(|err| {
+                error!("renew lease {err}");
+                false
+            });
+
+        let was_leader = leadership.valid_until.load(Ordering::SeqCst) > now;
+        if acquired {
+            //step down a third of lease before it expires, leave the margin to finish the
+            //running step before another instance take over
+            let valid_until = now + lease_duration.as_secs() as i64 * 2 / 3;
+            leadership.valid_until.store(valid_until, Ordering::SeqCst);
+            if !was_leader {
+                info!("instance {} become leader", leadership.instance);
+            }
+        } else if was_leader {
+            //keep leading until lease expire, renew may succeed next time
+            warn!("instance {} fail to renew lease", leadership.instance);
+        }
+        acquired
+    }
+
+    pub fn run_backend(
+        &self,
+        join_set: &mut JoinSet<Result<()>>,
+        token: CancellationToken,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        let leadership = self.leadership.clone();
+        let lease_duration = self.lease_duration;
+
+        join_set.spawn(async move {
+            info!("leader election is running");
+            loop {
+                Self::renew(&db, &leadership, lease_duration, Utc::now().timestamp()).await;
+
+                select! {
+                    _ = token.cancelled() => {
+                        leadership.valid_until.store(0, Ordering::SeqCst);
+                        return db.release_lease(BACKEND_LEASE, &leadership.instance).await;
+                    }
+                    _ = sleep(lease_duration / 3) => {}
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::Lease;
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct MemoryLeaseRepo {
+        lease: Arc<Mutex<Option<Lease>>>,
+    }
+
+    impl LeaseRepo for MemoryLeaseRepo {
+        async fn acquire_lease(
+            &self,
+            name: &str,
+            holder: &str,
+            now: i64,
+            expires_at: i64,
+        ) -> Result<bool> {
+            let mut lease = self.lease.lock().unwrap();
+            if lease
+                .as_ref()
+                .is_some_and(|lease| lease.holder != holder && lease.expires_at > now)
+            {
+                return Ok(false);
+            }
+            *lease = Some(Lease {
+                name: name.to_string(),
+                holder: holder.to_string(),
+                expires_at,
+                renewed_at: now,
+            });
+            Ok(true)
+        }
+
+        async fn release_lease(&self, _name: &str, holder: &str) -> Result<()> {
+            let mut lease = self.lease.lock().unwrap();
+            if let Some(lease) = lease.as_mut().filter(|lease| lease.holder == holder) {
+                lease.expires_at = 0;
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_renew() {
+        let db = MemoryLeaseRepo::default();
+        let duration = Duration::from_secs(15);
+        let a = LeaderElector::new(db.clone(), "a", duration);
+        let b = LeaderElector::new(db.clone(), "b", duration);
+
+        assert!(LeaderElector::renew(&db, &a.leadership, duration, 100).await);
+        assert_eq!(a.leadership.valid_until.load(Ordering::SeqCst), 110);
+        assert!(!LeaderElector::renew(&db, &b.leadership, duration, 105).await);
+        assert!(LeaderElector::renew(&db, &a.leadership, duration, 110).await);
+
+        //a stopped renewing, b take over after lease expired
+        assert!(!LeaderElector::renew(&db, &b.leadership, duration, 124).await);
+        assert!(LeaderElector::renew(&db, &b.leadership, duration, 125).await);
+        assert!(!LeaderElector::renew(&db, &a.leadership, duration, 126).await);
+
+        db.release_lease(BACKEND_LEASE, "b").await.unwrap();
+        assert!(LeaderElector::renew(&db, &a.leadership, duration, 127).await);
+        assert!(Leadership::standalone("c").is_leader());
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{
+    core::db::{
+        DataState,
+        Direction,
+        JobDbRepo,
+        JobState,
+        ListJobParams,
+        MainDbRepo,
+    },
+    dag::Dag,
+    dbrepo::MongoRunDbRepo,
+    job::leader::{
+        Leadership,
+        DEFAULT_INSTANCE,
+    },
+    utils::metrics::{
+        MetricFamily,
+        Registry,
+        LATENCY_BUCKETS,
+    },
+};
+use anyhow::Result;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    select,
+    task::JoinSet,
+    time::sleep,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{
+    error,
+    info,
+};
+
+/// buckets in seconds of job deploy duration
+const DEPLOY_BUCKETS: [f64; 8] = [1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+/// DaemonMetrics are exposed by daemon at /metrics
+#[derive(Clone)]
+pub struct DaemonMetrics {
+    registry: Registry,
+    /// `jzflow_jobs{state}` gauge, number of jobs in each state
+    pub jobs: Arc<MetricFamily>,
+    /// `jzflow_job_deploy_duration_seconds` histogram, time to deploy a job to cluster
+    pub deploy_duration: Arc<MetricFamily>,
+    /// `jzflow_job_deploy_failures_total` counter, jobs failed to deploy
+    pub deploy_failures: Arc<MetricFamily>,
+    /// `jzflow_backend_loop_duration_seconds{backend}` histogram, time of one iteration of a
+    /// backend loop, backend is job, hook or trigger
+    pub loop_duration: Arc<MetricFamily>,
+    /// `jzflow_backend_loop_errors_total{backend}` counter, errors in backend loops
+    pub loop_errors: Arc<MetricFamily>,
+    /// `jzflow_http_request_duration_seconds{method,route,status}` histogram, latency of api
+    /// requests, route is the matched pattern eg. /api/v1/job/{id}
+    pub request_duration: Arc<MetricFamily>,
+    /// `jzflow_node_backlog{job,node}` gauge, incoming batches received or processing by node of
+    /// running jobs
+    pub node_backlog: Arc<MetricFamily>,
+}
+
+impl Default for DaemonMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DaemonMetrics {
+    pub fn new() -> Self {
+        let registry = Registry::default();
+        DaemonMetrics {
+            jobs: registry.gauge("jzflow_jobs", "number of jobs in each state", &["state"]),
+            deploy_duration: registry.histogram(
+                "jzflow_job_deploy_duration_seconds",
+                "time to deploy a job to cluster",
+                &[],
+                &DEPLOY_BUCKETS,
+            ),
+            deploy_failures: registry.counter(
+                "jzflow_job_deploy_failures_total",
+                "jobs failed to deploy",
+                &[],
+            ),
+            loop_duration: registry.histogram(
+                "jzflow_backend_loop_duration_seconds",
+                "time of one iteration of a backend loop",
+                &["backend"],
+                &LATENCY_BUCKETS,
+            ),
+            loop_errors: registry.counter(
+                "jzflow_backend_loop_errors_total",
+                "errors in backend loops",
+                &["backend"],
+            ),
+            request_duration: registry.histogram(
+                "jzflow_http_request_duration_seconds",
+                "latency of api requests",
+                &["method", "route", "status"],
+                &LATENCY_BUCKETS,
+            ),
+            node_backlog: registry.gauge(
+                "jzflow_node_backlog",
+                "incoming batches received or processing by node of running jobs",
+                &["job", "node"],
+            ),
+            registry,
+        }
+    }
+
+    /// render all metrics in prometheus text format
+    pub fn render(&self) -> String {
+        self.registry.render()
+    }
+
+    /// set_job_counts replace jobs gauge, states without jobs are reported as 0
+    pub fn set_job_counts(&self, counts: &[(JobState, u64)]) {
+        let values = JobState::ALL.into_iter().map(|state| {
+            let count = counts
+                .iter()
+                .find(|(counted, _)| *counted == state)
+                .map_or(0, |(_, count)| *count);
+            (vec![format!("{state:?}")], count as f64)
+        });
+        self.jobs.replace(values);
+    }
+}
+
+/// MetricsCollector periodically compute gauges from database, so scrapes dont hit mongo
+pub struct MetricsCollector<MAINR>
+where
+    MAINR: MainDbRepo,
+{
+    db: MAINR,
+    connection_string: String,
+    metrics: DaemonMetrics,
+    interval: Duration,
+    leadership: Leadership,
+}
+
+impl<MAINR> MetricsCollector<MAINR>
+where
+    MAINR: MainDbRepo,
+{
+    pub fn new(
+        db: MAINR,
+        connection_string: &str,
+        metrics: DaemonMetrics,
+        interval: Duration,
+    ) -> Self {
+        MetricsCollector {
+            db,
+            connection_string: connection_string.to_string(),
+            metrics,
+            interval,
+            leadership: Leadership::standalone(DEFAULT_INSTANCE),
+        }
+    }
+
+    /// set_leadership only collect while this instance is leader, so that gauges of replicas
+    /// can be summed up
+    pub fn set_leadership(mut self, leadership: Leadership) -> Self {
+        self.leadership = leadership;
+        self
+    }
+
+    pub fn run_backend(
+        &self,
+        join_set: &mut JoinSet<Result<()>>,
+        token: CancellationToken,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        let connection_string = self.connection_string.clone();
+        let metrics = self.metrics.clone();
+        let interval = self.interval;
+        let leadership = self.leadership.clone();
+
+        join_set.spawn(async move {
+            info!("metrics collector is running");
+            loop {
+                if leadership.is_leader() {
+                    if let Err(err) = Self::collect(&db, &connection_string, &metrics).await {
+                        error!("collect metrics {err}");
+                    }
+                } else {
+                    metrics.jobs.replace([]);
+                    metrics.node_backlog.replace([]);
+                }
+
+                select! {
+                    _ = token.cancelled() => {
+                        return Ok(());
+                    }
+                    _ = sleep(interval) => {}
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn collect(db: &MAINR, connection_string: &str, metrics: &DaemonMetrics) -> Result<()> {
+        metrics.set_job_counts(&db.count_jobs().await?);
+
+        let running_jobs_params = &ListJobParams {
+            states: vec![JobState::Running],
+            ..Default::default()
+        };
+        let mut backlogs = HashMap::new();
+        for job in db.list_jobs(running_jobs_params).await? {
+            let db_url = format!("{connection_string}/{}", job.name);
+            let job_db = MongoRunDbRepo::new(&db_url).await?;
+            let dag = Dag::from_json(job.graph_json.as_str())?;
+            for node in dag.iter() {
+                let backlog = job_db
+                    .count(
+                        &node.name,
+                        &[&DataState::Received, &DataState::Assigned],
+                        Some(&Direction::In),
+                    )
+                    .await?;
+                backlogs.insert(vec![job.name.clone(), node.name.clone()], backlog as f64);
+            }
+        }
+        metrics.node_backlog.replace(backlogs);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemon_metrics() {
+        let metrics = DaemonMetrics::new();
+        metrics.set_job_counts(&[(JobState::Running, 2), (JobState::Finish, 5)]);
+        metrics.loop_errors.inc(&["job"]);
+        metrics
+            .request_duration
+            .observe(&["GET", "/api/v1/job/{id}", "200"], 0.02);
+
+        let text = metrics.render();
+        for line in [
+            "# TYPE jzflow_jobs gauge",
+            r#"jzflow_jobs{state="Running"} 2"#,
+            r#"jzflow_jobs{state="Queued"} 0"#,
+            "# TYPE jzflow_job_deploy_duration_seconds histogram",
+            "# TYPE jzflow_job_deploy_failures_total counter",
+            "# TYPE jzflow_backend_loop_duration_seconds histogram",
+            r#"jzflow_backend_loop_errors_total{backend="job"} 1"#,
+            r#"jzflow_http_request_duration_seconds_count{method="GET",route="/api/v1/job/{id}",status="200"} 1"#,
+            "# TYPE jzflow_node_backlog gauge",
+        ] {
+            assert!(text.lines().any(|l| l == line), "{line} not in {text}");
+        }
+    }
+}
@@ -1 +1,9 @@
+pub mod hooks;
 pub mod job_mgr;
+pub mod leader;
+pub mod metrics;
+pub mod queue;
+pub mod summary;
+pub mod trigger;
+pub mod watch;
+pub mod watchdog;
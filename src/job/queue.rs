@@ -0,0 +1,218 @@
+use crate::core::db::{
+    Job,
+    JobState,
+};
+use anyhow::{
+    anyhow,
+    Result,
+};
+use mongodb::bson::oid::ObjectId;
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+};
+
+/// holds_capacity return true if job is deploying, running, paused or draining in cluster
+pub fn holds_capacity(state: &JobState) -> bool {
+    matches!(
+        state,
+        JobState::Selected
+            | JobState::Deployed
+            | JobState::Running
+            | JobState::Paused
+            | JobState::Cancelling
+    )
+}
+
+/// queue_order order jobs by priority from high to low, then by submission time
+pub fn queue_order(a: &Job, b: &Job) -> Ordering {
+    b.priority
+        .cmp(&a.priority)
+        .then(a.created_at.cmp(&b.created_at))
+        .then(a.id.cmp(&b.id))
+}
+
+/// queue_positions return the position of every queued job, starting from 1
+pub fn queue_positions(jobs: &[Job]) -> HashMap<ObjectId, usize> {
+    let mut queued: Vec<_> = jobs
+        .iter()
+        .filter(|job| job.state == JobState::Queued)
+        .collect();
+    queued.sort_by(|a, b| queue_order(a, b));
+    queued
+        .into_iter()
+        .enumerate()
+        .map(|(index, job)| (job.id, index + 1))
+        .collect()
+}
+
+/// QueueLimits cap the number of jobs holding cluster resources at the same time
+#[derive(Debug, Default, Clone)]
+pub struct QueueLimits {
+    max_running: Option<usize>,
+    max_running_per_user: Option<usize>,
+    /// key=value label -> limit
+    max_running_per_label: HashMap<String, usize>,
+}
+
+impl QueueLimits {
+    pub fn set_max_running(mut self, max_running: usize) -> Self {
+        self.max_running = Some(max_running);
+        self
+    }
+
+    pub fn set_max_running_per_user(mut self, max_running: usize) -> Self {
+        self.max_running_per_user = Some(max_running);
+        self
+    }
+
+    /// set_max_running_per_label parse limit in format key=value:limit
+    pub fn set_max_running_per_label(mut self, limit: &str) -> Result<Self> {
+        let (label, max_running) = limit
+            .rsplit_once(':')
+            .filter(|(label, _)| label.contains('='))
+            .ok_or(anyhow!("label limit {limit} must be key=value:limit"))?;
+        let max_running = max_running
+            .parse()
+            .map_err(|err| anyhow!("label limit {limit} {err}"))?;
+        self.max_running_per_label
+            .insert(label.to_string(), max_running);
+        Ok(self)
+    }
+
+    /// admit pick queued jobs which fit in the limits, in priority and FIFO order. a job
+    /// blocked by user or label limit dont block jobs of others behind it
+    pub fn admit<'a>(&self, queued: &'a [Job], active: &[Job]) -> Vec<&'a Job> {
+        let mut usage = Usage::default();
+        for job in active.iter().filter(|job| holds_capacity(&job.state)) {
+            usage.add(job);
+        }
+
+        let mut queued: Vec<_> = queued.iter().collect();
+        queued.sort_by(|a, b| queue_order(a, b));
+
+        let mut admitted = vec![];
+        for job in queued {
+            if self.max_running.is_some_and(|max| usage.total >= max) {
+                break;
+            }
+            if let (Some(max), Some(user)) = (self.max_running_per_user, job.owner.as_ref()) {
+                if usage.per_user.get(user).is_some_and(|count| *count >= max) {
+                    continue;
+                }
+            }
+            if job_labels(job).any(|label| {
+                self.max_running_per_label
+                    .get(&label)
+                    .zip(usage.per_label.get(&label))
+                    .is_some_and(|(max, count)| count >= max)
+            }) {
+                continue;
+            }
+
+            usage.add(job);
+            admitted.push(job);
+        }
+        admitted
+    }
+}
+
+/// Usage count jobs holding capacity
+#[derive(Default)]
+struct Usage {
+    total: usize,
+    per_user: HashMap<String, usize>,
+    per_label: HashMap<String, usize>,
+}
+
+impl Usage {
+    fn add(&mut self, job: &Job) {
+        self.total += 1;
+        if let Some(user) = job.owner.as_ref() {
+            *self.per_user.entry(user.clone()).or_default() += 1;
+        }
+        for label in job_labels(job) {
+            *self.per_label.entry(label).or_default() += 1;
+        }
+    }
+}
+
+fn job_labels(job: &Job) -> impl Iterator<Item = String> + '_ {
+    job.labels
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_job(name: &str, priority: i32, created_at: i64, user: &str, team: &str) -> Job {
+        Job {
+            name: name.to_string(),
+            state: JobState::Queued,
+            priority,
+            owner: Some(user.to_string()),
+            labels: HashMap::from([("team".to_string(), team.to_string())]),
+            created_at,
+            ..Default::default()
+        }
+    }
+
+    fn names(jobs: Vec<&Job>) -> Vec<&str> {
+        jobs.into_iter().map(|job| job.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_admit() {
+        let queued = vec![
+            new_job("a", 0, 1, "alice", "x"),
+            new_job("b", 0, 2, "bob", "y"),
+            new_job("c", 5, 3, "alice", "x"),
+            new_job("d", 0, 4, "carol", "x"),
+        ];
+
+        let limits = QueueLimits::default();
+        assert_eq!(names(limits.admit(&queued, &[])), vec!["c", "a", "b", "d"]);
+
+        let limits = QueueLimits::default().set_max_running(2);
+        assert_eq!(names(limits.admit(&queued, &[])), vec!["c", "a"]);
+
+        let mut running = new_job("r", 0, 0, "bob", "x");
+        running.state = JobState::Running;
+        let mut finished = new_job("f", 0, 0, "bob", "x");
+        finished.state = JobState::Finish;
+        let active = vec![running, finished];
+        assert_eq!(names(limits.admit(&queued, &active)), vec!["c"]);
+        //paused job keep its resources
+        let mut paused = new_job("p", 0, 0, "bob", "y");
+        paused.state = JobState::Paused;
+        assert_eq!(names(limits.admit(&queued, &[paused])), vec!["c"]);
+
+        let limits = QueueLimits::default().set_max_running_per_user(1);
+        assert_eq!(names(limits.admit(&queued, &[])), vec!["c", "b", "d"]);
+
+        let limits = QueueLimits::default()
+            .set_max_running_per_label("team=x:2")
+            .unwrap();
+        assert_eq!(names(limits.admit(&queued, &active)), vec!["c", "b"]);
+
+        assert!(QueueLimits::default()
+            .set_max_running_per_label("team:2")
+            .is_err());
+    }
+
+    #[test]
+    fn test_queue_positions() {
+        let mut jobs = vec![
+            new_job("a", 0, 1, "alice", "x"),
+            new_job("b", 1, 2, "alice", "x"),
+            new_job("c", 0, 0, "alice", "x"),
+        ];
+        jobs[2].state = JobState::Running;
+        let positions = queue_positions(&jobs);
+        assert_eq!(positions.get(&jobs[1].id), Some(&1));
+        assert_eq!(positions.get(&jobs[0].id), Some(&2));
+        assert_eq!(positions.get(&jobs[2].id), None);
+    }
+}
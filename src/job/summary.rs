@@ -0,0 +1,134 @@
+use crate::core::db::{
+    DataRecord,
+    DataState,
+    Direction,
+    Node,
+    NodeSummary,
+};
+
+/// is_pending return true if incoming data is waiting or processing
+fn is_pending(record: &DataRecord) -> bool {
+    matches!(record.state, DataState::Received | DataState::Assigned)
+}
+
+/// summarize_node compute run metrics of node from its data records
+pub fn summarize_node(node: &Node, records: &[DataRecord]) -> NodeSummary {
+    let mut summary = NodeSummary {
+        node_name: node.node_name.clone(),
+        wall_time: node
+            .ready_at
+            .zip(node.finished_at)
+            .map(|(ready_at, finished_at)| finished_at - ready_at),
+        ..Default::default()
+    };
+
+    //batch enter backlog when received and leave it when processing ended
+    let mut backlog_changes = vec![];
+    let mut latencies = vec![];
+    for record in records {
+        if record.state == DataState::Error {
+            summary.errors += 1;
+        }
+        match record.direction {
+            Direction::In => {
+                summary.batches_in += 1;
+                summary.files_in += record.size as u64;
+                backlog_changes.push((record.created_at, 1));
+                if !is_pending(record) {
+                    backlog_changes.push((record.updated_at, -1));
+                }
+                if record.state == DataState::Processed {
+                    latencies.push(record.updated_at - record.created_at);
+                }
+            }
+            Direction::Out => {
+                summary.batches_out += 1;
+                summary.files_out += record.size as u64;
+            }
+        }
+    }
+
+    //leaving is applied before entering at the same time
+    backlog_changes.sort();
+    let mut backlog: i64 = 0;
+    for (_, change) in backlog_changes {
+        backlog += change;
+        summary.peak_backlog = summary.peak_backlog.max(backlog as u64);
+    }
+
+    if !latencies.is_empty() {
+        summary.avg_batch_latency =
+            Some(latencies.iter().sum::<i64>() as f64 / latencies.len() as f64);
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::{
+        DataFlag,
+        NodeType,
+        TrackerState,
+    };
+
+    fn new_record(
+        direction: Direction,
+        state: DataState,
+        size: u32,
+        created_at: i64,
+        updated_at: i64,
+    ) -> DataRecord {
+        DataRecord {
+            node_name: "compute".to_string(),
+            id: String::new(),
+            priority: 0,
+            flag: DataFlag::default(),
+            size,
+            state,
+            direction,
+            machine: String::new(),
+            sent: vec![],
+            created_at,
+            updated_at,
+        }
+    }
+
+    #[test]
+    fn test_summarize_node() {
+        let node = Node {
+            node_name: "compute".to_string(),
+            state: TrackerState::Finish,
+            node_type: NodeType::CoputeUnit,
+            up_nodes: vec![],
+            outgoing_streams: vec![],
+            ready_at: Some(100),
+            finished_at: Some(160),
+            created_at: 90,
+            updated_at: 160,
+        };
+        let records = vec![
+            new_record(Direction::In, DataState::Processed, 2, 100, 110),
+            new_record(Direction::In, DataState::Processed, 3, 105, 125),
+            new_record(Direction::In, DataState::Error, 1, 110, 115),
+            new_record(Direction::In, DataState::Received, 4, 130, 130),
+            new_record(Direction::Out, DataState::Sent, 5, 110, 120),
+        ];
+
+        let summary = summarize_node(&node, &records);
+        assert_eq!(
+            summary,
+            NodeSummary {
+                node_name: "compute".to_string(),
+                wall_time: Some(60),
+                batches_in: 4,
+                batches_out: 1,
+                files_in: 10,
+                files_out: 5,
+                errors: 1,
+                peak_backlog: 2,
+                avg_batch_latency: Some(15.0),
+            }
+        );
+    }
+}
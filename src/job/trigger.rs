@@ -0,0 +1,355 @@
+use crate::{
+    core::db::{
+        Job,
+        MainDbRepo,
+        Trigger,
+        TriggerCursor,
+    },
+    dbrepo::is_duplicate_key,
+    job::{
+        leader::{
+            Leadership,
+            DEFAULT_INSTANCE,
+        },
+        metrics::DaemonMetrics,
+    },
+    utils::StdIntoAnyhowResult,
+};
+use anyhow::Result;
+use chrono::Utc;
+use jiaozifs_client_rs::apis::{
+    branches_api::get_branch,
+    commit_api::compare_commit,
+    configuration::Configuration,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+use tokio::{
+    select,
+    task::JoinSet,
+    time::sleep,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{
+    error,
+    info,
+};
+
+/// check fetch head of trigger branch from jiaozifs, return the new cursor and the commit to run.
+/// if trigger has a path, commit is only run when changes since last seen commit touch it
+pub async fn check(
+    configuration: &Configuration,
+    trigger: &Trigger,
+    now: i64,
+) -> Result<(TriggerCursor, Option<String>)> {
+    let branch = get_branch(
+        configuration,
+        &trigger.owner,
+        &trigger.repo,
+        &trigger.branch,
+    )
+    .await
+    .anyhow()?;
+
+    let (cursor, commit) = trigger.observe(&branch.commit_hash, now);
+    let (Some(commit), Some(path), Some(base)) = (
+        commit.as_ref(),
+        trigger.path.as_ref(),
+        trigger.cursor.last_seen_commit.as_ref(),
+    ) else {
+        return Ok((cursor, commit));
+    };
+
+    let changes = compare_commit(
+        configuration,
+        &trigger.owner,
+        &trigger.repo,
+        &format!("{base}...{commit}"),
+        Some(path),
+    )
+    .await
+    .anyhow()?;
+    if changes.is_empty() {
+        info!("trigger {} skip {commit}, {path} not changed", trigger.name);
+        return Ok((cursor, None));
+    }
+    Ok((cursor, Some(commit.clone())))
+}
+
+/// TriggerPoller poll branches of all triggers and create jobs for new commits
+pub struct TriggerPoller<MAINR>
+where
+    MAINR: MainDbRepo,
+{
+    db: MAINR,
+    configuration: Configuration,
+    interval: Duration,
+    leadership: Leadership,
+    metrics: DaemonMetrics,
+}
+
+impl<MAINR> TriggerPoller<MAINR>
+where
+    MAINR: MainDbRepo,
+{
+    pub fn new(db: MAINR, configuration: Configuration, interval: Duration) -> Self {
+        TriggerPoller {
+            db,
+            configuration,
+            interval,
+            leadership: Leadership::standalone(DEFAULT_INSTANCE),
+            metrics: DaemonMetrics::new(),
+        }
+    }
+
+    /// set_leadership only poll triggers while this instance is leader
+    pub fn set_leadership(mut self, leadership: Leadership) -> Self {
+        self.leadership = leadership;
+        self
+    }
+
+    pub fn set_metrics(mut self, metrics: DaemonMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn run_backend(
+        &self,
+        join_set: &mut JoinSet<Result<()>>,
+        token: CancellationToken,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        let configuration = self.configuration.clone();
+        let interval = self.interval;
+        let leadership = self.leadership.clone();
+        let metrics = self.metrics.clone();
+
+        join_set.spawn(async move {
+            info!("trigger poller is running");
+            loop {
+                let loop_start = Instant::now();
+                //followers leave triggers to the leader
+                let triggers = if leadership.is_leader() {
+                    db.list_triggers().await
+                } else {
+                    Ok(vec![])
+                };
+                match triggers {
+                    Ok(triggers) => {
+                        for trigger in triggers {
+                            if let Err(err) = Self::poll(&db, &configuration, &trigger).await {
+                                error!("poll trigger {} {err}", trigger.name);
+                                metrics.loop_errors.inc(&["trigger"]);
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        error!("list triggers {err}");
+                        metrics.loop_errors.inc(&["trigger"]);
+                    }
+                }
+                metrics
+                    .loop_duration
+                    .observe(&["trigger"], loop_start.elapsed().as_secs_f64());
+
+                select! {
+                    _ = token.cancelled() => {
+                        return Ok(());
+                    }
+                    _ = sleep(interval) => {}
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn poll(db: &MAINR, configuration: &Configuration, trigger: &Trigger) -> Result<()> {
+        let now = Utc::now().timestamp();
+        let (cursor, commit) = check(configuration, trigger, now).await?;
+        if cursor == trigger.cursor {
+            return Ok(());
+        }
+
+        //create run before moving cursor so a failed insert is retried by next poll,
+        //run is named by commit, unique job name keep it from being created twice
+        if let Some(commit) = commit.as_ref() {
+            let run_name = trigger.run_name(commit);
+            let job = Job {
+                name: run_name.clone(),
+                graph_json: trigger.render_graph(&run_name, commit)?,
+                trigger_id: Some(trigger.id),
+                owner: trigger.created_by.clone(),
+                created_at: now,
+                updated_at: now,
+                ..Default::default()
+            }
+            .set_options(&trigger.job_options);
+            match db.insert(&job).await {
+                Ok(_) => info!("trigger {} create run {run_name} at {commit}", trigger.name),
+                Err(err) if is_duplicate_key(&err) => {
+                    info!("trigger {} run {run_name} already created", trigger.name)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let last_run_at = commit.as_ref().map(|_| now);
+        db.update_trigger_cursor(&trigger.id, &trigger.cursor, &cursor, last_run_at)
+            .await
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        web,
+        App,
+        HttpResponse,
+        HttpServer,
+    };
+    use jiaozifs_client_rs::models::{
+        change::Action,
+        Branch,
+        Change,
+    };
+    use std::{
+        collections::HashMap,
+        sync::{
+            Arc,
+            Mutex,
+        },
+    };
+
+    /// StubRepo emulate branch and compare endpoints of a jiaozifs repository
+    #[derive(Default)]
+    struct StubRepo {
+        head: String,
+        //basehead -> changed paths
+        changes: HashMap<String, Vec<String>>,
+    }
+
+    async fn stub_branch(
+        repo: web::Data<Mutex<StubRepo>>,
+        query: web::Query<HashMap<String, String>>,
+    ) -> HttpResponse {
+        let branch = Branch {
+            name: query.get("refName").cloned().unwrap_or_default(),
+            commit_hash: repo.lock().unwrap().head.clone(),
+            ..Default::default()
+        };
+        HttpResponse::Ok().json(branch)
+    }
+
+    async fn stub_compare(
+        repo: web::Data<Mutex<StubRepo>>,
+        path: web::Path<(String, String, String)>,
+        query: web::Query<HashMap<String, String>>,
+    ) -> HttpResponse {
+        let prefix = query.get("path").cloned().unwrap_or_default();
+        let changes: Vec<_> = repo
+            .lock()
+            .unwrap()
+            .changes
+            .get(&path.2)
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|changed| changed.starts_with(&prefix))
+            .map(|changed| Change::new(changed, Action::Variant1))
+            .collect();
+        HttpResponse::Ok().json(changes)
+    }
+
+    async fn start_stub(repo: Arc<Mutex<StubRepo>>) -> Configuration {
+        let data = web::Data::from(repo);
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(data.clone())
+                .route(
+                    "/api/v1/repos/{owner}/{repo}/branch",
+                    web::get().to(stub_branch),
+                )
+                .route(
+                    "/api/v1/repos/{owner}/{repo}/compare/{basehead}",
+                    web::get().to(stub_compare),
+                )
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let addr = server.addrs()[0];
+        tokio::spawn(server.run());
+
+        Configuration {
+            base_path: format!("http://{addr}/api/v1"),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let repo = Arc::new(Mutex::new(StubRepo {
+            head: "a".to_string(),
+            ..Default::default()
+        }));
+        let configuration = start_stub(repo.clone()).await;
+
+        let mut trigger = Trigger {
+            name: "on-main".to_string(),
+            owner: "jimmy".to_string(),
+            repo: "mnist".to_string(),
+            branch: "main".to_string(),
+            debounce: 10,
+            ..Default::default()
+        };
+
+        let (cursor, commit) = check(&configuration, &trigger, 100).await.unwrap();
+        assert_eq!(cursor.last_seen_commit.as_deref(), Some("a"));
+        assert!(commit.is_none());
+        trigger.cursor = cursor;
+
+        //rapid pushes are debounced
+        repo.lock().unwrap().head = "b".to_string();
+        let (cursor, commit) = check(&configuration, &trigger, 101).await.unwrap();
+        assert!(commit.is_none());
+        trigger.cursor = cursor;
+        repo.lock().unwrap().head = "c".to_string();
+        let (cursor, commit) = check(&configuration, &trigger, 105).await.unwrap();
+        assert!(commit.is_none());
+        trigger.cursor = cursor;
+        let (cursor, commit) = check(&configuration, &trigger, 115).await.unwrap();
+        assert_eq!(commit.as_deref(), Some("c"));
+        trigger.cursor = cursor;
+
+        //commits not touching path are skipped
+        trigger.path = Some("data/".to_string());
+        trigger.debounce = 0;
+        repo.lock().unwrap().head = "d".to_string();
+        repo.lock()
+            .unwrap()
+            .changes
+            .insert("c...d".to_string(), vec!["README.md".to_string()]);
+        let (cursor, commit) = check(&configuration, &trigger, 120).await.unwrap();
+        assert_eq!(cursor.last_seen_commit.as_deref(), Some("d"));
+        assert!(commit.is_none());
+        trigger.cursor = cursor;
+
+        repo.lock().unwrap().head = "e".to_string();
+        repo.lock()
+            .unwrap()
+            .changes
+            .insert("d...e".to_string(), vec!["data/train.csv".to_string()]);
+        let (_, commit) = check(&configuration, &trigger, 130).await.unwrap();
+        assert_eq!(commit.as_deref(), Some("e"));
+
+        let unreachable = Configuration {
+            base_path: "http://127.0.0.1:1/api/v1".to_string(),
+            ..Default::default()
+        };
+        assert!(check(&unreachable, &trigger, 140).await.is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{
+    core::db::Job,
+    driver::NodeStatus,
+    job::job_mgr::JobDetails,
+};
+use futures::{
+    stream,
+    Stream,
+};
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::VecDeque;
+use tokio::sync::watch;
+
+/// JobEvent is a change of job details pushed to watchers, the first event is always a snapshot
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobEvent {
+    /// full details of job
+    Snapshot { details: JobDetails },
+    /// job record changed, eg. its state
+    JobChanged { job: Job },
+    /// state, backlog or pods of node changed
+    NodeChanged { node: NodeStatus },
+    /// job stopped running, node status is not available any more
+    NodesCleared,
+}
+
+impl JobEvent {
+    /// redacted hide secrets of job in event, for events sent to watchers
+    pub fn redacted(self) -> JobEvent {
+        match self {
+            JobEvent::Snapshot { details } => JobEvent::Snapshot {
+                details: details.redacted(),
+            },
+            JobEvent::JobChanged { job } => JobEvent::JobChanged {
+                job: job.redacted(),
+            },
+            event => event,
+        }
+    }
+}
+
+/// diff_details compute events turning prev into cur
+pub fn diff_details(prev: Option<&JobDetails>, cur: &JobDetails) -> Vec<JobEvent> {
+    let Some(prev) = prev else {
+        return vec![JobEvent::Snapshot {
+            details: cur.clone(),
+        }];
+    };
+
+    let mut events = vec![];
+    if prev.job != cur.job {
+        events.push(JobEvent::JobChanged {
+            job: cur.job.clone(),
+        });
+    }
+    match (prev.node_status.as_ref(), cur.node_status.as_ref()) {
+        (_, Some(cur_nodes)) => {
+            let prev_nodes = prev.node_status.as_deref().unwrap_or_default();
+            for node in cur_nodes {
+                if !prev_nodes.contains(node) {
+                    events.push(JobEvent::NodeChanged { node: node.clone() });
+                }
+            }
+        }
+        (Some(_), None) => events.push(JobEvent::NodesCleared),
+        (None, None) => {}
+    }
+    events
+}
+
+/// job_events turn details published by JobManager::watch_job into events, the stream end when
+/// job was deleted
+pub fn job_events(
+    mut rx: watch::Receiver<Option<JobDetails>>,
+) -> impl Stream<Item = JobEvent> + Send + 'static {
+    //send details computed before this watcher subscribed
+    rx.mark_changed();
+    stream::unfold(
+        (rx, None, VecDeque::<JobEvent>::new()),
+        |(mut rx, mut prev, mut pending)| async move {
+            loop {
+                if let Some(event) = pending.pop_front() {
+                    return Some((event, (rx, prev, pending)));
+                }
+                if rx.changed().await.is_err() {
+                    return None;
+                }
+                let Some(cur) = rx.borrow_and_update().clone() else {
+                    continue;
+                };
+                pending.extend(diff_details(prev.as_ref(), &cur));
+                prev = Some(cur);
+            }
+        },
+    )
+}
+
+/// apply_event update details watched so far with event
+pub fn apply_event(details: &mut Option<JobDetails>, event: JobEvent) {
+    match (details.as_mut(), event) {
+        (_, JobEvent::Snapshot { details: snapshot }) => *details = Some(snapshot),
+        (Some(details), JobEvent::JobChanged { job }) => details.job = job,
+        (Some(details), JobEvent::NodeChanged { node }) => {
+            let nodes = details.node_status.get_or_insert_with(Vec::new);
+            match nodes.iter_mut().find(|status| status.name == node.name) {
+                Some(status) => *status = node,
+                None => nodes.push(node),
+            }
+        }
+        (Some(details), JobEvent::NodesCleared) => details.node_status = None,
+        //changes before snapshot can not happen
+        (None, _) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::{
+        JobState,
+        TrackerState,
+    };
+    use std::collections::HashMap;
+
+    fn new_node(name: &str, state: TrackerState, data_count: usize) -> NodeStatus {
+        NodeStatus {
+            name: name.to_string(),
+            state,
+            data_count,
+            replicas: 1,
+            storage: String::new(),
+            pods: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_diff_details() {
+        let job = Job {
+            state: JobState::Deployed,
+            ..Default::default()
+        };
+        let mut details = JobDetails {
+            job: job.clone(),
+            node_status: None,
+        };
+        let mut watched = None;
+        for event in diff_details(None, &details) {
+            apply_event(&mut watched, event);
+        }
+        assert_eq!(watched.as_ref(), Some(&details));
+
+        let mut steps = vec![];
+        details.job.state = JobState::Running;
+        details.node_status = Some(vec![
+            new_node("a", TrackerState::Ready, 0),
+            new_node("b", TrackerState::Ready, 0),
+        ]);
+        steps.push(details.clone());
+        details.node_status.as_mut().unwrap()[1].data_count = 3;
+        steps.push(details.clone());
+        details.job.state = JobState::Finish;
+        details.node_status = None;
+        steps.push(details.clone());
+
+        for cur in steps {
+            let events = diff_details(watched.as_ref(), &cur);
+            for event in events {
+                apply_event(&mut watched, event);
+            }
+            assert_eq!(watched.as_ref(), Some(&cur));
+        }
+
+        let prev = watched.clone().unwrap();
+        let mut cur = prev.clone();
+        cur.node_status = Some(vec![new_node("a", TrackerState::Ready, 1)]);
+        let events = diff_details(Some(&prev), &cur);
+        assert_eq!(
+            events,
+            vec![JobEvent::NodeChanged {
+                node: new_node("a", TrackerState::Ready, 1)
+            }]
+        );
+        assert!(diff_details(Some(&cur), &cur).is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{
+    core::db::Job,
+    dag::Dag,
+};
+
+/// NodeProgress describe how a node of running job is going
+#[derive(Debug, Default)]
+pub struct NodeProgress {
+    pub node_name: String,
+    pub timeout: Option<u64>,
+    pub stall_timeout: Option<u64>,
+    pub finished: bool,
+    /// node is a source or has incoming data not processed
+    pub has_work: bool,
+    /// last time node state or its data changed
+    pub last_progress: i64,
+}
+
+/// has_deadline return true if job or any node of it set a timeout
+pub fn has_deadline(job: &Job, dag: &Dag) -> bool {
+    job.timeout.is_some()
+        || job.stall_timeout.is_some()
+        || dag
+            .iter()
+            .any(|node| node.spec.timeout.is_some() || node.spec.stall_timeout.is_some())
+}
+
+/// expired_reason return why job should be expired at now, None if job is within all deadlines.
+/// timeouts count active_secs run before started_at, stalls count from started_at
+pub fn expired_reason(
+    timeout: Option<u64>,
+    started_at: i64,
+    active_secs: u64,
+    nodes: &[NodeProgress],
+    now: i64,
+) -> Option<String> {
+    let elapsed = active_secs as i64 + now - started_at;
+    if let Some(timeout) = timeout {
+        if elapsed >= timeout as i64 {
+            return Some(format!("job exceeded timeout {timeout}s"));
+        }
+    }
+
+    for node in nodes.iter().filter(|node| !node.finished) {
+        if let Some(timeout) = node.timeout {
+            if elapsed >= timeout as i64 {
+                return Some(format!(
+                    "node {} exceeded timeout {timeout}s",
+                    node.node_name
+                ));
+            }
+        }
+
+        if let Some(stall_timeout) = node.stall_timeout {
+            let idle = now - node.last_progress.max(started_at);
+            if node.has_work && idle >= stall_timeout as i64 {
+                //reason must stay the same while node is stalled, notify compare it with last one
+                return Some(format!(
+                    "node {} stalled, stall timeout {stall_timeout}s",
+                    node.node_name
+                ));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expired_reason() {
+        let mut nodes = vec![
+            NodeProgress {
+                node_name: "source".to_string(),
+                stall_timeout: Some(60),
+                has_work: true,
+                last_progress: 150,
+                ..Default::default()
+            },
+            NodeProgress {
+                node_name: "sink".to_string(),
+                timeout: Some(300),
+                stall_timeout: Some(60),
+                last_progress: 100,
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(expired_reason(None, 100, 0, &nodes, 200), None);
+        assert_eq!(
+            expired_reason(Some(100), 100, 0, &nodes, 200).unwrap(),
+            "job exceeded timeout 100s"
+        );
+        assert_eq!(
+            expired_reason(None, 100, 0, &nodes, 210).unwrap(),
+            "node source stalled, stall timeout 60s"
+        );
+        assert_eq!(
+            expired_reason(None, 100, 0, &nodes, 210),
+            expired_reason(None, 100, 0, &nodes, 250)
+        );
+
+        //resumed job keep time run before pause, stall count from resume
+        assert_eq!(expired_reason(Some(100), 300, 90, &nodes, 305), None);
+        assert_eq!(
+            expired_reason(Some(100), 300, 90, &nodes, 310).unwrap(),
+            "job exceeded timeout 100s"
+        );
+        assert_eq!(expired_reason(None, 300, 90, &nodes, 350), None);
+        assert_eq!(
+            expired_reason(None, 300, 90, &nodes, 360).unwrap(),
+            "node source stalled, stall timeout 60s"
+        );
+
+        //finished or idle node never stall
+        nodes[0].finished = true;
+        assert_eq!(expired_reason(None, 100, 0, &nodes, 390), None);
+        assert_eq!(
+            expired_reason(None, 100, 0, &nodes, 400).unwrap(),
+            "node sink exceeded timeout 300s"
+        );
+    }
+}
@@ -10,6 +10,10 @@ pub mod nodecontroller {
     tonic::include_proto!("nodecontroller");
 }
 
+pub mod jobservice {
+    tonic::include_proto!("jobservice");
+}
+
 pub mod datatransfer {
     tonic::include_proto!("datatransfer");
 }
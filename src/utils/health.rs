@@ -0,0 +1,145 @@
+use actix_web::HttpResponse;
+use anyhow::{
+    anyhow,
+    Result,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        Arc,
+        Mutex,
+    },
+    time::Duration,
+};
+use tokio::time::timeout;
+
+/// timeout of a single check, probes should fail rather than hang on an unreachable database
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// HealthReport is the body of /healthz and /readyz, status is 503 if any check failed
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    /// "ok" or error of each check
+    pub checks: BTreeMap<String, String>,
+}
+
+impl Default for HealthReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthReport {
+    pub fn new() -> Self {
+        HealthReport {
+            healthy: true,
+            checks: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_check(mut self, name: &str, result: Result<()>) -> Self {
+        let message = match result {
+            Ok(_) => "ok".to_string(),
+            Err(err) => {
+                self.healthy = false;
+                err.to_string()
+            }
+        };
+        self.checks.insert(name.to_string(), message);
+        self
+    }
+
+    /// add_async_check run check with CHECK_TIMEOUT
+    pub async fn add_async_check(
+        self,
+        name: &str,
+        check: impl Future<Output = Result<()>>,
+    ) -> Self {
+        let result = match timeout(CHECK_TIMEOUT, check).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow!("timeout after {CHECK_TIMEOUT:?}")),
+        };
+        self.add_check(name, result)
+    }
+
+    pub fn into_response(self) -> HttpResponse {
+        if self.healthy {
+            HttpResponse::Ok().json(self)
+        } else {
+            HttpResponse::ServiceUnavailable().json(self)
+        }
+    }
+}
+
+/// TaskHealth record whether long running tasks are still running, a task exited by error or
+/// panic leave the process wedged as other tasks keep running
+#[derive(Debug, Clone, Default)]
+pub struct TaskHealth {
+    tasks: Arc<Mutex<BTreeMap<String, bool>>>,
+}
+
+impl TaskHealth {
+    pub fn set_running(&self, name: &str, running: bool) {
+        self.tasks.lock().unwrap().insert(name.to_string(), running);
+    }
+
+    pub fn check(&self, name: &str) -> Result<()> {
+        match self.tasks.lock().unwrap().get(name) {
+            Some(true) => Ok(()),
+            Some(false) => Err(anyhow!("{name} exited")),
+            None => Err(anyhow!("{name} not started")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_health_report() {
+        let report = HealthReport::new()
+            .add_check("server", Ok(()))
+            .add_async_check("mongo", async { Ok(()) })
+            .await;
+        assert!(report.healthy);
+        assert_eq!(report.into_response().status(), StatusCode::OK);
+
+        let report = HealthReport::new()
+            .add_check("server", Ok(()))
+            .add_async_check("mongo", std::future::pending())
+            .await
+            .add_check("state", Err(anyhow!("node is Init")));
+        assert!(!report.healthy);
+        assert_eq!(report.checks["server"], "ok");
+        assert_eq!(report.checks["mongo"], "timeout after 3s");
+        assert_eq!(report.checks["state"], "node is Init");
+        assert_eq!(
+            report.into_response().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[test]
+    fn test_task_health() {
+        let tasks = TaskHealth::default();
+        assert!(tasks.check("server").is_err());
+
+        tasks.set_running("server", true);
+        assert!(tasks.check("server").is_ok());
+
+        let cloned = tasks.clone();
+        cloned.set_running("server", false);
+        assert_eq!(
+            tasks.check("server").unwrap_err().to_string(),
+            "server exited"
+        );
+    }
+}
@@ -1,7 +1,12 @@
-use std::env;
+use std::{
+    env,
+    str::FromStr,
+};
 
 use k8s_openapi::api::core::v1::Pod;
 
+use crate::core::RunMode;
+
 pub fn get_pod_status(pod: &Pod) -> String {
     if let Some(status) = &pod.status {
         if let Some(container_statuses) = &status.container_statuses {
@@ -39,3 +44,11 @@ pub fn get_machine_name() -> String {
             .to_string(),
     }
 }
+
+/// get_run_mode read run mode injected by the job template, stream node dont set this env
+pub fn get_run_mode() -> RunMode {
+    env::var("RUN_MODE")
+        .ok()
+        .and_then(|mode| RunMode::from_str(&mode).ok())
+        .unwrap_or_default()
+}
@@ -0,0 +1,296 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+
+/// content type of the prometheus text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// buckets in seconds used by latency histograms
+pub const LATENCY_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// MetricKind decide the TYPE of a metric family and how its samples are rendered
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    /// upper bounds of buckets in ascending order, +Inf is implied
+    Histogram(Vec<f64>),
+}
+
+#[derive(Debug, Clone)]
+enum Sample {
+    Value(f64),
+    /// buckets are cumulative, same as they are exposed
+    Histogram {
+        buckets: Vec<u64>,
+        sum: f64,
+        count: u64,
+    },
+}
+
+/// MetricFamily is a named metric, every combination of label values is a separate sample
+#[derive(Debug)]
+pub struct MetricFamily {
+    name: String,
+    help: String,
+    kind: MetricKind,
+    label_names: Vec<String>,
+    samples: Mutex<BTreeMap<Vec<String>, Sample>>,
+}
+
+impl MetricFamily {
+    fn update(&self, labels: &[&str], f: impl FnOnce(&mut Sample)) {
+        debug_assert_eq!(
+            labels.len(),
+            self.label_names.len(),
+            "labels of {}",
+            self.name
+        );
+        let key = labels.iter().map(|label| label.to_string()).collect();
+        let mut samples = self.samples.lock().unwrap();
+        let sample = samples.entry(key).or_insert_with(|| match &self.kind {
+            MetricKind::Histogram(bounds) => Sample::Histogram {
+                buckets: vec![0; bounds.len()],
+                sum: 0.0,
+                count: 0,
+            },
+            _ => Sample::Value(0.0),
+        });
+        f(sample)
+    }
+
+    pub fn inc_by(&self, labels: &[&str], value: f64) {
+        self.update(labels, |sample| {
+            if let Sample::Value(current) = sample {
+                *current += value;
+            }
+        })
+    }
+
+    pub fn inc(&self, labels: &[&str]) {
+        self.inc_by(labels, 1.0)
+    }
+
+    pub fn set(&self, labels: &[&str], value: f64) {
+        self.update(labels, |sample| {
+            if let Sample::Value(current) = sample {
+                *current = value;
+            }
+        })
+    }
+
+    pub fn observe(&self, labels: &[&str], value: f64) {
+        let MetricKind::Histogram(bounds) = &self.kind else {
+            return;
+        };
+        self.update(labels, |sample| {
+            if let Sample::Histogram {
+                buckets,
+                sum,
+                count,
+            } = sample
+            {
+                for (bucket, bound) in buckets.iter_mut().zip(bounds) {
+                    if value <= *bound {
+                        *bucket += 1;
+                    }
+                }
+                *sum += value;
+                *count += 1;
+            }
+        })
+    }
+
+    /// replace all samples, used by gauges recomputed from scratch so stale labels disappear
+    pub fn replace(&self, values: impl IntoIterator<Item = (Vec<String>, f64)>) {
+        let mut samples = self.samples.lock().unwrap();
+        samples.clear();
+        for (labels, value) in values {
+            samples.insert(labels, Sample::Value(value));
+        }
+    }
+
+    /// get return value of counter or gauge, number of observations of histogram
+    pub fn get(&self, labels: &[&str]) -> Option<f64> {
+        let key: Vec<String> = labels.iter().map(|label| label.to_string()).collect();
+        self.samples
+            .lock()
+            .unwrap()
+            .get(&key)
+            .map(|sample| match sample {
+                Sample::Value(value) => *value,
+                Sample::Histogram { count, .. } => *count as f64,
+            })
+    }
+
+    fn label_pairs(&self, values: &[String], extra: Option<(&str, &str)>) -> String {
+        let pairs: Vec<String> = self
+            .label_names
+            .iter()
+            .map(String::as_str)
+            .zip(values.iter().map(String::as_str))
+            .chain(extra)
+            .map(|(name, value)| format!("{name}=\"{}\"", escape_label(value)))
+            .collect();
+        if pairs.is_empty() {
+            return String::new();
+        }
+        format!("{{{}}}", pairs.join(","))
+    }
+
+    fn render(&self, out: &mut String) -> std::fmt::Result {
+        let kind = match self.kind {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+            MetricKind::Histogram(_) => "histogram",
+        };
+        writeln!(out, "# HELP {} {}", self.name, escape_help(&self.help))?;
+        writeln!(out, "# TYPE {} {kind}", self.name)?;
+
+        let name = &self.name;
+        for (values, sample) in self.samples.lock().unwrap().iter() {
+            match sample {
+                Sample::Value(value) => {
+                    writeln!(out, "{name}{} {value}", self.label_pairs(values, None))?
+                }
+                Sample::Histogram {
+                    buckets,
+                    sum,
+                    count,
+                } => {
+                    let MetricKind::Histogram(bounds) = &self.kind else {
+                        continue;
+                    };
+                    for (bucket, bound) in buckets.iter().zip(bounds) {
+                        let le = bound.to_string();
+                        let labels = self.label_pairs(values, Some(("le", &le)));
+                        writeln!(out, "{name}_bucket{labels} {bucket}")?;
+                    }
+                    let labels = self.label_pairs(values, Some(("le", "+Inf")));
+                    writeln!(out, "{name}_bucket{labels} {count}")?;
+                    let labels = self.label_pairs(values, None);
+                    writeln!(out, "{name}_sum{labels} {sum}")?;
+                    writeln!(out, "{name}_count{labels} {count}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label(value: &str) -> String {
+    escape_help(value).replace('"', "\\\"")
+}
+
+/// Registry own metric families and render them in prometheus text format
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    families: Arc<Mutex<Vec<Arc<MetricFamily>>>>,
+}
+
+impl Registry {
+    pub fn register(
+        &self,
+        name: &str,
+        help: &str,
+        kind: MetricKind,
+        label_names: &[&str],
+    ) -> Arc<MetricFamily> {
+        let family = Arc::new(MetricFamily {
+            name: name.to_string(),
+            help: help.to_string(),
+            kind,
+            label_names: label_names.iter().map(|name| name.to_string()).collect(),
+            samples: Mutex::new(BTreeMap::new()),
+        });
+        self.families.lock().unwrap().push(family.clone());
+        family
+    }
+
+    pub fn counter(&self, name: &str, help: &str, label_names: &[&str]) -> Arc<MetricFamily> {
+        self.register(name, help, MetricKind::Counter, label_names)
+    }
+
+    pub fn gauge(&self, name: &str, help: &str, label_names: &[&str]) -> Arc<MetricFamily> {
+        self.register(name, help, MetricKind::Gauge, label_names)
+    }
+
+    pub fn histogram(
+        &self,
+        name: &str,
+        help: &str,
+        label_names: &[&str],
+        buckets: &[f64],
+    ) -> Arc<MetricFamily> {
+        self.register(
+            name,
+            help,
+            MetricKind::Histogram(buckets.to_vec()),
+            label_names,
+        )
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for family in self.families.lock().unwrap().iter() {
+            //writing to string never fail
+            let _ = family.render(&mut out);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let registry = Registry::default();
+        let requests = registry.counter("requests_total", "handled requests", &["route"]);
+        let backlog = registry.gauge("backlog", "pending \"batches\"\nof node", &["node"]);
+        let latency = registry.histogram("latency_seconds", "latency", &[], &[0.1, 1.0]);
+
+        requests.inc(&["/job"]);
+        requests.inc_by(&["/job"], 2.0);
+        backlog.set(&["a\"b"], 4.0);
+        latency.observe(&[], 0.05);
+        latency.observe(&[], 0.5);
+        latency.observe(&[], 3.0);
+
+        assert_eq!(requests.get(&["/job"]), Some(3.0));
+        assert_eq!(latency.get(&[]), Some(3.0));
+        assert_eq!(
+            registry.render(),
+            r#"# HELP requests_total handled requests
+# TYPE requests_total counter
+requests_total{route="/job"} 3
+# HELP backlog pending "batches"\nof node
+# TYPE backlog gauge
+backlog{node="a\"b"} 4
+# HELP latency_seconds latency
+# TYPE latency_seconds histogram
+latency_seconds_bucket{le="0.1"} 1
+latency_seconds_bucket{le="1"} 2
+latency_seconds_bucket{le="+Inf"} 3
+latency_seconds_sum 3.55
+latency_seconds_count 3
+"#
+        );
+
+        backlog.replace([(vec!["c".to_string()], 1.0)]);
+        assert_eq!(backlog.get(&["a\"b"]), None);
+        assert_eq!(backlog.get(&["c"]), Some(1.0));
+    }
+}
@@ -1,4 +1,6 @@
+pub mod health;
 pub mod k8s_helper;
+pub mod metrics;
 pub mod sizefmt;
 
 use core::fmt;